@@ -3,7 +3,7 @@ use anyhow::Result;
 use rsa::{RsaPrivateKey, RsaPublicKey};
 
 /// A trait for cryptographic functions needed within the atSign library.
-pub trait CryptoFunctions {
+pub trait CryptoFunctions: Send + Sync {
     // ----- Base64 -----
     /// Encode a byte array to base64 String.
     fn base64_encode(&self, data: &[u8]) -> String;
@@ -186,7 +186,7 @@ mod test {
         let signature = subject.rsa_sign(data, &rsa_private_key).unwrap();
         let result = subject.rsa_verify(data, &signature, &rsa_public_key);
         assert!(result.is_ok());
-        assert!(result.unwrap() == true); // Just being explicit
+        assert!(result.unwrap());
     }
 
     #[test]
@@ -198,7 +198,7 @@ mod test {
         let signature = subject.rsa_sign(data, &rsa_private_key).unwrap();
         let result = subject.rsa_verify(data, &signature, &rsa_public_key);
         assert!(result.is_ok());
-        assert!(result.unwrap() == false); // Just being explicit
+        assert!(!result.unwrap());
     }
 
     #[test]
@@ -15,6 +15,8 @@ pub struct AtChops {
     rsa_public_key: RsaPublicKey,
     /// Used for authenticating with AtServer by signing challenges.
     pkam_private_key: RsaPrivateKey,
+    /// Base64 encoded AES key used to encrypt self keys, which only we can read.
+    encoded_self_encryption_key: String,
     //? Need to check that some of the functions should return String::from_utf8() instead of String created from base64encode
     //? In the future it probably makes sense to get rid of public keys entirely as the private keys are enough to derive them
 }
@@ -54,6 +56,7 @@ impl AtChops {
             rsa_private_key,
             rsa_public_key,
             pkam_private_key,
+            encoded_self_encryption_key: encoded_self_encryption_key.to_owned(),
         })
     }
 
@@ -193,6 +196,16 @@ impl AtChops {
         Ok(String::from_utf8(unpadded_data)?)
     }
 
+    /// Encrypt data only we can read (e.g. the value of a self key) with the self encryption key.
+    pub fn encrypt_data_with_self_encryption_key(&self, data: &str) -> Result<String> {
+        self.encrypt_data_with_shared_symmetric_key(&self.encoded_self_encryption_key, data)
+    }
+
+    /// Decrypt data encrypted with the self encryption key.
+    pub fn decrypt_data_with_self_encryption_key(&self, data: &str) -> Result<String> {
+        self.decrypt_data_with_shared_symmetric_key(&self.encoded_self_encryption_key, data)
+    }
+
     fn pkcs7_pad(data: &[u8], block_size: usize) -> Vec<u8> {
         let padding_len = block_size - (data.len() % block_size);
         let mut padded_data = Vec::from(data);
@@ -235,13 +248,13 @@ mod test {
 
     fn create_subject() -> Result<AtChops> {
         let crypto_service = Box::new(DefaultCryptoFunctions::new());
-        Ok(AtChops::new(
+        AtChops::new(
             crypto_service,
             SELF_ENCRYPTION_KEY_ENCODED,
             // Using PKAM key just for testing
             PKAM_KEY_ENCRYPTED_AND_ENCODED,
             PKAM_KEY_ENCRYPTED_AND_ENCODED,
-        )?)
+        )
     }
 
     #[test]
@@ -266,4 +279,17 @@ mod test {
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 44)
     }
+
+    #[test]
+    fn test_encrypt_and_decrypt_with_self_encryption_key() {
+        let subject = create_subject().unwrap();
+        let encrypted = subject
+            .encrypt_data_with_self_encryption_key("secret")
+            .unwrap();
+        assert_ne!(encrypted, "secret");
+        let decrypted = subject
+            .decrypt_data_with_self_encryption_key(&encrypted)
+            .unwrap();
+        assert_eq!(decrypted, "secret");
+    }
 }
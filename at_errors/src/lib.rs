@@ -4,87 +4,378 @@ use std::result;
 
 pub type Result<T> = result::Result<T, AtError>;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub enum AtError {
+    /// The atServer answered a command with an error.
+    Server(ServerError),
+    /// The atServer answered with something the client did not expect.
+    UnexpectedResponse(String),
+    UnknownAtClientException(String),
+    /// The request is valid but this client does not support it yet.
+    Unsupported(String),
+    IoError(std::io::Error),
+    Timeout(std::io::Error),
+    Utf8Error(std::str::Utf8Error),
+    JsonError(serde_json::Error),
+    /// Signing, encrypting or decrypting failed.
+    CryptoError(Box<dyn Error + Send + Sync>),
+}
+
+impl AtError {
+    /// Creates a server error with the given code and no message.
+    pub fn from_code(code: &str) -> AtError {
+        AtError::Server(ServerError::new(
+            ServerErrorCode::from_code(code),
+            String::new(),
+        ))
+    }
+
+    /// Parses the text following `error:` in a server response.
+    ///
+    /// Understands both `AT0015-key not found` and the JSON form sent by newer atServers,
+    /// `{"errorCode":"AT0015","errorDescription":"key not found"}`.
+    pub fn from_server_error(error: &str) -> AtError {
+        let error = error.trim();
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(error) {
+            if let Some(code) = json["errorCode"].as_str() {
+                let message = json["errorDescription"].as_str().unwrap_or_default();
+                return AtError::Server(ServerError::new(
+                    ServerErrorCode::from_code(code),
+                    message.to_owned(),
+                ));
+            }
+        }
+        let code_end = error
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(error.len());
+        let (code, message) = error.split_at(code_end);
+        let message = message.trim_start_matches(['-', ':', ' ']);
+        AtError::Server(ServerError::new(
+            ServerErrorCode::from_code(code),
+            message.to_owned(),
+        ))
+    }
+
+    /// Records the key the failed command was about, if this is a server error.
+    pub fn with_key(self, key: impl ToString) -> AtError {
+        match self {
+            AtError::Server(server_error) => AtError::Server(ServerError {
+                key: Some(key.to_string()),
+                ..server_error
+            }),
+            error => error,
+        }
+    }
+
+    /// The code sent by the atServer, if this is a server error.
+    pub fn server_code(&self) -> Option<&ServerErrorCode> {
+        match self {
+            AtError::Server(server_error) => Some(&server_error.code),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request might succeed later, e.g. on a new connection or once the server is less busy.
+    ///
+    /// IO errors count as retryable unless they were caused by invalid input or data.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AtError::Timeout(_) => true,
+            AtError::IoError(error) => !matches!(
+                error.kind(),
+                std::io::ErrorKind::InvalidInput
+                    | std::io::ErrorKind::InvalidData
+                    | std::io::ErrorKind::Unsupported
+            ),
+            AtError::Server(server_error) => matches!(
+                server_error.code,
+                ServerErrorCode::SocketError
+                    | ServerErrorCode::OutboundConnectionLimitExceeded
+                    | ServerErrorCode::InboundConnectionLimitExceeded
+                    | ServerErrorCode::ConnectionException
+                    | ServerErrorCode::SecureSocketException
+                    | ServerErrorCode::UnableToConnectToSecondary
+                    | ServerErrorCode::ResponseTimeout
+                    | ServerErrorCode::ServerIsPaused
+            ),
+            _ => false,
+        }
+    }
+
+    /// Whether retrying can't help without changing the request. The opposite of `is_retryable`.
+    pub fn is_permanent(&self) -> bool {
+        !self.is_retryable()
+    }
+
+    /// Whether the atServer refused to authenticate the client or authorise the request.
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(
+            self.server_code(),
+            Some(
+                ServerErrorCode::ClientAuthenticationFailed
+                    | ServerErrorCode::UnauthorizedClient
+                    | ServerErrorCode::HandshakeFailure
+                    | ServerErrorCode::EnrollmentDenied
+                    | ServerErrorCode::EnrollmentRevoked
+            )
+        )
+    }
+
+    /// Whether the requested key, or the atServer of the requested atSign, does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self.server_code(),
+            Some(ServerErrorCode::KeyNotFound | ServerErrorCode::SecondaryNotFound)
+        )
+    }
+}
+
+impl fmt::Display for AtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtError::Server(server_error) => write!(f, "{}", server_error),
+            AtError::UnexpectedResponse(response) => {
+                write!(f, "Unexpected response from server: {}", response)
+            }
+            AtError::UnknownAtClientException(message) => {
+                write!(f, "AT0014: Unknown AtClient exception: {}", message)
+            }
+            AtError::Unsupported(message) => write!(f, "Not supported yet: {}", message),
+            AtError::IoError(error) => write!(f, "IO error: {}", error),
+            AtError::Timeout(error) => write!(f, "Timed out: {}", error),
+            AtError::Utf8Error(error) => write!(f, "Invalid UTF-8: {}", error),
+            AtError::JsonError(error) => write!(f, "Invalid JSON: {}", error),
+            AtError::CryptoError(error) => write!(f, "Crypto error: {}", error),
+        }
+    }
+}
+
+impl Error for AtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtError::IoError(error) | AtError::Timeout(error) => Some(error),
+            AtError::Utf8Error(error) => Some(error),
+            AtError::JsonError(error) => Some(error),
+            AtError::CryptoError(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AtError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::TimedOut => AtError::Timeout(error),
+            _ => AtError::IoError(error),
+        }
+    }
+}
+
+impl From<std::str::Utf8Error> for AtError {
+    fn from(error: std::str::Utf8Error) -> Self {
+        AtError::Utf8Error(error)
+    }
+}
+
+impl From<serde_json::Error> for AtError {
+    fn from(error: serde_json::Error) -> Self {
+        AtError::JsonError(error)
+    }
+}
+
+/// An error reported by the atServer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    pub code: ServerErrorCode,
+    /// The text the server sent after the code.
+    pub message: String,
+    /// The key the failed command was about, if known.
+    pub key: Option<String>,
+}
+
+impl ServerError {
+    pub fn new(code: ServerErrorCode, message: String) -> Self {
+        Self {
+            code,
+            message,
+            key: None,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code)?;
+        if !self.message.is_empty() {
+            write!(f, " ({})", self.message)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, " for key {}", key)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ServerError {}
+
+/// The error codes of the atProtocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerErrorCode {
     ServerException,
     DatastoreException,
     InvalidSyntax,
     SocketError,
     BufferLimitExceeded,
+    OutboundConnectionLimitExceeded,
+    SecondaryNotFound,
     HandshakeFailure,
     UnauthorizedClient,
     InternalServerError,
     InternalServerException,
     InboundConnectionLimitExceeded,
-    ClientAuthenticationFailed,
     ConnectionException,
-    UnknownAtClientException(String),
+    UnknownAtClientException,
     KeyNotFound,
+    SecureSocketException,
+    InvalidAtKey,
+    InvalidAtSign,
+    InvalidValueType,
+    InvalidRequest,
     UnableToConnectToSecondary,
-    IoError(String),
+    IllegalArgument,
+    ResponseTimeout,
+    ServerIsPaused,
+    EnrollmentException,
+    InvalidEnrollmentId,
+    EnrollmentDenied,
+    EnrollmentPending,
+    EnrollmentRevoked,
+    MaxEnrollmentRequestsExceeded,
+    ClientAuthenticationFailed,
+    /// A code this client does not know about.
+    Unknown(String),
 }
 
-impl AtError {
-    pub fn from_code(code: &str) -> AtError {
+impl ServerErrorCode {
+    pub fn from_code(code: &str) -> ServerErrorCode {
         match code {
-            "AT0001" => AtError::ServerException,
-            "AT0002" => AtError::DatastoreException,
-            "AT0003" => AtError::InvalidSyntax,
-            "AT0004" => AtError::SocketError,
-            "AT0005" => AtError::BufferLimitExceeded,
-            "AT0008" => AtError::HandshakeFailure,
-            "AT0009" => AtError::UnauthorizedClient,
-            "AT0010" => AtError::InternalServerError,
-            "AT0011" => AtError::InternalServerException,
-            "AT0012" => AtError::InboundConnectionLimitExceeded,
-            "AT0401" => AtError::ClientAuthenticationFailed,
-            "AT0013" => AtError::ConnectionException,
-            "AT0014" => {
-                AtError::UnknownAtClientException(String::from("Unknown AtClient exception"))
-            }
-            "AT0015" => AtError::KeyNotFound,
-            "AT0021" => AtError::UnableToConnectToSecondary,
-            _ => AtError::UnknownAtClientException(String::from("Unknown AtClient exception")),
+            "AT0001" => ServerErrorCode::ServerException,
+            "AT0002" => ServerErrorCode::DatastoreException,
+            "AT0003" => ServerErrorCode::InvalidSyntax,
+            "AT0004" => ServerErrorCode::SocketError,
+            "AT0005" => ServerErrorCode::BufferLimitExceeded,
+            "AT0006" => ServerErrorCode::OutboundConnectionLimitExceeded,
+            "AT0007" => ServerErrorCode::SecondaryNotFound,
+            "AT0008" => ServerErrorCode::HandshakeFailure,
+            "AT0009" => ServerErrorCode::UnauthorizedClient,
+            "AT0010" => ServerErrorCode::InternalServerError,
+            "AT0011" => ServerErrorCode::InternalServerException,
+            "AT0012" => ServerErrorCode::InboundConnectionLimitExceeded,
+            "AT0013" => ServerErrorCode::ConnectionException,
+            "AT0014" => ServerErrorCode::UnknownAtClientException,
+            "AT0015" => ServerErrorCode::KeyNotFound,
+            "AT0016" => ServerErrorCode::SecureSocketException,
+            "AT0017" => ServerErrorCode::InvalidAtKey,
+            "AT0018" => ServerErrorCode::InvalidAtSign,
+            "AT0019" => ServerErrorCode::InvalidValueType,
+            "AT0020" => ServerErrorCode::InvalidRequest,
+            "AT0021" => ServerErrorCode::UnableToConnectToSecondary,
+            "AT0022" => ServerErrorCode::IllegalArgument,
+            "AT0023" => ServerErrorCode::ResponseTimeout,
+            "AT0024" => ServerErrorCode::ServerIsPaused,
+            "AT0025" => ServerErrorCode::EnrollmentException,
+            "AT0026" => ServerErrorCode::InvalidEnrollmentId,
+            "AT0027" => ServerErrorCode::EnrollmentDenied,
+            "AT0028" => ServerErrorCode::EnrollmentPending,
+            "AT0029" => ServerErrorCode::EnrollmentRevoked,
+            "AT0030" => ServerErrorCode::MaxEnrollmentRequestsExceeded,
+            "AT0401" => ServerErrorCode::ClientAuthenticationFailed,
+            _ => ServerErrorCode::Unknown(code.to_owned()),
         }
     }
-}
 
-impl fmt::Display for AtError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    pub fn code(&self) -> &str {
         match self {
-            AtError::ServerException => write!(f, "AT0001: Server exception"),
-            AtError::DatastoreException => write!(f, "AT0002: Datastore exception"),
-            AtError::InvalidSyntax => write!(f, "AT0003: Invalid syntax"),
-            AtError::SocketError => write!(f, "AT0004: Socket error"),
-            AtError::BufferLimitExceeded => write!(f, "AT0005: Buffer limit exceeded"),
-            AtError::HandshakeFailure => write!(f, "AT0008: Handshake failure"),
-            AtError::UnauthorizedClient => write!(f, "AT0009: Unauthorized client in the request"),
-            AtError::InternalServerError => write!(f, "AT0010: Internal server error"),
-            AtError::InternalServerException => write!(f, "AT0011: Internal server exception"),
-            AtError::InboundConnectionLimitExceeded => {
-                write!(f, "AT0012: Inbound connection limit exceeded")
-            }
-            AtError::ClientAuthenticationFailed => {
-                write!(f, "AT0401: Client authentication failed")
-            }
-            AtError::ConnectionException => write!(f, "AT0013: Connection exception"),
-            AtError::UnknownAtClientException(message) => {
-                write!(f, "AT0014: Unknown AtClient exception: {}", message)
+            ServerErrorCode::ServerException => "AT0001",
+            ServerErrorCode::DatastoreException => "AT0002",
+            ServerErrorCode::InvalidSyntax => "AT0003",
+            ServerErrorCode::SocketError => "AT0004",
+            ServerErrorCode::BufferLimitExceeded => "AT0005",
+            ServerErrorCode::OutboundConnectionLimitExceeded => "AT0006",
+            ServerErrorCode::SecondaryNotFound => "AT0007",
+            ServerErrorCode::HandshakeFailure => "AT0008",
+            ServerErrorCode::UnauthorizedClient => "AT0009",
+            ServerErrorCode::InternalServerError => "AT0010",
+            ServerErrorCode::InternalServerException => "AT0011",
+            ServerErrorCode::InboundConnectionLimitExceeded => "AT0012",
+            ServerErrorCode::ConnectionException => "AT0013",
+            ServerErrorCode::UnknownAtClientException => "AT0014",
+            ServerErrorCode::KeyNotFound => "AT0015",
+            ServerErrorCode::SecureSocketException => "AT0016",
+            ServerErrorCode::InvalidAtKey => "AT0017",
+            ServerErrorCode::InvalidAtSign => "AT0018",
+            ServerErrorCode::InvalidValueType => "AT0019",
+            ServerErrorCode::InvalidRequest => "AT0020",
+            ServerErrorCode::UnableToConnectToSecondary => "AT0021",
+            ServerErrorCode::IllegalArgument => "AT0022",
+            ServerErrorCode::ResponseTimeout => "AT0023",
+            ServerErrorCode::ServerIsPaused => "AT0024",
+            ServerErrorCode::EnrollmentException => "AT0025",
+            ServerErrorCode::InvalidEnrollmentId => "AT0026",
+            ServerErrorCode::EnrollmentDenied => "AT0027",
+            ServerErrorCode::EnrollmentPending => "AT0028",
+            ServerErrorCode::EnrollmentRevoked => "AT0029",
+            ServerErrorCode::MaxEnrollmentRequestsExceeded => "AT0030",
+            ServerErrorCode::ClientAuthenticationFailed => "AT0401",
+            ServerErrorCode::Unknown(code) => code,
+        }
+    }
+
+    fn description(&self) -> &str {
+        match self {
+            ServerErrorCode::ServerException => "Server exception",
+            ServerErrorCode::DatastoreException => "Datastore exception",
+            ServerErrorCode::InvalidSyntax => "Invalid syntax",
+            ServerErrorCode::SocketError => "Socket error",
+            ServerErrorCode::BufferLimitExceeded => "Buffer limit exceeded",
+            ServerErrorCode::OutboundConnectionLimitExceeded => {
+                "Outbound connection limit exceeded"
             }
-            AtError::KeyNotFound => write!(f, "AT0015: Key not found"),
-            AtError::UnableToConnectToSecondary => {
-                write!(f, "AT0021: Unable to connect to secondary")
+            ServerErrorCode::SecondaryNotFound => "No secondary found",
+            ServerErrorCode::HandshakeFailure => "Handshake failure",
+            ServerErrorCode::UnauthorizedClient => "Unauthorized client in the request",
+            ServerErrorCode::InternalServerError => "Internal server error",
+            ServerErrorCode::InternalServerException => "Internal server exception",
+            ServerErrorCode::InboundConnectionLimitExceeded => "Inbound connection limit exceeded",
+            ServerErrorCode::ConnectionException => "Connection exception",
+            ServerErrorCode::UnknownAtClientException => "Unknown AtClient exception",
+            ServerErrorCode::KeyNotFound => "Key not found",
+            ServerErrorCode::SecureSocketException => "Secure socket exception",
+            ServerErrorCode::InvalidAtKey => "Invalid atKey",
+            ServerErrorCode::InvalidAtSign => "Invalid atSign",
+            ServerErrorCode::InvalidValueType => "Invalid value type",
+            ServerErrorCode::InvalidRequest => "Invalid request",
+            ServerErrorCode::UnableToConnectToSecondary => "Unable to connect to secondary",
+            ServerErrorCode::IllegalArgument => "Illegal arguments",
+            ServerErrorCode::ResponseTimeout => "Timeout waiting for response",
+            ServerErrorCode::ServerIsPaused => "Server is paused",
+            ServerErrorCode::EnrollmentException => "Enrollment exception",
+            ServerErrorCode::InvalidEnrollmentId => "Invalid enrollment id",
+            ServerErrorCode::EnrollmentDenied => "Enrollment request denied",
+            ServerErrorCode::EnrollmentPending => "Enrollment request pending",
+            ServerErrorCode::EnrollmentRevoked => "Enrollment revoked",
+            ServerErrorCode::MaxEnrollmentRequestsExceeded => {
+                "Maximum enrollment requests exceeded"
             }
-            AtError::IoError(message) => write!(f, "IO error: {}", message),
+            ServerErrorCode::ClientAuthenticationFailed => "Client authentication failed",
+            ServerErrorCode::Unknown(_) => "Unknown error code",
         }
     }
 }
 
-impl Error for AtError {}
-
-impl From<std::io::Error> for AtError {
-    fn from(error: std::io::Error) -> Self {
-        AtError::IoError(error.to_string())
+impl fmt::Display for ServerErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.description())
     }
 }
 
@@ -96,7 +387,17 @@ mod test {
     #[test]
     fn test_error_from_code() {
         let error = AtError::from_code("AT0001");
-        assert_eq!(error, AtError::ServerException);
+        assert_eq!(error.server_code(), Some(&ServerErrorCode::ServerException));
+        let error = AtError::from_code("AT0026");
+        assert_eq!(
+            error.server_code(),
+            Some(&ServerErrorCode::InvalidEnrollmentId)
+        );
+        let error = AtError::from_code("AT9999");
+        assert_eq!(
+            error.server_code(),
+            Some(&ServerErrorCode::Unknown(String::from("AT9999")))
+        );
     }
 
     #[test]
@@ -104,4 +405,73 @@ mod test {
         let error = AtError::from_code("AT0001");
         assert_eq!(error.to_string(), "AT0001: Server exception");
     }
+
+    #[test]
+    fn test_from_server_error() {
+        let error = AtError::from_server_error(
+            "AT0015-key not found : @bob:phone@alice does not exist in keystore",
+        );
+        match &error {
+            AtError::Server(server_error) => {
+                assert_eq!(server_error.code, ServerErrorCode::KeyNotFound);
+                assert_eq!(
+                    server_error.message,
+                    "key not found : @bob:phone@alice does not exist in keystore"
+                );
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+        let error = error.with_key("@bob:phone@alice");
+        assert_eq!(
+            error.to_string(),
+            "AT0015: Key not found (key not found : @bob:phone@alice does not exist in keystore) for key @bob:phone@alice"
+        );
+    }
+
+    #[test]
+    fn test_from_server_error_json() {
+        let error = AtError::from_server_error(
+            r#"{"errorCode":"AT0003","errorDescription":"Invalid syntax"}"#,
+        );
+        match error {
+            AtError::Server(server_error) => {
+                assert_eq!(server_error.code, ServerErrorCode::InvalidSyntax);
+                assert_eq!(server_error.message, "Invalid syntax");
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classification() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(AtError::from(io_error).is_retryable());
+        let io_error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "bad host");
+        assert!(AtError::from(io_error).is_permanent());
+        let io_error = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        assert!(AtError::from(io_error).is_retryable());
+        for code in ["AT0004", "AT0012", "AT0021"] {
+            assert!(AtError::from_code(code).is_retryable(), "{}", code);
+        }
+
+        let error = AtError::from_code("AT0401");
+        assert!(error.is_auth_failure());
+        assert!(error.is_permanent());
+        assert!(!error.is_not_found());
+
+        let error = AtError::from_code("AT0015");
+        assert!(error.is_not_found());
+        assert!(!error.is_auth_failure());
+        assert!(!AtError::UnexpectedResponse(String::new()).is_retryable());
+    }
+
+    #[test]
+    fn test_timeout_from_io_error() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::TimedOut, "read timed out");
+        let error = AtError::from(io_error);
+        assert!(matches!(error, AtError::Timeout(_)));
+        assert_eq!(error.source().unwrap().to_string(), "read timed out");
+        let io_error = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe");
+        assert!(matches!(AtError::from(io_error), AtError::IoError(_)));
+    }
 }
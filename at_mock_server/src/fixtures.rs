@@ -0,0 +1,50 @@
+//! Key files for test atSigns. Generated for these tests only; never used with a real atServer.
+
+use at_chops::{default_crypto_functions::DefaultCryptoFunctions, AtChops};
+use at_secrets::AtSecrets;
+use at_sign::AtSign;
+
+/// A test atSign with its `.atKeys` file and plain text public keys.
+pub struct TestAtSign {
+    pub at_sign: AtSign,
+    pub at_keys: &'static str,
+    /// Base64 encoded DER public key used to verify PKAM signatures.
+    pub pkam_public_key: &'static str,
+    /// Base64 encoded DER public key others use to encrypt data for this atSign (`public:publickey@<atSign>`).
+    pub encrypt_public_key: &'static str,
+}
+
+impl TestAtSign {
+    pub fn secrets(&self) -> AtSecrets {
+        AtSecrets::from_file(self.at_keys).expect("Invalid fixture key file")
+    }
+
+    pub fn at_chops(&self) -> AtChops {
+        let secrets = self.secrets();
+        AtChops::new(
+            Box::new(DefaultCryptoFunctions::new()),
+            &secrets.encoded_self_encryption_key,
+            &secrets.encoded_and_encrypted_encrypt_private_key,
+            &secrets.encoded_and_encrypted_pkam_private_key,
+        )
+        .expect("Invalid fixture keys")
+    }
+}
+
+pub fn alice() -> TestAtSign {
+    TestAtSign {
+        at_sign: AtSign::new(String::from("alice")),
+        at_keys: include_str!("../fixtures/alice_key.atKeys"),
+        pkam_public_key: include_str!("../fixtures/alice_pkam_public_key.txt").trim(),
+        encrypt_public_key: include_str!("../fixtures/alice_encrypt_public_key.txt").trim(),
+    }
+}
+
+pub fn bob() -> TestAtSign {
+    TestAtSign {
+        at_sign: AtSign::new(String::from("bob")),
+        at_keys: include_str!("../fixtures/bob_key.atKeys"),
+        pkam_public_key: include_str!("../fixtures/bob_pkam_public_key.txt").trim(),
+        encrypt_public_key: include_str!("../fixtures/bob_encrypt_public_key.txt").trim(),
+    }
+}
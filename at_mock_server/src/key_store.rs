@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+/// A value held by the mock atServer together with the options it was last updated with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoredRecord {
+    pub value: String,
+    /// Time to live in milliseconds.
+    pub ttl: Option<u64>,
+    /// Time to birth in milliseconds.
+    pub ttb: Option<u64>,
+    /// Time to refresh in milliseconds.
+    pub ttr: Option<i64>,
+    /// Cascade delete.
+    pub ccd: Option<bool>,
+    /// When a cached copy needs refreshing, as sent in the metadata, e.g. `2024-02-06 11:15:30.123Z`.
+    pub refresh_at: Option<String>,
+}
+
+impl StoredRecord {
+    pub fn new(value: String) -> Self {
+        Self {
+            value,
+            ..Default::default()
+        }
+    }
+}
+
+/// An entry in the commit log: the key and whether it was updated (`+`) or deleted (`-`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    pub commit_id: u64,
+    pub key: String,
+    pub operation: char,
+}
+
+/// The keys stored by a `MockAtServer`.
+///
+/// Keys are stored exactly as the client sent them (e.g. `@bob:phone.wavi@alice`),
+/// so every atSign served by the mock shares one store and one commit log.
+#[derive(Debug, Default)]
+pub struct KeyStore {
+    records: BTreeMap<String, StoredRecord>,
+    commit_log: Vec<Commit>,
+}
+
+impl KeyStore {
+    /// Creates or replaces the record and returns the new commit id.
+    pub fn put(&mut self, key: &str, record: StoredRecord) -> u64 {
+        self.records.insert(key.to_owned(), record);
+        self.commit(key, '+')
+    }
+
+    fn commit(&mut self, key: &str, operation: char) -> u64 {
+        let commit_id = self.last_commit_id() + 1;
+        self.commit_log.push(Commit {
+            commit_id,
+            key: key.to_owned(),
+            operation,
+        });
+        commit_id
+    }
+
+    /// The id of the latest commit, or 0 if there hasn't been one.
+    pub fn last_commit_id(&self) -> u64 {
+        self.commit_log.last().map_or(0, |commit| commit.commit_id)
+    }
+
+    /// Every commit after `commit_id`, oldest first. Unlike the real atServer the log is never compacted.
+    pub fn commits_after(&self, commit_id: u64) -> impl Iterator<Item = &Commit> {
+        self.commit_log
+            .iter()
+            .filter(move |commit| commit.commit_id > commit_id)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&StoredRecord> {
+        self.records.get(key)
+    }
+
+    /// Removes the record and returns the new commit id, or `None` if the key didn't exist.
+    pub fn remove(&mut self, key: &str) -> Option<u64> {
+        self.records.remove(key)?;
+        Some(self.commit(key, '-'))
+    }
+
+    /// All keys in lexicographic order.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.records.keys()
+    }
+}
+
+/// The atSign which owns the key, without the `@`.
+pub(crate) fn key_owner(key: &str) -> &str {
+    key.rsplit_once('@').map_or("", |(_, owner)| owner)
+}
+
+/// The atSign the key is shared with, without the `@`, for keys like `[cached:]@bob:phone@alice`.
+pub(crate) fn key_shared_with(key: &str) -> Option<&str> {
+    let key = key.strip_prefix("cached:").unwrap_or(key);
+    let (shared_with, _) = key.strip_prefix('@')?.split_once(':')?;
+    Some(shared_with)
+}
+
+/// Hidden keys are only returned by scan with `showhidden:true`.
+pub(crate) fn is_hidden(key: &str) -> bool {
+    let key = key.strip_prefix("cached:").unwrap_or(key);
+    let record = match key_shared_with(key) {
+        Some(_) => key.split_once(':').map_or(key, |(_, rest)| rest),
+        None => key.strip_prefix("public:").unwrap_or(key),
+    };
+    record.starts_with('_') || key.starts_with("privatekey:")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_put_get_remove() {
+        let mut store = KeyStore::default();
+        assert_eq!(
+            store.put("phone@alice", StoredRecord::new(String::from("1"))),
+            1
+        );
+        assert_eq!(
+            store.put("phone@alice", StoredRecord::new(String::from("2"))),
+            2
+        );
+        assert_eq!(store.get("phone@alice").unwrap().value, "2");
+        assert_eq!(store.remove("phone@alice"), Some(3));
+        assert_eq!(store.remove("phone@alice"), None);
+        assert!(store.get("phone@alice").is_none());
+
+        let operations: Vec<char> = store
+            .commits_after(1)
+            .map(|commit| commit.operation)
+            .collect();
+        assert_eq!(operations, vec!['+', '-']);
+        assert_eq!(store.last_commit_id(), 3);
+    }
+
+    #[test]
+    fn test_key_parts() {
+        assert_eq!(key_owner("@bob:phone.wavi@alice"), "alice");
+        assert_eq!(key_shared_with("@bob:phone.wavi@alice"), Some("bob"));
+        assert_eq!(key_shared_with("cached:@bob:phone.wavi@alice"), Some("bob"));
+        assert_eq!(key_shared_with("public:phone.wavi@alice"), None);
+    }
+
+    #[test]
+    fn test_is_hidden() {
+        assert!(is_hidden("_latestnotificationid.wavi@alice"));
+        assert!(is_hidden("@bob:_secret@alice"));
+        assert!(is_hidden("privatekey:at_pkam_publickey"));
+        assert!(!is_hidden("public:phone.wavi@alice"));
+        assert!(!is_hidden("@bob:phone.wavi@alice"));
+    }
+}
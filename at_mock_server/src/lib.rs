@@ -0,0 +1,14 @@
+//! An in-process atServer and root server for tests which need to run full `AtClient` flows without a network.
+//!
+//! Both servers listen on `localhost` using a self-signed certificate.
+//! Use `connection_config` to get a `TlsConnectionConfig` which trusts it.
+
+pub mod fixtures;
+pub mod key_store;
+pub mod mock_at_server;
+pub mod mock_root_server;
+mod server_tls;
+
+pub use mock_at_server::MockAtServer;
+pub use mock_root_server::MockRootServer;
+pub use server_tls::connection_config;
@@ -0,0 +1,1239 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use at_chops::{default_crypto_functions::DefaultCryptoFunctions, CryptoFunctions};
+use at_sign::AtSign;
+use at_tls::at_server_addr::AtServerAddr;
+use at_tls::rustls_connection::RustlsConnection;
+use at_tls::TlsClient;
+use log::debug;
+use regex::Regex;
+use serde_json::json;
+
+use crate::key_store::{is_hidden, key_owner, key_shared_with, KeyStore, StoredRecord};
+use crate::server_tls::{connection_config, read_line, write, TlsListener, TlsStream};
+
+/// A notification received by the mock atServer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceivedNotification {
+    pub id: String,
+    /// The authenticated atSign which sent the notification, without the `@`.
+    pub from: String,
+    /// Who the notification's key is shared with, without the `@`, or empty if nobody.
+    pub to: String,
+    /// Everything after `notify:` in the command.
+    pub command: String,
+    /// When the notification was received, in milliseconds since the Unix epoch.
+    pub epoch_millis: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct ServerState {
+    /// Base64 encoded PKAM public keys, keyed by atSign without the `@`.
+    pub(crate) pkam_public_keys: HashMap<String, String>,
+    pub(crate) key_store: KeyStore,
+    /// Notifications waiting for their recipient to remove them with `notify:remove`.
+    pub(crate) notifications: Vec<ReceivedNotification>,
+    /// The IDs of delivered notifications and who sent them, for `notify:status`.
+    pub(crate) delivered: HashMap<String, String>,
+    /// How many keys `scan:page:<n>` returns, or `None` to reject paged scans like older atServers.
+    pub(crate) scan_page_limit: Option<usize>,
+    /// Whether to answer paged scans with every key, like an atServer which accepts `:page:` but ignores it.
+    pub(crate) ignore_scan_pages: bool,
+    /// Used to find the atServers of other atSigns to check their `pol` proofs.
+    pub(crate) root_server: Option<AtServerAddr>,
+    /// The `block` and `allow` lists, keyed by owner without the `@` and list name. The atSigns have the `@`.
+    pub(crate) config_lists: HashMap<(String, String), BTreeSet<String>>,
+    /// How many `noop` commands have been answered, to check keepalives.
+    pub(crate) noop_count: usize,
+    /// How much longer than asked to wait before answering `noop`, to simulate a stalled atServer.
+    pub(crate) noop_delay: Duration,
+    /// Told about each `noop` once it has been counted, to wait for keepalives without sleeping.
+    pub(crate) noop_listeners: Vec<Sender<()>>,
+    /// How many clients are connected.
+    pub(crate) open_connections: usize,
+    /// How many more commands to answer before closing the connection which sends the next one, to test reconnecting.
+    pub(crate) drop_connection_after: Option<usize>,
+}
+
+/// The state of a single client connection.
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    /// The atSign sent with `from` and the challenge it was given.
+    pending_challenge: Option<(String, String)>,
+    /// The atSign which has completed PKAM authentication, without the `@`.
+    authenticated: Option<String>,
+    /// Another atSign sent with `from`, the key it must publish as proof and the value it must have.
+    pending_proof: Option<(String, String, String)>,
+    /// Another atSign which has completed `pol` authentication, without the `@`.
+    pol_authenticated: Option<String>,
+}
+
+impl Session {
+    /// The prompt the real atServer sends after every response.
+    fn prompt(&self) -> String {
+        match self
+            .authenticated
+            .as_ref()
+            .or(self.pol_authenticated.as_ref())
+        {
+            Some(at_sign) => format!("@{}@", at_sign),
+            None => String::from("@"),
+        }
+    }
+}
+
+/// An in-process atServer serving any number of atSigns from one shared key store.
+///
+/// Understands `from`, `pkam`, `pol`, `info`, `noop`, `exit`, `update`, `lookup`, `llookup`, `plookup`, `scan`, `delete`, `batch`, `stats`, `sync`, `config` and `notify`, including `notify:list`, `notify:status`, `notify:remove` and `notify:all`.
+/// PKAM signatures are verified against the public key registered for the atSign.
+/// Like the real atServer, `info`, `noop`, `plookup` and `scan` of public keys work without authenticating.
+/// Other atSigns authenticate with `pol`, which needs `set_root_server` to find their atServer, and may then `lookup` keys shared with them.
+pub struct MockAtServer {
+    listener: TlsListener,
+    state: Arc<Mutex<ServerState>>,
+}
+
+impl MockAtServer {
+    /// Starts the server on a random port on localhost.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(ServerState::default()));
+        let handler_state = state.clone();
+        let listener = TlsListener::start(move |stream| handle_connection(&handler_state, stream));
+        Self { listener, state }
+    }
+
+    pub fn address(&self) -> AtServerAddr {
+        self.listener.address()
+    }
+
+    /// Allows `at_sign` to authenticate with PKAM using the matching private key.
+    pub fn register_at_sign(&self, at_sign: &AtSign, encoded_pkam_public_key: &str) {
+        self.state().pkam_public_keys.insert(
+            at_sign.get_at_sign_without_prefix(),
+            encoded_pkam_public_key.trim().to_owned(),
+        );
+    }
+
+    /// Stores a value directly, e.g. `public:publickey@bob`.
+    pub fn put(&self, key: &str, value: &str) {
+        self.state()
+            .key_store
+            .put(key, StoredRecord::new(value.to_owned()));
+    }
+
+    /// Stores a record with metadata directly, e.g. a `cached:` copy with a ttr.
+    pub fn put_record(&self, key: &str, record: StoredRecord) {
+        self.state().key_store.put(key, record);
+    }
+
+    pub fn get(&self, key: &str) -> Option<StoredRecord> {
+        self.state().key_store.get(key).cloned()
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.state().key_store.keys().cloned().collect()
+    }
+
+    pub fn notifications(&self) -> Vec<ReceivedNotification> {
+        self.state().notifications.clone()
+    }
+
+    /// How many `noop` commands the server has answered.
+    pub fn noop_count(&self) -> usize {
+        self.state().noop_count
+    }
+
+    /// Waits `delay` longer than asked before answering each `noop`, to test clients giving up on a stalled atServer.
+    pub fn set_noop_delay(&self, delay: Duration) {
+        self.state().noop_delay = delay;
+    }
+
+    /// Receives a message for each `noop` the server answers from now on.
+    pub fn noop_events(&self) -> Receiver<()> {
+        let (sender, receiver) = mpsc::channel();
+        self.state().noop_listeners.push(sender);
+        receiver
+    }
+
+    /// How many clients are connected, counting each connection until the client leaves with `exit` or disconnects.
+    pub fn open_connections(&self) -> usize {
+        self.state().open_connections
+    }
+
+    /// Lets atSigns which aren't registered with this server authenticate with `pol`.
+    pub fn set_root_server(&self, root_server: AtServerAddr) {
+        self.state().root_server = Some(root_server);
+    }
+
+    /// Answers `commands` more commands, on any connection, then closes the connection which sends the next one without replying.
+    /// Only one connection is dropped.
+    pub fn drop_connection_after(&self, commands: usize) {
+        self.state().drop_connection_after = Some(commands);
+    }
+
+    /// Splits scans with `:page:<n>` into pages of `limit` keys. By default paged scans are rejected as invalid syntax.
+    pub fn set_scan_page_limit(&self, limit: Option<usize>) {
+        self.state().scan_page_limit = limit;
+    }
+
+    /// Answers paged scans with every key, ignoring the page, to test clients against a misbehaving atServer.
+    pub fn set_ignore_scan_pages(&self, ignore: bool) {
+        self.state().ignore_scan_pages = ignore;
+    }
+
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap()
+    }
+}
+
+fn handle_connection(state: &Mutex<ServerState>, stream: &mut TlsStream) -> std::io::Result<()> {
+    state.lock().unwrap().open_connections += 1;
+    let result = serve_connection(state, stream);
+    state.lock().unwrap().open_connections -= 1;
+    result
+}
+
+fn serve_connection(state: &Mutex<ServerState>, stream: &mut TlsStream) -> std::io::Result<()> {
+    let mut session = Session::default();
+    write(stream, &session.prompt())?;
+    while let Some(command) = read_line(stream)? {
+        if command.is_empty() {
+            continue;
+        }
+        debug!("Mock atServer received: {}", command);
+        if command == "exit" {
+            // Like the real atServer, closes the connection without replying
+            break;
+        }
+        if should_drop_connection(state) {
+            debug!("Mock atServer dropping the connection");
+            break;
+        }
+        let response = handle_command(state, &mut session, &command);
+        write(stream, &format!("{}\n{}", response, session.prompt()))?;
+    }
+    Ok(())
+}
+
+/// Counts down `drop_connection_after`, returning true when it runs out.
+fn should_drop_connection(state: &Mutex<ServerState>) -> bool {
+    let mut state = state.lock().unwrap();
+    match state.drop_connection_after {
+        Some(0) => {
+            state.drop_connection_after = None;
+            true
+        }
+        Some(ref mut remaining) => {
+            *remaining -= 1;
+            false
+        }
+        None => false,
+    }
+}
+
+const INVALID_SYNTAX: &str = "error:AT0003-Invalid syntax";
+const NOT_AUTHENTICATED: &str = "error:AT0401-Client authentication failed";
+const KEY_NOT_FOUND: &str = "error:AT0015-key not found";
+
+/// Runs one command and returns the response line, without the newline or prompt.
+pub(crate) fn handle_command(
+    state: &Mutex<ServerState>,
+    session: &mut Session,
+    command: &str,
+) -> String {
+    let (verb, args) = command.split_once(':').unwrap_or((command, ""));
+    if verb == "pol" {
+        // Not holding the lock, as checking the proof talks to another server
+        let root_server = state.lock().unwrap().root_server.clone();
+        return pol(root_server, session);
+    }
+    if verb == "noop" {
+        // Not holding the lock while waiting
+        let noop_delay = state.lock().unwrap().noop_delay;
+        let response = noop(args, noop_delay);
+        let mut state = state.lock().unwrap();
+        state.noop_count += 1;
+        // Forgetting listeners which have gone
+        state
+            .noop_listeners
+            .retain(|listener| listener.send(()).is_ok());
+        return response;
+    }
+    let mut state = state.lock().unwrap();
+    match verb {
+        "info" => info(),
+        "from" => from(&state, session, args),
+        "pkam" => pkam(&state, session, args),
+        "plookup" => plookup(&state, args),
+        "lookup" => match session
+            .authenticated
+            .as_ref()
+            .or(session.pol_authenticated.as_ref())
+        {
+            Some(at_sign) => lookup(&state, at_sign, args),
+            None => String::from(NOT_AUTHENTICATED),
+        },
+        _ if command.starts_with("scan") => scan(&state, session.authenticated.as_deref(), command),
+        _ => {
+            let Some(at_sign) = session.authenticated.clone() else {
+                return String::from(NOT_AUTHENTICATED);
+            };
+            match verb {
+                "update" => update(&mut state, args),
+                "llookup" => llookup(&state, args),
+                "delete" => delete(&mut state, args),
+                "batch" => batch(&mut state, args),
+                "config" => config(&mut state, &at_sign, args),
+                "stats" => stats(&state),
+                "sync" => sync(&state, &at_sign, args),
+                "notify" => notify(&mut state, &at_sign, args),
+                _ => String::from(INVALID_SYNTAX),
+            }
+        }
+    }
+}
+
+/// Answers `info` like an atServer without any optional features.
+fn info() -> String {
+    format!(
+        "data:{}",
+        json!({ "version": env!("CARGO_PKG_VERSION"), "uptimeAsWords": "0 seconds", "features": [] })
+    )
+}
+
+/// Answers `noop:<delay>` after waiting `delay` milliseconds, plus `extra_delay`.
+fn noop(args: &str, extra_delay: Duration) -> String {
+    let Ok(delay) = args.parse() else {
+        return String::from(INVALID_SYNTAX);
+    };
+    std::thread::sleep(Duration::from_millis(delay) + extra_delay);
+    String::from("data:ok")
+}
+
+/// Challenges registered atSigns to authenticate with PKAM, and other atSigns to prove who they are with `pol`.
+fn from(state: &ServerState, session: &mut Session, args: &str) -> String {
+    let at_sign = args.trim_start_matches('@');
+    if at_sign.is_empty() {
+        return String::from(INVALID_SYNTAX);
+    }
+    session.authenticated = None;
+    session.pol_authenticated = None;
+    if !state.pkam_public_keys.contains_key(at_sign) {
+        let key = format!("_{:032x}@{}", rand::random::<u128>(), at_sign);
+        let value = format!("{:032x}", rand::random::<u128>());
+        let proof = format!("data:proof:{}:{}", key, value);
+        session.pending_proof = Some((at_sign.to_owned(), key, value));
+        return proof;
+    }
+    let challenge = format!(
+        "_{:032x}@{}:{:032x}",
+        rand::random::<u128>(),
+        at_sign,
+        rand::random::<u128>()
+    );
+    session.pending_challenge = Some((at_sign.to_owned(), challenge.clone()));
+    format!("data:{}", challenge)
+}
+
+fn pkam(state: &ServerState, session: &mut Session, signature: &str) -> String {
+    let Some((at_sign, challenge)) = session.pending_challenge.take() else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let verified = state
+        .pkam_public_keys
+        .get(&at_sign)
+        .is_some_and(|public_key| verify_signature(public_key, &challenge, signature));
+    if verified {
+        session.authenticated = Some(at_sign);
+        String::from("data:success")
+    } else {
+        String::from(NOT_AUTHENTICATED)
+    }
+}
+
+/// Accepts the atSign from `from` if its atServer has the public proof key with the expected value.
+fn pol(root_server: Option<AtServerAddr>, session: &mut Session) -> String {
+    let Some((at_sign, key, value)) = session.pending_proof.take() else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let Some(root_server) = root_server else {
+        return String::from(NOT_AUTHENTICATED);
+    };
+    match plookup_remote(&root_server, &at_sign, &key) {
+        Ok(Some(published)) if published == value => {
+            session.pol_authenticated = Some(at_sign);
+            String::from("data:success")
+        }
+        result => {
+            debug!("pol proof for {} not found: {:?}", at_sign, result);
+            String::from(NOT_AUTHENTICATED)
+        }
+    }
+}
+
+/// Finds `at_sign`'s atServer with the root server and looks up the public `key` on it.
+fn plookup_remote(
+    root_server: &AtServerAddr,
+    at_sign: &str,
+    key: &str,
+) -> std::io::Result<Option<String>> {
+    let config = connection_config();
+    let mut root_client = TlsClient::connect_with_config::<RustlsConnection>(root_server, &config)?;
+    root_client.send_data(at_sign)?;
+    let response = String::from_utf8_lossy(&root_client.read_data()?).into_owned();
+    let address = response.trim().trim_start_matches('@');
+    let Some((host, port)) = address.split_once(':') else {
+        return Ok(None);
+    };
+    let Ok(port) = port.parse() else {
+        return Ok(None);
+    };
+    let address = AtServerAddr::new(host.to_owned(), port);
+    let mut at_server_client =
+        TlsClient::connect_with_config::<RustlsConnection>(&address, &config)?;
+    at_server_client.send_data(format!("plookup:{}", key))?;
+    let response = String::from_utf8_lossy(&at_server_client.read_data()?).into_owned();
+    let response = response.trim().trim_start_matches('@');
+    Ok(response.strip_prefix("data:").map(str::to_owned))
+}
+
+/// Whether `signature` is a valid base64 encoded signature of `challenge`. Any decoding error means it isn't.
+fn verify_signature(encoded_public_key: &str, challenge: &str, signature: &str) -> bool {
+    let crypto = DefaultCryptoFunctions::new();
+    let Ok(public_key) = crypto
+        .base64_decode(encoded_public_key.as_bytes())
+        .and_then(|der| crypto.construct_rsa_public_key(&der))
+    else {
+        return false;
+    };
+    crypto
+        .base64_decode(signature.as_bytes())
+        .and_then(|signature| crypto.rsa_verify(challenge.as_bytes(), &signature, &public_key))
+        .unwrap_or(false)
+}
+
+/// Splits `update`'s `ttl:1000:ttr:-1:<key> <value>` into the record and key.
+fn update(state: &mut ServerState, args: &str) -> String {
+    let mut record = StoredRecord::default();
+    let mut rest = args;
+    while let Some((name, (value, after_value))) = rest
+        .split_once(':')
+        .and_then(|(name, after_name)| Some((name, after_name.split_once(':')?)))
+    {
+        match name {
+            "ttl" => record.ttl = value.parse().ok(),
+            "ttb" => record.ttb = value.parse().ok(),
+            "ttr" => record.ttr = value.parse().ok(),
+            "ccd" => record.ccd = value.parse().ok(),
+            _ => break,
+        }
+        rest = after_value;
+    }
+    let Some((key, value)) = rest.split_once(' ') else {
+        return String::from(INVALID_SYNTAX);
+    };
+    record.value = value.to_owned();
+    let commit_id = state.key_store.put(key, record);
+    format!("data:{}", commit_id)
+}
+
+/// What a lookup asked for: `data` (the default), `meta` or `all`.
+fn split_return_type(args: &str) -> (&str, &str) {
+    for return_type in ["meta", "all"] {
+        if let Some(key) = args
+            .strip_prefix(return_type)
+            .and_then(|rest| rest.strip_prefix(':'))
+        {
+            return (return_type, key);
+        }
+    }
+    ("data", args)
+}
+
+fn format_record(key: &str, record: &StoredRecord, return_type: &str) -> String {
+    let metadata = json!({
+        "ttl": record.ttl,
+        "ttb": record.ttb,
+        "ttr": record.ttr,
+        "ccd": record.ccd,
+        "refreshAt": record.refresh_at,
+        "isBinary": false,
+    });
+    match return_type {
+        "meta" => format!("data:{}", metadata),
+        "all" => format!(
+            "data:{}",
+            json!({ "key": key, "data": record.value, "metaData": metadata })
+        ),
+        _ => format!("data:{}", record.value),
+    }
+}
+
+fn llookup(state: &ServerState, args: &str) -> String {
+    let (return_type, key) = split_return_type(args);
+    match state.key_store.get(key) {
+        Some(record) => format_record(key, record, return_type),
+        None => String::from(KEY_NOT_FOUND),
+    }
+}
+
+/// Strips `bypassCache:true:` or `bypassCache:false:` from `lookup` and `plookup` arguments.
+fn split_bypass_cache(args: &str) -> (bool, &str) {
+    match args.strip_prefix("bypassCache:") {
+        Some(rest) => match rest.split_once(':') {
+            Some(("true", rest)) => (true, rest),
+            Some((_, rest)) => (false, rest),
+            None => (false, args),
+        },
+        None => (false, args),
+    }
+}
+
+/// Resolves `<key>@<owner>` to a cached copy (unless bypassing the cache), the copy shared with the client,
+/// the public copy or, for the owner, the key itself.
+fn lookup(state: &ServerState, at_sign: &str, args: &str) -> String {
+    let (bypass_cache, args) = split_bypass_cache(args);
+    let (return_type, key) = split_return_type(args);
+    let mut candidates = Vec::new();
+    if !bypass_cache {
+        candidates.push(format!("cached:@{}:{}", at_sign, key));
+        candidates.push(format!("cached:public:{}", key));
+    }
+    candidates.push(format!("@{}:{}", at_sign, key));
+    candidates.push(format!("public:{}", key));
+    if key_owner(key) == at_sign {
+        candidates.push(key.to_owned());
+    }
+    candidates
+        .iter()
+        .find_map(|candidate| {
+            let record = state.key_store.get(candidate)?;
+            Some(format_record(candidate, record, return_type))
+        })
+        .unwrap_or_else(|| String::from(KEY_NOT_FOUND))
+}
+
+/// Resolves `<key>@<owner>` to a cached public copy (unless bypassing the cache) or the public key itself.
+fn plookup(state: &ServerState, args: &str) -> String {
+    let (bypass_cache, args) = split_bypass_cache(args);
+    let (return_type, key) = split_return_type(args);
+    let mut candidates = Vec::new();
+    if !bypass_cache {
+        candidates.push(format!("cached:public:{}", key));
+    }
+    candidates.push(format!("public:{}", key));
+    candidates
+        .iter()
+        .find_map(|candidate| {
+            let record = state.key_store.get(candidate)?;
+            Some(format_record(candidate, record, return_type))
+        })
+        .unwrap_or_else(|| String::from(KEY_NOT_FOUND))
+}
+
+fn delete(state: &mut ServerState, key: &str) -> String {
+    match state.key_store.remove(key) {
+        Some(commit_id) => format!("data:{}", commit_id),
+        None => String::from(KEY_NOT_FOUND),
+    }
+}
+
+/// Runs each `update` or `delete` in the JSON array `[{"id":1,"command":"..."}]` and answers with each response.
+fn batch(state: &mut ServerState, args: &str) -> String {
+    let Ok(requests) = serde_json::from_str::<Vec<serde_json::Value>>(args) else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let responses: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|request| {
+            let command = request["command"].as_str().unwrap_or_default();
+            let response = match command.split_once(':') {
+                Some(("update", args)) => update(state, args),
+                Some(("delete", args)) => delete(state, args),
+                _ => String::from(INVALID_SYNTAX),
+            };
+            let response = match response.strip_prefix("data:") {
+                Some(data) => json!({ "data": data }),
+                None => {
+                    let error = response.trim_start_matches("error:");
+                    let (code, message) = error.split_once('-').unwrap_or((error, ""));
+                    json!({ "error_code": code, "error_message": message })
+                }
+            };
+            json!({ "id": request["id"], "response": response })
+        })
+        .collect();
+    format!("data:{}", json!(responses))
+}
+
+/// Only answers stat 3, the last commit id.
+fn stats(state: &ServerState) -> String {
+    let last_commit_id = match state.key_store.last_commit_id() {
+        0 => String::from("null"),
+        commit_id => commit_id.to_string(),
+    };
+    format!(
+        "data:{}",
+        json!([{ "id": "3", "name": "lastCommitID", "value": last_commit_id }])
+    )
+}
+
+/// Answers `sync:from:<commitId>[:limit:<n>]` with the commits to keys owned by `at_sign`.
+fn sync(state: &ServerState, at_sign: &str, args: &str) -> String {
+    let Some(args) = args.strip_prefix("from:") else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let (from, limit) = match args.split_once(":limit:") {
+        Some((from, limit)) => (from, limit.parse().ok()),
+        None => (args, Some(usize::MAX)),
+    };
+    let (Ok(from), Some(limit)) = (from.parse::<i64>(), limit) else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let entries: Vec<serde_json::Value> = state
+        .key_store
+        .commits_after(from.max(0) as u64)
+        .filter(|commit| key_owner(&commit.key) == at_sign && !commit.key.starts_with("cached:"))
+        .take(limit)
+        .map(|commit| {
+            let record = state
+                .key_store
+                .get(&commit.key)
+                .filter(|_| commit.operation == '+');
+            json!({
+                "atKey": commit.key,
+                "value": record.map(|record| &record.value),
+                "metadata": record.map(|record| json!({ "ttl": record.ttl, "ttr": record.ttr })),
+                "commitId": commit.commit_id,
+                "operation": commit.operation.to_string(),
+            })
+        })
+        .collect();
+    format!("data:{}", json!(entries))
+}
+
+/// Answers `notify:<operation>:<key>[:<value>]` from `at_sign`, and the `list`, `status`, `remove` and `all` sub-verbs.
+fn notify(state: &mut ServerState, at_sign: &str, args: &str) -> String {
+    let (sub_verb, sub_args) = args.split_once(':').unwrap_or((args, ""));
+    match sub_verb {
+        "list" => notify_list(state, at_sign, sub_args),
+        "status" => notify_status(state, at_sign, sub_args),
+        "remove" => notify_remove(state, at_sign, sub_args),
+        "all" => notify_all(state, at_sign, sub_args),
+        _ => match send_notification(state, at_sign, args) {
+            Some(id) => format!("data:{}", id),
+            None => String::from(INVALID_SYNTAX),
+        },
+    }
+}
+
+/// Delivers the notification in `command`, e.g. `update:@bob:phone@alice:123`, and returns its ID.
+fn send_notification(state: &mut ServerState, at_sign: &str, command: &str) -> Option<String> {
+    let (_, key, _) = parse_notification(command)?;
+    let id = format!("{:032x}", rand::random::<u128>());
+    let recipient = key_shared_with(key).unwrap_or_default().to_owned();
+    // Like the real atServer, notifications to someone who has blocked the sender are silently dropped
+    let blocked = state
+        .config_lists
+        .get(&(recipient.clone(), String::from("block")))
+        .is_some_and(|blocked| blocked.contains(&format!("@{}", at_sign)));
+    if !blocked {
+        state.notifications.push(ReceivedNotification {
+            id: id.clone(),
+            from: at_sign.to_owned(),
+            to: recipient,
+            command: command.to_owned(),
+            epoch_millis: now_millis(),
+        });
+        state.delivered.insert(id.clone(), at_sign.to_owned());
+    }
+    Some(id)
+}
+
+/// Splits a notification like `update:@bob:phone@alice:123` into its operation, key and value.
+fn parse_notification(command: &str) -> Option<(&str, &str, Option<&str>)> {
+    let (operation, rest) = command.split_once(':')?;
+    // A shared key has a colon of its own after who it is shared with
+    let key_start = match rest.strip_prefix('@').and_then(|shared| shared.find(':')) {
+        Some(shared_with_end) => shared_with_end + 2,
+        None => 0,
+    };
+    Some(match rest[key_start..].find(':') {
+        Some(key_end) => {
+            let key_end = key_start + key_end;
+            (operation, &rest[..key_end], Some(&rest[key_end + 1..]))
+        }
+        None => (operation, rest, None),
+    })
+}
+
+/// Answers `notify:list[:<from date>[:<to date>]][:<regex>]` with the notifications received by `at_sign`.
+fn notify_list(state: &ServerState, at_sign: &str, args: &str) -> String {
+    let mut args = args;
+    let mut dates = vec![];
+    while let Some(days) = args.get(..10).and_then(days_since_epoch) {
+        dates.push(days);
+        args = args[10..].strip_prefix(':').unwrap_or(&args[10..]);
+    }
+    let from_day = dates.first().copied().unwrap_or(i64::MIN);
+    let to_day = dates.get(1).copied().unwrap_or(i64::MAX);
+    let regex = match Regex::new(if args.is_empty() { ".*" } else { args }) {
+        Ok(regex) => regex,
+        Err(_) => return String::from(INVALID_SYNTAX),
+    };
+    let notifications: Vec<_> = state
+        .notifications
+        .iter()
+        .filter(|notification| notification.to == at_sign)
+        .filter(|notification| {
+            let day = (notification.epoch_millis / 86_400_000) as i64;
+            (from_day..=to_day).contains(&day)
+        })
+        .filter_map(|notification| {
+            let (operation, key, value) = parse_notification(&notification.command)?;
+            regex.is_match(key).then(|| {
+                json!({
+                    "id": notification.id,
+                    "from": format!("@{}", notification.from),
+                    "to": format!("@{}", notification.to),
+                    "key": key,
+                    "value": value,
+                    "operation": operation,
+                    "epochMillis": notification.epoch_millis,
+                })
+            })
+        })
+        .collect();
+    if notifications.is_empty() {
+        return String::from("data:null");
+    }
+    format!("data:{}", json!(notifications))
+}
+
+/// Answers `notify:status:<id>`. Every notification is delivered straight away, and unknown ones have expired.
+fn notify_status(state: &ServerState, at_sign: &str, id: &str) -> String {
+    match state.delivered.get(id) {
+        Some(from) if from == at_sign => String::from("data:delivered"),
+        _ => String::from("data:expired"),
+    }
+}
+
+/// Answers `notify:remove:<id>`, removing a notification received by `at_sign`.
+fn notify_remove(state: &mut ServerState, at_sign: &str, id: &str) -> String {
+    if id.is_empty() {
+        return String::from(INVALID_SYNTAX);
+    }
+    state
+        .notifications
+        .retain(|notification| notification.id != id || notification.to != at_sign);
+    String::from("data:success")
+}
+
+/// Answers `notify:all:<operation>:<@recipient,...>:<key>[:<value>]` with each recipient's notification ID.
+fn notify_all(state: &mut ServerState, at_sign: &str, args: &str) -> String {
+    let mut parts = args.splitn(3, ':');
+    let (Some(operation), Some(recipients), Some(key)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let mut ids = serde_json::Map::new();
+    for recipient in recipients.split(',') {
+        let command = format!("{}:{}:{}", operation, recipient, key);
+        let Some(id) = send_notification(state, at_sign, &command) else {
+            return String::from(INVALID_SYNTAX);
+        };
+        ids.insert(recipient.to_owned(), id.into());
+    }
+    format!("data:{}", json!(ids))
+}
+
+/// Days since 1970-01-01 of a date like `2024-02-06`.
+fn days_since_epoch(date: &str) -> Option<i64> {
+    let mut parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as u64)
+}
+
+/// Answers `config:<block|allow>:<add|remove>:<atSigns>` and `config:<block|allow>:show` for `at_sign`'s lists.
+fn config(state: &mut ServerState, at_sign: &str, args: &str) -> String {
+    let Some((list, operation)) = args.split_once(':') else {
+        return String::from(INVALID_SYNTAX);
+    };
+    if list != "block" && list != "allow" {
+        return String::from(INVALID_SYNTAX);
+    }
+    let at_signs = state
+        .config_lists
+        .entry((at_sign.to_owned(), list.to_owned()))
+        .or_default();
+    let (operation, args) = operation.split_once(':').unwrap_or((operation, ""));
+    let listed = args
+        .split_whitespace()
+        .map(|at_sign| format!("@{}", at_sign.trim_start_matches('@')));
+    match operation {
+        "add" if !args.is_empty() => at_signs.extend(listed),
+        "remove" if !args.is_empty() => listed.for_each(|at_sign| {
+            at_signs.remove(&at_sign);
+        }),
+        "show" if at_signs.is_empty() => return String::from("data:null"),
+        "show" => return format!("data:{}", json!(at_signs)),
+        _ => return String::from(INVALID_SYNTAX),
+    }
+    String::from("data:success")
+}
+
+/// Lists the keys owned by or shared with the client as a JSON array, or only public keys if the client isn't authenticated.
+fn scan(state: &ServerState, at_sign: Option<&str>, command: &str) -> String {
+    let (options, regex) = command.split_once(' ').unwrap_or((command, ""));
+    let Some(options) = options.strip_prefix("scan") else {
+        return String::from(INVALID_SYNTAX);
+    };
+    let show_hidden = options.contains(":showhidden:true");
+    let page = match options.split_once(":page:") {
+        Some(_) if state.ignore_scan_pages => None,
+        Some((_, page)) => match (page.parse::<usize>(), state.scan_page_limit) {
+            (Ok(page), Some(limit)) if page > 0 => Some((page, limit)),
+            _ => return String::from(INVALID_SYNTAX),
+        },
+        None => None,
+    };
+    let regex = match Regex::new(regex.trim()) {
+        Ok(regex) => regex,
+        Err(_) => return String::from(INVALID_SYNTAX),
+    };
+    let keys = state
+        .key_store
+        .keys()
+        .filter(|key| match at_sign {
+            Some(at_sign) => key_owner(key) == at_sign || key_shared_with(key) == Some(at_sign),
+            None => key.starts_with("public:"),
+        })
+        .filter(|key| show_hidden || !is_hidden(key))
+        .filter(|key| regex.is_match(key));
+    let keys: Vec<&String> = match page {
+        Some((page, limit)) => keys.skip((page - 1) * limit).take(limit).collect(),
+        None => keys.collect(),
+    };
+    format!("data:{}", json!(keys))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures;
+
+    /// A session already authenticated as `test_at_sign`, whose PKAM public key is registered.
+    fn authenticated_session(
+        state: &Mutex<ServerState>,
+        test_at_sign: &fixtures::TestAtSign,
+    ) -> Session {
+        let at_sign = test_at_sign.at_sign.get_at_sign_without_prefix();
+        state
+            .lock()
+            .unwrap()
+            .pkam_public_keys
+            .insert(at_sign.clone(), test_at_sign.pkam_public_key.to_owned());
+        Session {
+            authenticated: Some(at_sign),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pkam_authentication() {
+        let state = Mutex::new(ServerState::default());
+        let alice = fixtures::alice();
+        state
+            .lock()
+            .unwrap()
+            .pkam_public_keys
+            .insert(String::from("alice"), alice.pkam_public_key.to_owned());
+        let at_chops = alice.at_chops();
+        let mut session = Session::default();
+
+        let challenge = handle_command(&state, &mut session, "from:alice");
+        let challenge = challenge.strip_prefix("data:").unwrap();
+        let signature = at_chops.sign_challenge(challenge).unwrap();
+        let response = handle_command(&state, &mut session, &format!("pkam:{}", signature));
+
+        assert_eq!(response, "data:success");
+        assert_eq!(session.prompt(), "@alice@");
+    }
+
+    #[test]
+    fn test_pkam_wrong_signature() {
+        let state = Mutex::new(ServerState::default());
+        state.lock().unwrap().pkam_public_keys.insert(
+            String::from("alice"),
+            fixtures::alice().pkam_public_key.to_owned(),
+        );
+        let mut session = Session::default();
+        handle_command(&state, &mut session, "from:alice");
+        let response = handle_command(&state, &mut session, "pkam:bm90IGEgc2lnbmF0dXJl");
+        assert_eq!(response, NOT_AUTHENTICATED);
+        assert_eq!(session.prompt(), "@");
+    }
+
+    #[test]
+    fn test_pol_without_proof() {
+        let state = Mutex::new(ServerState::default());
+        let mut session = Session::default();
+        let proof = handle_command(&state, &mut session, "from:@alice");
+        let proof = proof.strip_prefix("data:proof:_").unwrap();
+        assert!(proof.contains("@alice:"));
+        // Without a root server the proof can't be checked
+        assert_eq!(
+            handle_command(&state, &mut session, "pol"),
+            NOT_AUTHENTICATED
+        );
+        assert_eq!(handle_command(&state, &mut session, "pol"), INVALID_SYNTAX);
+        assert_eq!(
+            handle_command(&state, &mut session, "lookup:phone@bob"),
+            NOT_AUTHENTICATED
+        );
+    }
+
+    #[test]
+    fn test_requires_authentication() {
+        let state = Mutex::new(ServerState::default());
+        let mut session = Session::default();
+        let response = handle_command(&state, &mut session, "llookup:phone@alice");
+        assert_eq!(response, NOT_AUTHENTICATED);
+    }
+
+    #[test]
+    fn test_update_and_lookups() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let mut bob = authenticated_session(&state, &fixtures::bob());
+
+        let response = handle_command(&state, &mut alice, "update:ttr:-1:@bob:phone@alice 123");
+        assert_eq!(response, "data:1");
+        handle_command(&state, &mut alice, "update:public:email@alice a@b.c");
+
+        assert_eq!(
+            handle_command(&state, &mut alice, "llookup:@bob:phone@alice"),
+            "data:123"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "lookup:phone@alice"),
+            "data:123"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "lookup:email@alice"),
+            "data:a@b.c"
+        );
+        assert_eq!(
+            handle_command(&state, &mut Session::default(), "plookup:email@alice"),
+            "data:a@b.c"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "lookup:missing@alice"),
+            KEY_NOT_FOUND
+        );
+        let meta = handle_command(&state, &mut alice, "llookup:meta:@bob:phone@alice");
+        let meta: serde_json::Value =
+            serde_json::from_str(meta.strip_prefix("data:").unwrap()).unwrap();
+        assert_eq!(meta["ttr"], -1);
+    }
+
+    #[test]
+    fn test_cached_lookups() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let mut bob = authenticated_session(&state, &fixtures::bob());
+        handle_command(&state, &mut alice, "update:@bob:phone@alice 123");
+        handle_command(&state, &mut alice, "update:public:email@alice a@b.c");
+        handle_command(&state, &mut bob, "update:cached:@bob:phone@alice 100");
+        handle_command(&state, &mut bob, "update:cached:public:email@alice old@b.c");
+
+        assert_eq!(
+            handle_command(&state, &mut bob, "lookup:phone@alice"),
+            "data:100"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "lookup:bypassCache:true:phone@alice"),
+            "data:123"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "plookup:email@alice"),
+            "data:old@b.c"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "plookup:bypassCache:true:email@alice"),
+            "data:a@b.c"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "llookup:cached:@bob:phone@alice"),
+            "data:100"
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let response = handle_command(
+            &state,
+            &mut alice,
+            r#"batch:[{"id":1,"command":"update:phone@alice 123"},{"id":2,"command":"delete:email@alice"},{"id":3,"command":"llookup:phone@alice"}]"#,
+        );
+        assert_eq!(
+            response,
+            r#"data:[{"id":1,"response":{"data":"1"}},{"id":2,"response":{"error_code":"AT0015","error_message":"key not found"}},{"id":3,"response":{"error_code":"AT0003","error_message":"Invalid syntax"}}]"#
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "llookup:phone@alice"),
+            "data:123"
+        );
+    }
+
+    #[test]
+    fn test_stats_and_sync() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        assert_eq!(
+            handle_command(&state, &mut alice, "stats:3"),
+            r#"data:[{"id":"3","name":"lastCommitID","value":"null"}]"#
+        );
+        handle_command(&state, &mut alice, "update:public:email@alice a@b.c");
+        handle_command(&state, &mut alice, "update:public:email@bob b@b.c");
+        handle_command(&state, &mut alice, "update:phone@alice 123");
+        handle_command(&state, &mut alice, "delete:public:email@alice");
+        assert_eq!(
+            handle_command(&state, &mut alice, "stats:3"),
+            r#"data:[{"id":"3","name":"lastCommitID","value":"4"}]"#
+        );
+
+        let mut sync = |command| {
+            let response = handle_command(&state, &mut alice, command);
+            let entries: serde_json::Value =
+                serde_json::from_str(response.strip_prefix("data:").unwrap()).unwrap();
+            entries
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{}{}={}",
+                        entry["operation"].as_str().unwrap(),
+                        entry["atKey"].as_str().unwrap(),
+                        entry["value"]
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sync("sync:from:-1"),
+            vec![
+                "+public:email@alice=null",
+                "+phone@alice=\"123\"",
+                "-public:email@alice=null"
+            ]
+        );
+        assert_eq!(sync("sync:from:1:limit:1"), vec!["+phone@alice=\"123\""]);
+        assert!(sync("sync:from:4").is_empty());
+    }
+
+    #[test]
+    fn test_scan_and_delete() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        handle_command(&state, &mut alice, "update:public:email@alice a@b.c");
+        handle_command(&state, &mut alice, "update:_hidden.app@alice x");
+        handle_command(&state, &mut alice, "update:public:email@bob b@b.c");
+
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan"),
+            r#"data:["public:email@alice"]"#
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan:showhidden:true"),
+            r#"data:["_hidden.app@alice","public:email@alice"]"#
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan:showhidden:true _hidden"),
+            r#"data:["_hidden.app@alice"]"#
+        );
+
+        assert_eq!(
+            handle_command(&state, &mut alice, "delete:public:email@alice"),
+            "data:4"
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "delete:public:email@alice"),
+            KEY_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_anonymous_scan() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        handle_command(&state, &mut alice, "update:public:email@alice a@b.c");
+        handle_command(&state, &mut alice, "update:public:_hidden@alice x");
+        handle_command(&state, &mut alice, "update:@bob:phone@alice 123");
+        assert_eq!(
+            handle_command(&state, &mut Session::default(), "scan"),
+            r#"data:["public:email@alice"]"#
+        );
+    }
+
+    #[test]
+    fn test_paged_scan() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        for key in ["a", "b", "c"] {
+            handle_command(
+                &state,
+                &mut alice,
+                &format!("update:public:{}@alice x", key),
+            );
+        }
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan:page:1"),
+            INVALID_SYNTAX
+        );
+
+        state.lock().unwrap().scan_page_limit = Some(2);
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan:page:1"),
+            r#"data:["public:a@alice","public:b@alice"]"#
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "scan:page:2"),
+            r#"data:["public:c@alice"]"#
+        );
+        assert_eq!(handle_command(&state, &mut alice, "scan:page:3"), "data:[]");
+    }
+
+    #[test]
+    fn test_notify() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let response = handle_command(&state, &mut alice, "notify:update:@bob:phone@alice:123");
+        let id = response.strip_prefix("data:").unwrap();
+        let notifications = &state.lock().unwrap().notifications;
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].id, id);
+        assert_eq!(notifications[0].from, "alice");
+        assert_eq!(notifications[0].command, "update:@bob:phone@alice:123");
+    }
+
+    #[test]
+    fn test_info_and_noop() {
+        let state = Mutex::new(ServerState::default());
+        let mut session = Session::default();
+        assert!(handle_command(&state, &mut session, "info").starts_with(r#"data:{""#));
+        assert_eq!(handle_command(&state, &mut session, "noop:1"), "data:ok");
+        assert_eq!(
+            handle_command(&state, &mut session, "noop:soon"),
+            INVALID_SYNTAX
+        );
+        assert_eq!(state.lock().unwrap().noop_count, 2);
+    }
+
+    #[test]
+    fn test_notification_management() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let mut bob = authenticated_session(&state, &fixtures::bob());
+        let response = handle_command(
+            &state,
+            &mut alice,
+            "notify:all:update:@bob,@carol:phone.test@alice:123",
+        );
+        let ids: HashMap<String, String> =
+            serde_json::from_str(response.strip_prefix("data:").unwrap()).unwrap();
+        handle_command(&state, &mut alice, "notify:delete:@bob:email.test@alice");
+
+        let response = handle_command(&state, &mut bob, "notify:list:phone");
+        let listed: serde_json::Value =
+            serde_json::from_str(response.strip_prefix("data:").unwrap()).unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["id"], ids["@bob"]);
+        assert_eq!(listed[0]["from"], "@alice");
+        assert_eq!(listed[0]["key"], "@bob:phone.test@alice");
+        assert_eq!(listed[0]["value"], "123");
+        assert_eq!(listed[0]["operation"], "update");
+        let response = handle_command(&state, &mut bob, "notify:list:1970-01-01:1970-01-02");
+        assert_eq!(response, "data:null");
+        let response = handle_command(&state, &mut bob, "notify:list:2000-01-01:email");
+        assert!(response.contains(r#""operation":"delete""#));
+
+        let status = format!("notify:status:{}", ids["@bob"]);
+        assert_eq!(
+            handle_command(&state, &mut alice, &status),
+            "data:delivered"
+        );
+        assert_eq!(handle_command(&state, &mut bob, &status), "data:expired");
+
+        let remove = format!("notify:remove:{}", ids["@bob"]);
+        assert_eq!(handle_command(&state, &mut bob, &remove), "data:success");
+        let response = handle_command(&state, &mut bob, "notify:list:phone");
+        assert_eq!(response, "data:null");
+        assert_eq!(
+            handle_command(&state, &mut alice, &status),
+            "data:delivered"
+        );
+    }
+
+    #[test]
+    fn test_config_block_list() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        let mut bob = authenticated_session(&state, &fixtures::bob());
+        assert_eq!(
+            handle_command(&state, &mut bob, "config:block:show"),
+            "data:null"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "config:block:add:@alice @carol"),
+            "data:success"
+        );
+        handle_command(&state, &mut bob, "config:block:remove:@carol");
+        assert_eq!(
+            handle_command(&state, &mut bob, "config:block:show"),
+            r#"data:["@alice"]"#
+        );
+        assert_eq!(
+            handle_command(&state, &mut alice, "config:block:show"),
+            "data:null"
+        );
+        assert_eq!(
+            handle_command(&state, &mut bob, "config:deny:show"),
+            INVALID_SYNTAX
+        );
+
+        handle_command(&state, &mut alice, "notify:update:@bob:phone@alice:123");
+        assert!(state.lock().unwrap().notifications.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_verb() {
+        let state = Mutex::new(ServerState::default());
+        let mut alice = authenticated_session(&state, &fixtures::alice());
+        assert_eq!(
+            handle_command(&state, &mut alice, "nonsense:abc"),
+            INVALID_SYNTAX
+        );
+    }
+}
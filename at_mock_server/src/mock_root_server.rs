@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use at_sign::AtSign;
+use at_tls::at_server_addr::AtServerAddr;
+
+use crate::server_tls::{read_line, write, TlsListener, TlsStream};
+
+/// An in-process root server which answers each atSign with the address of its atServer.
+pub struct MockRootServer {
+    listener: TlsListener,
+    addresses: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl MockRootServer {
+    /// Starts the server on a random port on localhost.
+    pub fn start() -> Self {
+        let addresses = Arc::new(Mutex::new(HashMap::new()));
+        let handler_addresses = addresses.clone();
+        let listener =
+            TlsListener::start(move |stream| handle_connection(&handler_addresses, stream));
+        Self {
+            listener,
+            addresses,
+        }
+    }
+
+    pub fn address(&self) -> AtServerAddr {
+        self.listener.address()
+    }
+
+    /// Answers lookups for `at_sign` with `address`.
+    pub fn register(&self, at_sign: &AtSign, address: &AtServerAddr) {
+        self.addresses.lock().unwrap().insert(
+            at_sign.get_at_sign_without_prefix(),
+            format!("{}:{}", address.host, address.port),
+        );
+    }
+}
+
+fn handle_connection(
+    addresses: &Mutex<HashMap<String, String>>,
+    stream: &mut TlsStream,
+) -> std::io::Result<()> {
+    while let Some(at_sign) = read_line(stream)? {
+        let at_sign = at_sign.trim_start_matches('@');
+        let response = match addresses.lock().unwrap().get(at_sign) {
+            Some(address) => format!("@{}\n", address),
+            None => String::from("@null\n"),
+        };
+        write(stream, &response)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use at_tls::at_server_addr::AtServerAddr;
+use at_tls::tls_connection_config::{RootCertificates, TlsConnectionConfig};
+use log::{debug, warn};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+pub(crate) type TlsStream = BufReader<StreamOwned<ServerConnection, TcpStream>>;
+
+/// The self-signed certificate for `localhost` shared by every mock server in the process.
+fn certified_key() -> &'static rcgen::CertifiedKey {
+    static CERTIFIED_KEY: OnceLock<rcgen::CertifiedKey> = OnceLock::new();
+    CERTIFIED_KEY.get_or_init(|| {
+        rcgen::generate_simple_self_signed(vec![String::from("localhost")])
+            .expect("Failed to generate test certificate")
+    })
+}
+
+fn server_config() -> Arc<ServerConfig> {
+    let certified_key = certified_key();
+    let server_config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certified_key.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(certified_key.key_pair.serialize_der().into()),
+        )
+        .expect("Failed to create server config");
+    Arc::new(server_config)
+}
+
+/// A `TlsConnectionConfig` which trusts the mock servers' certificate, with 5 second read and write timeouts.
+pub fn connection_config() -> TlsConnectionConfig {
+    let mut config = TlsConnectionConfig::new(
+        Some(Duration::from_secs(5)),
+        Some(Duration::from_secs(5)),
+        Some(Duration::from_secs(5)),
+    );
+    config.root_certificates =
+        RootCertificates::Custom(vec![CertificateDer::clone(certified_key().cert.der())]);
+    config
+}
+
+/// A TLS listener on localhost which runs `handler` on its own thread for each connection.
+///
+/// Stops accepting connections when dropped.
+pub(crate) struct TlsListener {
+    port: u16,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl TlsListener {
+    pub(crate) fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&mut TlsStream) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+        let port = listener.local_addr().unwrap().port();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server_config = server_config();
+        let handler = Arc::new(handler);
+        let thread_shutdown = shutdown.clone();
+        std::thread::spawn(move || {
+            for tcp_stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(tcp_stream) = tcp_stream else {
+                    continue;
+                };
+                let Ok(session) = ServerConnection::new(server_config.clone()) else {
+                    continue;
+                };
+                let handler = handler.clone();
+                std::thread::spawn(move || {
+                    let mut stream = BufReader::new(StreamOwned::new(session, tcp_stream));
+                    if let Err(e) = handler(&mut stream) {
+                        debug!("Mock server connection closed: {}", e);
+                    }
+                });
+            }
+        });
+        Self { port, shutdown }
+    }
+
+    /// The address to connect to. Uses `localhost` so the certificate matches.
+    pub(crate) fn address(&self) -> AtServerAddr {
+        AtServerAddr::new(String::from("localhost"), self.port)
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it notices the shutdown
+        if TcpStream::connect(("127.0.0.1", self.port)).is_err() {
+            warn!("Unable to stop mock server on port {}", self.port);
+        }
+    }
+}
+
+/// Reads one line without the trailing newline. Returns `None` once the client disconnects.
+pub(crate) fn read_line(stream: &mut TlsStream) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+pub(crate) fn write(stream: &mut TlsStream, data: &str) -> std::io::Result<()> {
+    let stream = stream.get_mut();
+    stream.write_all(data.as_bytes())?;
+    stream.flush()
+}
@@ -1,8 +1,7 @@
 use std::{fmt::Display, str::FromStr};
 
-use at_sign::AtSign;
-use log::{error, trace};
-use regex::Regex;
+use at_sign::{AtSign, AtSignError};
+use log::trace;
 
 /**
 An AtKey is the identifier half of the "identifier-value" pair.
@@ -38,7 +37,7 @@ Example: `cached:@bob:record1.namespace1@alice` -> Cached data that belongs to @
 - Format of the key shared with someone else should be `cached:<Shared with @sign>::<Created by @sign>`.
 - The user who has cached the key should not be allowed to update the cached key. An atSign owner who has created and shared the key should be allowed to update a cached key, and if the "autoNotify" config parameters is set to true, the updated value should be notified (please refer to the notify verb) and the cached key updated with the new value. If the user who originally shared the keys set the CCD (Cascade delete) to true, the cached key will be deleted when the original key is deleted.
 */
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AtKey {
     pub record_id: String,
     pub namespace: Option<String>,
@@ -47,7 +46,7 @@ pub struct AtKey {
     pub visibility_scope: Visibility,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Visibility {
     Public,
     Private,
@@ -55,199 +54,303 @@ pub enum Visibility {
     Shared(AtSign),
 }
 
+/// The maximum length of an AtKey's record ID, namespace and owner combined.
+pub const MAX_AT_KEY_LENGTH: usize = 240;
+
 impl AtKey {
+    /// # Panics
+    /// If the key is longer than `MAX_AT_KEY_LENGTH`. Use `try_new_public_key` for keys from user input.
     pub fn new_public_key<T: AsRef<str>>(record_id: T, namespace: T, owner: AtSign) -> Self {
-        assert!(
-            record_id.as_ref().len() + namespace.as_ref().len() + owner.to_string().len() <= 240,
-            "AtKey must be less than or equal to 240 characters"
-        );
-        AtKey {
-            record_id: record_id.as_ref().to_string(),
-            namespace: Some(namespace.as_ref().to_string()),
+        Self::try_new_public_key(record_id, namespace, owner).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_public_key<T: AsRef<str>>(
+        record_id: T,
+        namespace: T,
+        owner: AtSign,
+    ) -> Result<Self, AtKeyError> {
+        Self::try_new(
+            record_id.as_ref(),
+            Some(namespace.as_ref()),
             owner,
-            is_cached: false,
-            visibility_scope: Visibility::Public,
-        }
+            false,
+            Visibility::Public,
+        )
     }
 
+    /// # Panics
+    /// If the key is longer than `MAX_AT_KEY_LENGTH`. Use `try_new_private_key` for keys from user input.
     pub fn new_private_key<T: AsRef<str>>(
         record_id: T,
         namespace: Option<T>,
         owner: AtSign,
     ) -> Self {
-        assert!(
-            record_id.as_ref().len()
-                + namespace
-                    .as_ref()
-                    .map_or_else(|| "", |ns| ns.as_ref())
-                    .len()
-                + owner.to_string().len()
-                <= 240,
-            "AtKey must be less than or equal to 240 characters"
-        );
-        AtKey {
-            record_id: record_id.as_ref().to_string(),
-            namespace: namespace.map(|ns| ns.as_ref().to_string()),
+        Self::try_new_private_key(record_id, namespace, owner).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_private_key<T: AsRef<str>>(
+        record_id: T,
+        namespace: Option<T>,
+        owner: AtSign,
+    ) -> Result<Self, AtKeyError> {
+        Self::try_new(
+            record_id.as_ref(),
+            namespace.as_ref().map(|ns| ns.as_ref()),
             owner,
-            is_cached: false,
-            visibility_scope: Visibility::Private,
-        }
+            false,
+            Visibility::Private,
+        )
     }
 
+    /// # Panics
+    /// If the key is longer than `MAX_AT_KEY_LENGTH`. Use `try_new_user_key` for keys from user input.
     pub fn new_user_key<T: AsRef<str>>(
         record_id: T,
         namespace: T,
         owner: AtSign,
         shared_with: AtSign,
     ) -> Self {
-        assert!(
-            record_id.as_ref().len() + namespace.as_ref().len() + owner.to_string().len() <= 240,
-            "AtKey must be less than or equal to 240 characters"
-        );
-        AtKey {
-            record_id: record_id.as_ref().to_string(),
-            namespace: Some(namespace.as_ref().to_string()),
+        Self::try_new_user_key(record_id, namespace, owner, shared_with)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_user_key<T: AsRef<str>>(
+        record_id: T,
+        namespace: T,
+        owner: AtSign,
+        shared_with: AtSign,
+    ) -> Result<Self, AtKeyError> {
+        Self::try_new(
+            record_id.as_ref(),
+            Some(namespace.as_ref()),
             owner,
-            is_cached: false,
-            visibility_scope: Visibility::Shared(shared_with),
-        }
+            false,
+            Visibility::Shared(shared_with),
+        )
     }
 
+    /// # Panics
+    /// If the key is longer than `MAX_AT_KEY_LENGTH`. Use `try_new_internal_key` for keys from user input.
     pub fn new_internal_key<T: AsRef<str>>(record_id: T, namespace: T, owner: AtSign) -> Self {
-        assert!(
-            record_id.as_ref().len() + namespace.as_ref().len() + owner.to_string().len() <= 240,
-            "AtKey must be less than or equal to 240 characters"
-        );
-        AtKey {
-            record_id: record_id.as_ref().to_string(),
-            namespace: Some(namespace.as_ref().to_string()),
+        Self::try_new_internal_key(record_id, namespace, owner).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_internal_key<T: AsRef<str>>(
+        record_id: T,
+        namespace: T,
+        owner: AtSign,
+    ) -> Result<Self, AtKeyError> {
+        Self::try_new(
+            record_id.as_ref(),
+            Some(namespace.as_ref()),
             owner,
-            is_cached: false,
-            visibility_scope: Visibility::Internal,
-        }
+            false,
+            Visibility::Internal,
+        )
     }
 
+    /// # Panics
+    /// If the key is longer than `MAX_AT_KEY_LENGTH`. Use `try_new_cached_key` for keys from user input.
     pub fn new_cached_key<T: AsRef<str>>(
         record_id: T,
         namespace: T,
         owner: AtSign,
         cached_by: AtSign,
     ) -> Self {
-        assert!(
-            record_id.as_ref().len() + namespace.as_ref().len() + owner.to_string().len() <= 240,
-            "AtKey must be less than or equal to 240 characters"
-        );
-        AtKey {
-            record_id: record_id.as_ref().to_string(),
-            namespace: Some(namespace.as_ref().to_string()),
+        Self::try_new_cached_key(record_id, namespace, owner, cached_by)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new_cached_key<T: AsRef<str>>(
+        record_id: T,
+        namespace: T,
+        owner: AtSign,
+        cached_by: AtSign,
+    ) -> Result<Self, AtKeyError> {
+        Self::try_new(
+            record_id.as_ref(),
+            Some(namespace.as_ref()),
             owner,
-            is_cached: true,
-            visibility_scope: Visibility::Shared(cached_by),
+            true,
+            Visibility::Shared(cached_by),
+        )
+    }
+
+    fn try_new(
+        record_id: &str,
+        namespace: Option<&str>,
+        owner: AtSign,
+        is_cached: bool,
+        visibility_scope: Visibility,
+    ) -> Result<Self, AtKeyError> {
+        if record_id.is_empty() {
+            return Err(AtKeyError::EmptyRecordId);
+        }
+        let length = record_id.len() + namespace.map_or(0, |ns| ns.len()) + owner.to_string().len();
+        if length > MAX_AT_KEY_LENGTH {
+            return Err(AtKeyError::TooLong(length));
+        }
+        Ok(AtKey {
+            record_id: record_id.to_string(),
+            namespace: namespace.map(|ns| ns.to_string()),
+            owner,
+            is_cached,
+            visibility_scope,
+        })
+    }
+
+    /// The key as the atServer stores it, used by verbs acting on our own atServer such as `update` and `llookup`.
+    ///
+    /// Format: `[cached:][public:|@<shared with>:|_]<record ID>[.<namespace>]<owner>`.
+    /// Unlike `Display`, private keys have no prefix because the atProtocol has no `private:` prefix.
+    pub fn full_key(&self) -> String {
+        let mut key = String::new();
+        if self.is_cached {
+            key.push_str("cached:");
         }
+        match &self.visibility_scope {
+            Visibility::Public => key.push_str("public:"),
+            Visibility::Shared(shared_with) => {
+                key.push_str(&shared_with.get_at_sign_with_prefix());
+                key.push(':');
+            }
+            Visibility::Private | Visibility::Internal => {}
+        }
+        key.push_str(&self.lookup_key());
+        key
+    }
+
+    /// The key without `cached:`, `public:` or who it is shared with, used by `lookup` and `plookup`,
+    /// where the atServer works out which key to return from who is asking.
+    ///
+    /// Format: `[_]<record ID>[.<namespace>]<owner>`.
+    pub fn lookup_key(&self) -> String {
+        let mut key = String::new();
+        if self.visibility_scope == Visibility::Internal {
+            key.push('_');
+        }
+        key.push_str(&self.record_id);
+        if let Some(namespace) = &self.namespace {
+            key.push('.');
+            key.push_str(namespace);
+        }
+        key.push_str(&self.owner.get_at_sign_with_prefix());
+        key
+    }
+}
+
+/// Why a string or set of parts is not a valid AtKey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtKeyError {
+    EmptyRecordId,
+    /// The key has this many characters, more than `MAX_AT_KEY_LENGTH`.
+    TooLong(usize),
+    /// The string does not match any of the AtKey formats.
+    InvalidFormat(String),
+    InvalidAtSign(AtSignError),
+}
+
+impl Display for AtKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtKeyError::EmptyRecordId => write!(f, "AtKey record ID must not be empty"),
+            AtKeyError::TooLong(length) => write!(
+                f,
+                "AtKey must be less than or equal to {} characters but has {}",
+                MAX_AT_KEY_LENGTH, length
+            ),
+            AtKeyError::InvalidFormat(key) => write!(
+                f,
+                "{} does not match any expected key format. Expected format: [cached:]<visibility scope>:<record ID>.<namespace><owner’s atSign>",
+                key
+            ),
+            AtKeyError::InvalidAtSign(e) => write!(f, "Invalid atSign in AtKey: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AtKeyError {}
+
+impl From<AtSignError> for AtKeyError {
+    fn from(error: AtSignError) -> Self {
+        AtKeyError::InvalidAtSign(error)
     }
 }
 
 impl Display for AtKey {
+    /// Formats the key like `full_key`, but with a `private:` prefix for private keys so they can be told apart.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buffer = String::from("");
-        if self.is_cached {
-            buffer.push_str("cached:");
-        };
         match &self.visibility_scope {
-            Visibility::Public => {
-                buffer.push_str("public:");
-            }
             Visibility::Private => {
-                buffer.push_str("private:");
-            }
-            Visibility::Internal => {
-                buffer.push('_');
-            }
-            Visibility::Shared(shared_with) => {
-                buffer.push_str(&format!("{}:", shared_with.get_at_sign_with_prefix()));
+                if self.is_cached {
+                    write!(f, "cached:")?;
+                }
+                write!(f, "private:{}", self.lookup_key())
             }
-        };
-        buffer.push_str(&self.record_id);
-        if let Some(namespace) = &self.namespace {
-            buffer.push_str(&format!(".{}", namespace));
+            _ => write!(f, "{}", self.full_key()),
         }
-        buffer.push_str(&self.owner.get_at_sign_with_prefix());
-        write!(f, "{}", buffer)
     }
 }
 
 impl FromStr for AtKey {
-    type Err = &'static str;
+    type Err = AtKeyError;
 
+    /// Parses a key in a single pass, only allocating the parts of the returned `AtKey`.
+    ///
+    /// The record ID ends at the first `.` and the rest up to the owner is the namespace,
+    /// so `phone.wavi.app@alice` has the record ID `phone` and the namespace `wavi.app`.
+    /// The namespace is optional for every visibility.
+    /// Keys without a visibility prefix, e.g. `shared_key.bob@alice`, are private.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO: Improve performance by only creating regex if the previous one doesn't match
-        // TODO: Improve performance by putting most common case first
-
-        // TODO: Check that it works with namespaces with multiple periods
         trace!("Constructing at_key from_str");
+        let invalid = || AtKeyError::InvalidFormat(s.to_owned());
 
-        // Regex patterns for each key type
-        let public_re =
-            Regex::new(r"^public:(?P<record_id>[^.]+)\.(?P<namespace>[^@]+)@(?P<owner>.+)$")
-                .unwrap();
-        let private_re =
-            Regex::new(r"^private:(?P<record_id>[^.]+)(?:\.(?P<namespace>[^@]+))?@(?P<owner>.+)$")
-                .unwrap();
-        let user_re = Regex::new(
-            r"^@(?P<shared_with>[^:]+):(?P<record_id>[^.]+)\.(?P<namespace>[^@]+)@(?P<owner>.+)$",
-        )
-        .unwrap();
-        let internal_re =
-            Regex::new(r"^_(?P<record_id>[^.]+)\.(?P<namespace>[^@]+)@(?P<owner>.+)$").unwrap();
-        let cached_re = Regex::new(r"^cached:@(?P<cached_by>[^:]+):(?P<record_id>[^.]+)\.(?P<namespace>[^@]+)@(?P<owner>.+)$").unwrap();
-
-        // Attempt to match each pattern and extract components
-        if let Some(caps) = public_re.captures(s) {
-            trace!("Matched public key pattern");
-            Ok(AtKey::new_public_key(
-                caps["record_id"].to_string(),
-                caps["namespace"].to_string(),
-                AtSign::new(caps["owner"].to_string()),
-            ))
-        } else if let Some(caps) = private_re.captures(s) {
-            trace!("Matched private key pattern");
-            Ok(AtKey::new_private_key(
-                caps["record_id"].to_string(),
-                caps.name("namespace").map(|m| m.as_str().to_string()), // Converts Option<Match> to Option<String>
-                AtSign::new(caps["owner"].to_string()),
-            ))
-        } else if let Some(caps) = user_re.captures(s) {
-            trace!("Matched user key pattern");
-            Ok(AtKey::new_user_key(
-                caps["record_id"].to_string(),
-                caps["namespace"].to_string(),
-                AtSign::new(caps["owner"].to_string()),
-                AtSign::new(caps["shared_with"].to_string()),
-            ))
-        } else if let Some(caps) = internal_re.captures(s) {
-            trace!("Matched internal key pattern");
-            Ok(AtKey::new_internal_key(
-                caps["record_id"].to_string(),
-                caps["namespace"].to_string(),
-                AtSign::new(caps["owner"].to_string()),
-            ))
-        } else if let Some(caps) = cached_re.captures(s) {
-            trace!("Matched cached key pattern");
-            Ok(AtKey::new_cached_key(
-                caps["record_id"].to_string(),
-                caps["namespace"].to_string(),
-                AtSign::new(caps["owner"].to_string()),
-                AtSign::new(caps["cached_by"].to_string()),
-            ))
+        let (is_cached, rest) = match s.strip_prefix("cached:") {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (visibility_scope, rest) = if let Some(rest) = rest.strip_prefix("public:") {
+            (Visibility::Public, rest)
+        } else if let Some(rest) = rest.strip_prefix("private:") {
+            (Visibility::Private, rest)
+        } else if let Some(rest) = rest.strip_prefix('_') {
+            (Visibility::Internal, rest)
+        } else if let Some(rest) = rest.strip_prefix('@') {
+            let (shared_with, rest) = rest.split_once(':').ok_or_else(invalid)?;
+            (Visibility::Shared(AtSign::from_str(shared_with)?), rest)
         } else {
-            error!("Input didn't match any expected key format.");
-            Err("Input does not match any expected key format. Expected format: [cached:]<visibility scope>:<record ID>.<namespace><owner’s atSign>")
+            (Visibility::Private, rest)
+        };
+
+        let (name, owner) = rest.rsplit_once('@').ok_or_else(invalid)?;
+        if name.contains([':', '@']) {
+            return Err(invalid());
         }
+        let (record_id, namespace) = match name.split_once('.') {
+            Some((record_id, namespace)) => {
+                if namespace.split('.').any(str::is_empty) {
+                    return Err(invalid());
+                }
+                (record_id, Some(namespace))
+            }
+            None => (name, None),
+        };
+        AtKey::try_new(
+            record_id,
+            namespace,
+            AtSign::from_str(owner)?,
+            is_cached,
+            visibility_scope,
+        )
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::collections::{BTreeSet, HashSet};
+
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -307,6 +410,15 @@ mod test {
         AtKey::new_private_key(record_id, Some(namespace), owner);
     }
 
+    #[test]
+    fn test_try_new_key_too_long() {
+        let record_id = "a".repeat(200);
+        let namespace = "b".repeat(200);
+        let owner = AtSign::new(String::from("alice"));
+        let result = AtKey::try_new_private_key(record_id, Some(namespace), owner);
+        assert_eq!(result.unwrap_err(), AtKeyError::TooLong(406));
+    }
+
     #[test]
     fn test_public_at_key_from_str() {
         let at_key = AtKey::from_str("public:record1.namespace1@alice").unwrap();
@@ -339,9 +451,192 @@ mod test {
         assert_eq!(at_key.to_string(), "cached:@bob:record1.namespace1@alice");
     }
 
+    #[test]
+    fn test_multi_segment_namespace_from_str() {
+        let at_key = AtKey::from_str("@bob:phone.wavi.app@alice").unwrap();
+        assert_eq!(at_key.record_id, "phone");
+        assert_eq!(at_key.namespace.as_deref(), Some("wavi.app"));
+        assert_eq!(at_key.to_string(), "@bob:phone.wavi.app@alice");
+    }
+
+    #[test]
+    fn test_at_key_without_namespace_from_str() {
+        for key in [
+            "public:publickey@bob",
+            "private:record1@alice",
+            "@bob:record1@alice",
+            "_latestnotificationid@alice",
+            "cached:@bob:record1@alice",
+        ] {
+            let at_key = AtKey::from_str(key).unwrap();
+            assert_eq!(at_key.namespace, None, "{}", key);
+            assert_eq!(at_key.to_string(), key);
+        }
+    }
+
+    #[test]
+    fn test_cached_public_at_key_from_str() {
+        let at_key = AtKey::from_str("cached:public:publickey@bob").unwrap();
+        assert!(at_key.is_cached);
+        assert_eq!(at_key.visibility_scope, Visibility::Public);
+        assert_eq!(at_key.owner, AtSign::new(String::from("bob")));
+        assert_eq!(at_key.to_string(), "cached:public:publickey@bob");
+    }
+
+    #[test]
+    fn test_unprefixed_at_key_from_str() {
+        let at_key = AtKey::from_str("shared_key.bob@alice").unwrap();
+        assert_eq!(at_key.visibility_scope, Visibility::Private);
+        assert_eq!(at_key.record_id, "shared_key");
+        assert_eq!(at_key.namespace.as_deref(), Some("bob"));
+        assert_eq!(at_key.owner, AtSign::new(String::from("alice")));
+    }
+
     #[test]
     fn test_at_key_from_str_invalid() {
         let at_key = AtKey::from_str("invalid");
-        assert!(at_key.is_err());
+        assert_eq!(
+            at_key.unwrap_err(),
+            AtKeyError::InvalidFormat(String::from("invalid"))
+        );
+        for key in [
+            "public:record1.@alice",
+            "public:record1..wavi@alice",
+            "public:a@b@alice",
+            "@bob@alice",
+            "unknown:record1@alice",
+        ] {
+            assert_eq!(
+                AtKey::from_str(key).unwrap_err(),
+                AtKeyError::InvalidFormat(String::from(key))
+            );
+        }
+        assert_eq!(
+            AtKey::from_str("public:.wavi@alice").unwrap_err(),
+            AtKeyError::EmptyRecordId
+        );
+        assert!(matches!(
+            AtKey::from_str("@:record1@alice"),
+            Err(AtKeyError::InvalidAtSign(AtSignError::Empty))
+        ));
+        let too_long = format!("public:{}.{}@alice", "a".repeat(200), "b".repeat(200));
+        assert!(matches!(
+            AtKey::from_str(&too_long),
+            Err(AtKeyError::TooLong(_))
+        ));
+        let owner_too_long = format!("public:record1.namespace1@{}", "a".repeat(60));
+        assert!(matches!(
+            AtKey::from_str(&owner_too_long),
+            Err(AtKeyError::InvalidAtSign(AtSignError::TooLong(60)))
+        ));
+    }
+
+    #[test]
+    fn test_full_key() {
+        for (key, full_key) in [
+            ("public:phone.wavi@alice", "public:phone.wavi@alice"),
+            ("private:phone.wavi@alice", "phone.wavi@alice"),
+            ("_phone.wavi@alice", "_phone.wavi@alice"),
+            ("@bob:phone.wavi@alice", "@bob:phone.wavi@alice"),
+            (
+                "cached:@bob:phone.wavi@alice",
+                "cached:@bob:phone.wavi@alice",
+            ),
+            (
+                "cached:public:publickey@alice",
+                "cached:public:publickey@alice",
+            ),
+        ] {
+            assert_eq!(AtKey::from_str(key).unwrap().full_key(), full_key);
+        }
+    }
+
+    #[test]
+    fn test_lookup_key() {
+        for (key, lookup_key) in [
+            ("public:phone.wavi@alice", "phone.wavi@alice"),
+            ("private:phone.wavi@alice", "phone.wavi@alice"),
+            ("_phone.wavi@alice", "_phone.wavi@alice"),
+            ("@bob:phone.wavi@alice", "phone.wavi@alice"),
+            ("cached:@bob:phone.wavi@alice", "phone.wavi@alice"),
+            ("cached:public:publickey@alice", "publickey@alice"),
+        ] {
+            assert_eq!(AtKey::from_str(key).unwrap().lookup_key(), lookup_key);
+        }
+    }
+
+    #[test]
+    fn test_at_keys_in_sets() {
+        let keys: BTreeSet<AtKey> = [
+            "public:phone.wavi@alice",
+            "@bob:phone.wavi@alice",
+            "public:email.wavi@alice",
+            "public:phone.wavi@alice",
+        ]
+        .iter()
+        .map(|key| AtKey::from_str(key).unwrap())
+        .collect();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys.first().unwrap().record_id, "email");
+        let keys: HashSet<AtKey> = keys.into_iter().collect();
+        assert!(keys.contains(&AtKey::from_str("@bob:phone.wavi@alice").unwrap()));
+    }
+
+    fn at_sign() -> impl Strategy<Value = AtSign> {
+        "[a-z0-9_🦄é]{1,20}".prop_map(AtSign::new)
+    }
+
+    fn visibility() -> impl Strategy<Value = Visibility> {
+        prop_oneof![
+            Just(Visibility::Public),
+            Just(Visibility::Private),
+            Just(Visibility::Internal),
+            at_sign().prop_map(Visibility::Shared),
+        ]
+    }
+
+    fn at_key() -> impl Strategy<Value = AtKey> {
+        (
+            "[a-z0-9_-]{1,30}",
+            proptest::option::of("[a-z0-9_-]{1,10}(\\.[a-z0-9_-]{1,10}){0,3}"),
+            at_sign(),
+            any::<bool>(),
+            visibility(),
+        )
+            .prop_map(
+                |(record_id, namespace, owner, is_cached, visibility_scope)| {
+                    AtKey::try_new(
+                        &record_id,
+                        namespace.as_deref(),
+                        owner,
+                        is_cached,
+                        visibility_scope,
+                    )
+                    .unwrap()
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_from_str_round_trips_display(at_key in at_key()) {
+            let parsed = AtKey::from_str(&at_key.to_string()).unwrap();
+            prop_assert_eq!(parsed, at_key);
+        }
+
+        #[test]
+        fn test_from_str_round_trips_full_key(at_key in at_key()) {
+            // Without a prefix, a private record ID starting with `_` reads as an internal key.
+            prop_assume!(
+                at_key.visibility_scope != Visibility::Private || !at_key.record_id.starts_with('_')
+            );
+            let parsed = AtKey::from_str(&at_key.full_key()).unwrap();
+            prop_assert_eq!(parsed, at_key);
+        }
+
+        #[test]
+        fn test_from_str_never_panics(key in "\\PC*") {
+            let _ = AtKey::from_str(&key);
+        }
     }
 }
@@ -0,0 +1,369 @@
+use at_sign::AtSign;
+
+use crate::at_key::{AtKey, Visibility};
+
+/// The kind of `Visibility` a key must have to match an `AtKeyFilter`, regardless of who it is shared with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VisibilityKind {
+    Public,
+    Private,
+    Internal,
+    Shared,
+}
+
+/**
+Selects keys by their parts, e.g. all keys in the namespace `devices.myapp` shared with @bob.
+
+A filter can be turned into the regex sent with the scan verb using `to_regex`,
+and checked against the returned keys with `matches`.
+The regex only narrows down what the atServer sends back, as some filters such as private vs internal keys can't be told apart by a regex.
+Every part is optional; the empty filter matches every key.
+
+```
+use at_records::at_key_filter::AtKeyFilter;
+use at_sign::AtSign;
+
+let filter = AtKeyFilter::new()
+    .with_namespace_prefix("devices.myapp")
+    .with_shared_with(AtSign::new(String::from("bob")));
+assert!(filter.matches(&"@bob:laptop.devices.myapp@alice".parse().unwrap()));
+assert!(!filter.matches(&"@bob:laptop.myapp@alice".parse().unwrap()));
+```
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AtKeyFilter {
+    namespace_prefix: Option<String>,
+    owner: Option<AtSign>,
+    shared_with: Option<AtSign>,
+    visibility: Option<VisibilityKind>,
+    is_cached: Option<bool>,
+    record_id_glob: Option<String>,
+}
+
+impl AtKeyFilter {
+    /// A filter which matches every key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keys whose namespace is `namespace_prefix` or starts with `namespace_prefix` followed by a `.`,
+    /// so `devices.myapp` matches `devices.myapp.v2` but not `devices.myapp2` or keys without a namespace.
+    pub fn with_namespace_prefix<T: Into<String>>(mut self, namespace_prefix: T) -> Self {
+        self.namespace_prefix = Some(namespace_prefix.into());
+        self
+    }
+
+    pub fn with_owner(mut self, owner: AtSign) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// Only keys shared with `shared_with`, including keys cached by them.
+    pub fn with_shared_with(mut self, shared_with: AtSign) -> Self {
+        self.shared_with = Some(shared_with);
+        self
+    }
+
+    pub fn with_visibility(mut self, visibility: VisibilityKind) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    /// Only cached keys if `is_cached` is true, otherwise only keys which aren't cached.
+    pub fn with_cached(mut self, is_cached: bool) -> Self {
+        self.is_cached = Some(is_cached);
+        self
+    }
+
+    /// Only keys whose record ID matches `glob`, where `*` matches any number of characters and `?` a single character.
+    pub fn with_record_id_glob<T: Into<String>>(mut self, glob: T) -> Self {
+        self.record_id_glob = Some(glob.into());
+        self
+    }
+
+    /// Whether `at_key` has all the parts the filter asks for.
+    pub fn matches(&self, at_key: &AtKey) -> bool {
+        if let Some(prefix) = &self.namespace_prefix {
+            let namespace_matches = at_key.namespace.as_deref().is_some_and(|namespace| {
+                namespace
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            });
+            if !namespace_matches {
+                return false;
+            }
+        }
+        if self
+            .owner
+            .as_ref()
+            .is_some_and(|owner| *owner != at_key.owner)
+        {
+            return false;
+        }
+        if let Some(shared_with) = &self.shared_with {
+            if at_key.visibility_scope != Visibility::Shared(shared_with.clone()) {
+                return false;
+            }
+        }
+        if let Some(visibility) = self.visibility {
+            let kind = match at_key.visibility_scope {
+                Visibility::Public => VisibilityKind::Public,
+                Visibility::Private => VisibilityKind::Private,
+                Visibility::Internal => VisibilityKind::Internal,
+                Visibility::Shared(_) => VisibilityKind::Shared,
+            };
+            if kind != visibility {
+                return false;
+            }
+        }
+        if self
+            .is_cached
+            .is_some_and(|is_cached| is_cached != at_key.is_cached)
+        {
+            return false;
+        }
+        match &self.record_id_glob {
+            Some(glob) => glob_matches(glob, &at_key.record_id),
+            None => true,
+        }
+    }
+
+    /// A regex for the scan verb which matches the `full_key` of every key that `matches` accepts.
+    pub fn to_regex(&self) -> String {
+        let mut regex = String::from("^");
+        match self.is_cached {
+            Some(true) => regex.push_str("cached:"),
+            Some(false) => {}
+            None => regex.push_str("(cached:)?"),
+        }
+
+        let shared_with = match &self.shared_with {
+            Some(shared_with) => escape(&shared_with.get_at_sign_without_prefix()),
+            None => String::from("[^:@]+"),
+        };
+        match (self.visibility, &self.shared_with) {
+            (Some(VisibilityKind::Shared), _) | (None, Some(_)) => {
+                regex.push_str(&format!("@{}:", shared_with));
+            }
+            (Some(VisibilityKind::Public), _) => regex.push_str("public:"),
+            (Some(VisibilityKind::Internal), _) => regex.push('_'),
+            // Private keys have no prefix, but a private record ID may start with `_`
+            (Some(VisibilityKind::Private), _) => {}
+            (None, None) => regex.push_str("(public:|@[^:@]+:|_)?"),
+        }
+
+        match &self.record_id_glob {
+            Some(glob) => regex.push_str(&glob_to_regex(glob)),
+            None => regex.push_str("[^.:@]+"),
+        }
+        match &self.namespace_prefix {
+            Some(prefix) => regex.push_str(&format!(r"\.{}(\.[^@]+)?", escape(prefix))),
+            None => regex.push_str(r"(\.[^@]+)?"),
+        }
+        match &self.owner {
+            Some(owner) => {
+                regex.push_str(&format!("@{}", escape(&owner.get_at_sign_without_prefix())))
+            }
+            None => regex.push_str("@[^@]+"),
+        }
+        regex.push('$');
+        regex
+    }
+}
+
+/// Matches `text` against a glob of `*` and `?` wildcards, backtracking to the last `*` on a mismatch.
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // The position of the last `*` and the text it has matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    star = Some((star_g, star_t + 1));
+                    g = star_g + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/// Record IDs can't contain `.`, `:` or `@`, so wildcards don't match them.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str("[^.:@]*"),
+            '?' => regex.push_str("[^.:@]"),
+            c => regex.push_str(&escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex
+}
+
+fn escape(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+    use regex::Regex;
+
+    use super::*;
+
+    fn at_sign(at_sign: &str) -> AtSign {
+        AtSign::new(String::from(at_sign))
+    }
+
+    fn key(key: &str) -> AtKey {
+        AtKey::from_str(key).unwrap()
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = AtKeyFilter::new();
+        let regex = Regex::new(&filter.to_regex()).unwrap();
+        for k in [
+            "public:phone.wavi@alice",
+            "phone@alice",
+            "_latestnotificationid.wavi@alice",
+            "cached:@bob:phone.wavi@alice",
+        ] {
+            assert!(filter.matches(&key(k)));
+            assert!(regex.is_match(&key(k).full_key()), "{}", k);
+        }
+    }
+
+    #[test]
+    fn test_namespace_prefix() {
+        let filter = AtKeyFilter::new().with_namespace_prefix("devices.myapp");
+        assert!(filter.matches(&key("public:laptop.devices.myapp@alice")));
+        assert!(filter.matches(&key("public:laptop.devices.myapp.v2@alice")));
+        assert!(!filter.matches(&key("public:laptop.devices.myapp2@alice")));
+        assert!(!filter.matches(&key("public:laptop.myapp@alice")));
+        assert!(!filter.matches(&key("public:laptop@alice")));
+    }
+
+    #[test]
+    fn test_owner_shared_with_and_visibility() {
+        let filter = AtKeyFilter::new()
+            .with_owner(at_sign("alice"))
+            .with_shared_with(at_sign("bob"));
+        assert!(filter.matches(&key("@bob:phone.wavi@alice")));
+        assert!(filter.matches(&key("cached:@bob:phone.wavi@alice")));
+        assert!(!filter.matches(&key("@charlie:phone.wavi@alice")));
+        assert!(!filter.matches(&key("@bob:phone.wavi@charlie")));
+        assert!(!filter.matches(&key("public:phone.wavi@alice")));
+
+        let filter = AtKeyFilter::new().with_visibility(VisibilityKind::Internal);
+        assert!(filter.matches(&key("_phone.wavi@alice")));
+        assert!(!filter.matches(&key("phone.wavi@alice")));
+    }
+
+    #[test]
+    fn test_cached() {
+        let filter = AtKeyFilter::new().with_cached(false);
+        assert!(filter.matches(&key("public:publickey@bob")));
+        assert!(!filter.matches(&key("cached:public:publickey@bob")));
+        assert_eq!(
+            filter.to_regex(),
+            r"^(public:|@[^:@]+:|_)?[^.:@]+(\.[^@]+)?@[^@]+$"
+        );
+    }
+
+    #[test]
+    fn test_record_id_glob() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("phone*", "phone_work"));
+        assert!(glob_matches("*_work", "phone_work"));
+        assert!(glob_matches("p?one*k", "phone_work"));
+        assert!(glob_matches("*o*o*", "phone_work"));
+        assert!(!glob_matches("phone", "phone_work"));
+        assert!(!glob_matches("*home", "phone_work"));
+        assert!(!glob_matches("?", ""));
+    }
+
+    #[test]
+    fn test_to_regex() {
+        let filter = AtKeyFilter::new()
+            .with_namespace_prefix("devices.myapp")
+            .with_owner(at_sign("alice"))
+            .with_shared_with(at_sign("bob"))
+            .with_cached(false)
+            .with_record_id_glob("lap*");
+        assert_eq!(
+            filter.to_regex(),
+            r"^@bob:lap[^.:@]*\.devices\.myapp(\.[^@]+)?@alice$"
+        );
+        let regex = Regex::new(&filter.to_regex()).unwrap();
+        assert!(regex.is_match("@bob:laptop.devices.myapp@alice"));
+        assert!(!regex.is_match("@bob:phone.devices.myapp@alice"));
+        assert!(!regex.is_match("cached:@bob:laptop.devices.myapp@alice"));
+    }
+
+    fn filter() -> impl Strategy<Value = AtKeyFilter> {
+        let visibility = prop_oneof![
+            Just(VisibilityKind::Public),
+            Just(VisibilityKind::Private),
+            Just(VisibilityKind::Internal),
+            Just(VisibilityKind::Shared),
+        ];
+        (
+            proptest::option::of("a|b|a\\.b"),
+            proptest::option::of("alice|bob"),
+            proptest::option::of("alice|bob"),
+            proptest::option::of(visibility),
+            proptest::option::of(any::<bool>()),
+            proptest::option::of("[ab*?]{1,3}"),
+        )
+            .prop_map(
+                |(namespace_prefix, owner, shared_with, visibility, is_cached, record_id_glob)| {
+                    AtKeyFilter {
+                        namespace_prefix,
+                        owner: owner.map(AtSign::new),
+                        shared_with: shared_with.map(AtSign::new),
+                        visibility,
+                        is_cached,
+                        record_id_glob,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_regex_matches_whatever_filter_matches(
+            filter in filter(),
+            key in "(cached:)?(public:|@(alice|bob):|_)?[ab][ab_]{0,2}(\\.(a|b)){0,2}@(alice|bob)",
+        ) {
+            let at_key = AtKey::from_str(&key).unwrap();
+            let regex = Regex::new(&filter.to_regex()).unwrap();
+            if filter.matches(&at_key) {
+                prop_assert!(regex.is_match(&at_key.full_key()), "{} {:?}", at_key.full_key(), filter);
+            }
+        }
+    }
+}
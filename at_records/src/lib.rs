@@ -1,3 +1,4 @@
 pub mod at_key;
+pub mod at_key_filter;
 pub mod at_record;
 pub mod record_metadata;
@@ -1,18 +1,28 @@
-#![allow(unused_variables)]
 #![allow(dead_code)]
 
-#[derive(Debug)]
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+/// The metadata the atServer keeps for a record, as returned by `llookup:meta:`, `lookup:meta:` and `plookup:meta:`.
+///
+/// Dates are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct RecordMetadata {
     /// A Date and Time derived from the ttb (now + ttb). A Key should be only available after availableFrom.
-    available_from: usize,
+    #[serde(rename = "availableAt", deserialize_with = "deserialize_date")]
+    available_from: Option<usize>,
     /// Indicates if a cached key needs to be deleted when the atSign user who has originally shared it deletes it.
     ccd: Option<bool>,
     /// atSign that has created the key
-    created_by: String,
+    created_by: Option<String>,
     /// Date and time when the key has been created.
-    created_on: usize,
+    #[serde(rename = "createdAt", deserialize_with = "deserialize_date")]
+    created_on: Option<usize>,
     /// A Date and Time derived from the ttl (now + ttl). A Key should be auto deleted once it expires.
-    expires_on: usize,
+    #[serde(rename = "expiresAt", deserialize_with = "deserialize_date")]
+    expires_on: Option<usize>,
     /// True if the value is a binary value.
     is_binary: bool,
     /// True if the key is cached.
@@ -20,15 +30,161 @@ pub struct RecordMetadata {
     /// True if the value is encrypted.
     is_encrypted: bool,
     /// A Date and Time derived from the ttr. The time at which the key gets refreshed.
+    #[serde(deserialize_with = "deserialize_date")]
     refresh_at: Option<usize>,
     /// atSign of the user with whom the key has been shared. Can be null if not shared with anyone.
     shared_with: Option<String>,
     /// Date and time when the key has been last updated.
-    updated_on: usize,
+    #[serde(rename = "updatedAt", deserialize_with = "deserialize_date")]
+    updated_on: Option<usize>,
     /// Time to birth in milliseconds.
     ttb: Option<usize>,
     /// Time to live in milliseconds.
     ttl: Option<usize>,
     /// Time in milliseconds after which the cached key needs to be refreshed. A ttr of -1 indicates that the key can be cached forever. ttr of 0 indicates do not refresh. ttr of > 0 will refresh the key. ttr of null indicates the key is impossible to cache, hence, refreshing does not make sense (which has the same effect as a ttr of 0).
-    ttr: Option<usize>,
+    ttr: Option<i64>,
+}
+
+impl RecordMetadata {
+    /// Time in milliseconds after which a cached copy needs to be refreshed, see `needs_refresh`.
+    pub fn ttr(&self) -> Option<i64> {
+        self.ttr
+    }
+
+    /// When a cached copy needs to be refreshed, in milliseconds since the Unix epoch.
+    pub fn refresh_at(&self) -> Option<usize> {
+        self.refresh_at
+    }
+
+    /// Whether a cached copy of the record is stale at `now` (milliseconds since the Unix epoch),
+    /// so it should be looked up again from its owner's atServer instead of read from the cache.
+    ///
+    /// A ttr of -1 means the copy never goes stale. A ttr of 0 or no ttr means it shouldn't have been cached at all.
+    /// Otherwise the copy is stale from `refresh_at`, or straight away if the atServer didn't say when that is.
+    pub fn needs_refresh(&self, now: usize) -> bool {
+        match self.ttr {
+            Some(-1) => false,
+            Some(ttr) if ttr > 0 => self.refresh_at.is_none_or(|refresh_at| now >= refresh_at),
+            _ => true,
+        }
+    }
+}
+
+impl FromStr for RecordMetadata {
+    type Err = serde_json::Error;
+
+    /// Parses the metadata JSON sent by the atServer.
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(json)
+    }
+}
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(date) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_date(&date)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid date: {}", date)))
+}
+
+/// Parses a UTC date like `2024-02-06 10:15:30.123Z` (or with a `T` separator) into milliseconds since the Unix epoch.
+fn parse_date(date: &str) -> Option<usize> {
+    let date = date.strip_suffix('Z').unwrap_or(date);
+    let (day, time) = date.split_once([' ', 'T'])?;
+
+    let mut day_parts = day.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (
+        day_parts.next()?.ok()?,
+        day_parts.next()?.ok()?,
+        day_parts.next()?.ok()?,
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let mut time_parts = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hours, minutes, seconds) = (
+        time_parts.next()?.ok()?,
+        time_parts.next()?.ok()?,
+        time_parts.next()?.ok()?,
+    );
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let millis: i64 = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse()
+        .ok()?;
+
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    usize::try_from(seconds * 1_000 + millis).ok()
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01 00:00:00.000Z"), Some(0));
+        assert_eq!(
+            parse_date("2024-02-06 10:15:30.123Z"),
+            Some(1_707_214_530_123)
+        );
+        assert_eq!(
+            parse_date("2024-02-06T10:15:30.123456Z"),
+            Some(1_707_214_530_123)
+        );
+        assert_eq!(parse_date("2000-03-01 00:00:00Z"), Some(951_868_800_000));
+        assert_eq!(parse_date("2024-13-06 10:15:30.123Z"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let metadata = RecordMetadata::from_str(
+            r#"{"createdBy":"@alice","createdAt":"2024-02-06 10:15:30.123Z","refreshAt":"2024-02-06 11:15:30.123Z","ttr":3600000,"ttl":null,"isBinary":false,"isEncrypted":true,"status":"active"}"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.created_by.as_deref(), Some("@alice"));
+        assert_eq!(metadata.created_on, Some(1_707_214_530_123));
+        assert_eq!(metadata.refresh_at(), Some(1_707_218_130_123));
+        assert_eq!(metadata.ttr(), Some(3_600_000));
+        assert_eq!(metadata.ttl, None);
+        assert!(metadata.is_encrypted);
+
+        assert!(RecordMetadata::from_str(r#"{"refreshAt":"soon"}"#).is_err());
+        assert!(RecordMetadata::from_str("not json").is_err());
+    }
+
+    #[test]
+    fn test_needs_refresh() {
+        let refresh_at = 1_707_218_130_123;
+        let metadata = |ttr, refresh_at| RecordMetadata {
+            ttr,
+            refresh_at,
+            ..Default::default()
+        };
+        assert!(!metadata(Some(-1), None).needs_refresh(refresh_at));
+        assert!(metadata(Some(0), None).needs_refresh(0));
+        assert!(metadata(None, None).needs_refresh(0));
+        assert!(!metadata(Some(1000), Some(refresh_at)).needs_refresh(refresh_at - 1));
+        assert!(metadata(Some(1000), Some(refresh_at)).needs_refresh(refresh_at));
+        assert!(metadata(Some(1000), None).needs_refresh(0));
+    }
 }
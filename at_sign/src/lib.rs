@@ -1,19 +1,53 @@
 use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq)]
+#[cfg(feature = "serde")]
+mod serde;
+
+/// The maximum length of an atSign in characters, without the `@` prefix.
+pub const MAX_AT_SIGN_LENGTH: usize = 55;
+
+/// An atSign such as `@alice`.
+///
+/// atSigns are case-insensitive, so they are stored in lowercase and `@Alice` equals `@alice`.
+/// Ordering compares the lowercase names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AtSign {
-    /// The atSign of the client device. Without the `@` prefix.
+    /// The atSign of the client device. Without the `@` prefix, in lowercase.
     at_sign: String,
 }
 
 impl AtSign {
-    /// Create a new atSign. `at_sign` should be a string without the `@` prefix.
+    /// Create a new atSign from a string with or without the `@` prefix.
+    ///
+    /// # Panics
+    /// If `at_sign` is not a valid atSign. Use `try_new` or `str::parse` for atSigns from user input.
     pub fn new(at_sign: String) -> AtSign {
-        assert!(
-            at_sign.len() <= 55,
-            "at_sign must be less than 55 characters"
-        );
-        AtSign { at_sign }
+        match Self::try_new(at_sign) {
+            Ok(at_sign) => at_sign,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create a new atSign from a string with or without the `@` prefix, checking that it is valid.
+    ///
+    /// The name is lowercased. It must not be empty, be longer than `MAX_AT_SIGN_LENGTH`,
+    /// or contain characters reserved by the atProtocol: `@`, `:`, whitespace and control characters.
+    pub fn try_new(at_sign: String) -> Result<AtSign, AtSignError> {
+        let name = at_sign.strip_prefix('@').unwrap_or(&at_sign);
+        if name.is_empty() {
+            return Err(AtSignError::Empty);
+        }
+        if let Some(reserved) = name.chars().find(|c| is_reserved(*c)) {
+            return Err(AtSignError::ReservedCharacter(reserved));
+        }
+        let length = name.chars().count();
+        if length > MAX_AT_SIGN_LENGTH {
+            return Err(AtSignError::TooLong(length));
+        }
+        Ok(AtSign {
+            at_sign: name.to_lowercase(),
+        })
     }
 
     /// Get the name of the atSign without the `@` prefix.
@@ -32,3 +66,130 @@ impl Display for AtSign {
         write!(f, "@{}", self.at_sign)
     }
 }
+
+impl FromStr for AtSign {
+    type Err = AtSignError;
+
+    /// Parses an atSign with or without the `@` prefix. See `AtSign::try_new`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_new(s.to_owned())
+    }
+}
+
+fn is_reserved(c: char) -> bool {
+    c == '@' || c == ':' || c.is_whitespace() || c.is_control()
+}
+
+/// Why a string is not a valid atSign.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AtSignError {
+    Empty,
+    /// The atSign has this many characters, more than `MAX_AT_SIGN_LENGTH`.
+    TooLong(usize),
+    /// The atSign contains a character which the atProtocol reserves, e.g. `:` or a space.
+    ReservedCharacter(char),
+}
+
+impl Display for AtSignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtSignError::Empty => write!(f, "atSign must not be empty"),
+            AtSignError::TooLong(length) => write!(
+                f,
+                "atSign must be at most {} characters but has {}",
+                MAX_AT_SIGN_LENGTH, length
+            ),
+            AtSignError::ReservedCharacter(c) => {
+                write!(f, "atSign must not contain {:?}", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtSignError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_new() {
+        let at_sign = AtSign::try_new(String::from("alice")).unwrap();
+        assert_eq!(at_sign.to_string(), "@alice");
+        assert_eq!(AtSign::try_new(String::new()), Err(AtSignError::Empty));
+        assert_eq!(
+            AtSign::try_new("a".repeat(56)),
+            Err(AtSignError::TooLong(56))
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let at_sign: AtSign = "@alice".parse().unwrap();
+        assert_eq!(at_sign.get_at_sign_without_prefix(), "alice");
+        let at_sign: AtSign = "alice".parse().unwrap();
+        assert_eq!(at_sign.get_at_sign_with_prefix(), "@alice");
+        assert!("@".parse::<AtSign>().is_err());
+    }
+
+    #[test]
+    fn test_normalization() {
+        let at_sign: AtSign = "@Alice".parse().unwrap();
+        assert_eq!(at_sign.get_at_sign_without_prefix(), "alice");
+        assert_eq!(at_sign, AtSign::new(String::from("ALICE")));
+        assert_eq!(AtSign::new(String::from("@bob")).to_string(), "@bob");
+    }
+
+    #[test]
+    fn test_reserved_characters() {
+        for (at_sign, reserved) in [
+            ("ali ce", ' '),
+            ("alice:bob", ':'),
+            ("alice@bob", '@'),
+            ("@@alice", '@'),
+            ("alice\t", '\t'),
+            ("alice\u{7}", '\u{7}'),
+        ] {
+            assert_eq!(
+                at_sign.parse::<AtSign>(),
+                Err(AtSignError::ReservedCharacter(reserved)),
+                "{:?}",
+                at_sign
+            );
+        }
+    }
+
+    #[test]
+    fn test_unicode() {
+        let at_sign: AtSign = "@🦄ÉLAN".parse().unwrap();
+        assert_eq!(at_sign.get_at_sign_with_prefix(), "@🦄élan");
+        // Length is counted in characters, not bytes.
+        assert!(AtSign::try_new("🦄".repeat(MAX_AT_SIGN_LENGTH)).is_ok());
+        assert_eq!(
+            AtSign::try_new("🦄".repeat(MAX_AT_SIGN_LENGTH + 1)),
+            Err(AtSignError::TooLong(MAX_AT_SIGN_LENGTH + 1))
+        );
+    }
+
+    #[test]
+    fn test_ord_and_hash() {
+        let mut at_signs: Vec<AtSign> = ["@Charlie", "bob", "@alice", "@BOB"]
+            .iter()
+            .map(|at_sign| at_sign.parse().unwrap())
+            .collect();
+        at_signs.sort();
+        at_signs.dedup();
+        assert_eq!(
+            at_signs.iter().map(AtSign::to_string).collect::<Vec<_>>(),
+            vec!["@alice", "@bob", "@charlie"]
+        );
+        let set: std::collections::HashSet<AtSign> = at_signs.into_iter().collect();
+        assert!(set.contains(&"@ALICE".parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 55 characters")]
+    fn test_new_too_long() {
+        AtSign::new("a".repeat(56));
+    }
+}
@@ -0,0 +1,44 @@
+//! atSigns are (de)serialized as strings with the `@` prefix, e.g. `"@alice"`.
+
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::AtSign;
+
+impl Serialize for AtSign {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AtSign {
+    /// Accepts atSigns with or without the `@` prefix, validated like `AtSign::try_new`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let at_sign = String::deserialize(deserializer)?;
+        AtSign::from_str(&at_sign).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let at_sign = AtSign::new(String::from("alice"));
+        let json = serde_json::to_string(&at_sign).unwrap();
+        assert_eq!(json, "\"@alice\"");
+        assert_eq!(serde_json::from_str::<AtSign>(&json).unwrap(), at_sign);
+        assert_eq!(
+            serde_json::from_str::<AtSign>("\"Alice\"").unwrap(),
+            at_sign
+        );
+    }
+
+    #[test]
+    fn test_deserialize_invalid() {
+        let error = serde_json::from_str::<AtSign>("\"alice:bob\"").unwrap_err();
+        assert!(error.to_string().contains("must not contain ':'"));
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::async_tls_connection_trait::AsyncTlsConnection;
+use crate::at_server_addr::AtServerAddr;
+use crate::retry_policy::RetryPolicy;
+use crate::tls_connection_config::TlsConnectionConfig;
+use crate::tokio_rustls_connection::with_timeout;
+use crate::trace::TraceRecorder;
+
+/// The async counterpart of `TlsClient`.
+pub struct AsyncTlsClient {
+    /// Kept for the lifetime of the connection so bytes read past a newline aren't lost between reads.
+    reader: BufReader<Box<dyn AsyncTlsConnection>>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    /// Records the session when `record_trace_to` is set, like `RecordingConnection` does for `TlsClient`.
+    recorder: Option<TraceRecorder>,
+}
+
+impl AsyncTlsClient {
+    /// Create a new client without read or write timeouts. Only for testing.
+    pub fn new(tls_connection: Box<dyn AsyncTlsConnection>) -> Self {
+        Self {
+            reader: BufReader::new(tls_connection),
+            read_timeout: None,
+            write_timeout: None,
+            recorder: None,
+        }
+    }
+
+    /// Connects to the specified server address using TLS with the given timeouts.
+    ///
+    /// Reads and writes that exceed the configured timeouts fail with `std::io::ErrorKind::TimedOut`.
+    /// If `config.record_trace_to` is set, the session is recorded to that file.
+    pub async fn connect_with_config<T: AsyncTlsConnection + 'static>(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> std::io::Result<Self> {
+        let recorder = match &config.record_trace_to {
+            Some(path) => Some(TraceRecorder::append_to_file(path, address)?),
+            None => None,
+        };
+        let tls_connection = T::connect_with_config(address, config).await?;
+        Ok(Self {
+            reader: BufReader::new(Box::new(tls_connection)),
+            read_timeout: config.read_timeout,
+            write_timeout: config.write_timeout,
+            recorder,
+        })
+    }
+
+    /// Connects to the specified server address, retrying failed attempts according to `retry_policy`.
+    ///
+    /// Returns the error of the last attempt if all attempts fail.
+    pub async fn connect_with_retry<T: AsyncTlsConnection + 'static>(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+        retry_policy: &RetryPolicy,
+    ) -> std::io::Result<Self> {
+        let mut attempt = 1;
+        loop {
+            match Self::connect_with_config::<T>(address, config).await {
+                Ok(client) => return Ok(client),
+                Err(e) if attempt < retry_policy.max_attempts => {
+                    let delay = retry_policy.delay_for_retry(attempt);
+                    warn!(
+                        "Failed to connect to {}:{} (attempt {}): {}. Retrying in {:?}",
+                        address.host, address.port, attempt, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends data to the server.
+    /// Data is expected to be one line (no newlines in the middle of the data).
+    /// Appends a newline to the data as this is the delimiter in the protocol.
+    pub async fn send_data<U: AsRef<[u8]>>(&mut self, data: U) -> std::io::Result<()> {
+        let data_slice = data.as_ref();
+        let mut data_with_newline = Vec::with_capacity(data_slice.len() + 1);
+        data_with_newline.extend_from_slice(data_slice);
+        data_with_newline.push(b'\n');
+        debug!(
+            "Sending data: {:?}",
+            String::from_utf8_lossy(&data_with_newline)
+        );
+        let connection = self.reader.get_mut();
+        with_timeout(self.write_timeout, async {
+            connection.write_all(&data_with_newline).await?;
+            connection.flush().await
+        })
+        .await?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_sent(&String::from_utf8_lossy(data_slice));
+        }
+        Ok(())
+    }
+
+    /// Reads a line from the stream and returns the bytes.
+    pub async fn read_data(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut res = vec![];
+        // Newline is the delimiter in the protocol
+        let bytes_read =
+            with_timeout(self.read_timeout, self.reader.read_until(b'\n', &mut res)).await?;
+        if bytes_read == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Connection closed by server",
+            ));
+        }
+        debug!("Reading data: {:?}", String::from_utf8_lossy(&res));
+        if let Some(recorder) = &mut self.recorder {
+            let line = res.strip_suffix(b"\n").unwrap_or(&res);
+            recorder.record_received(&String::from_utf8_lossy(line));
+        }
+        Ok(res)
+    }
+
+    /// Shuts the connection down cleanly, e.g. with a TLS close_notify.
+    ///
+    /// Unlike `TlsClient`, nothing is done when the client is dropped, as that can't wait for the shutdown.
+    pub async fn close(&mut self) -> std::io::Result<()> {
+        debug!("Closing connection");
+        let connection = self.reader.get_mut();
+        with_timeout(self.write_timeout, connection.shutdown()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::tokio_rustls_connection::TokioRustlsConnection;
+
+    #[tokio::test]
+    async fn test_send_and_read_data() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let config = config_trusting(&certified_key);
+        let mut subject =
+            AsyncTlsClient::connect_with_config::<TokioRustlsConnection>(&address, &config)
+                .await
+                .unwrap();
+        subject.send_data("first").await.unwrap();
+        subject.send_data("second").await.unwrap();
+        assert_eq!(subject.read_data().await.unwrap(), b"data:first\n");
+        assert_eq!(subject.read_data().await.unwrap(), b"data:second\n");
+    }
+
+    #[tokio::test]
+    async fn test_records_redacted_session() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let mut config = config_trusting(&certified_key);
+        let trace_path = std::env::temp_dir().join(format!(
+            "at_tls_async_trace_{}_{}.txt",
+            std::process::id(),
+            address.port
+        ));
+        config.record_trace_to = Some(trace_path.clone());
+        let mut subject =
+            AsyncTlsClient::connect_with_config::<TokioRustlsConnection>(&address, &config)
+                .await
+                .unwrap();
+        for command in ["pkam:c2lnbmF0dXJl", "llookup:phone@alice"] {
+            subject.send_data(command).await.unwrap();
+            subject.read_data().await.unwrap();
+        }
+
+        let trace = std::fs::read_to_string(&trace_path).unwrap();
+        std::fs::remove_file(&trace_path).unwrap();
+        assert_eq!(
+            trace,
+            format!(
+                "# session localhost:{}\n\
+                 > pkam:<redacted>\n< data:pkam:c2lnbmF0dXJl\n\
+                 > llookup:phone@alice\n< data:<redacted>\n",
+                address.port
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_data_timeout() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let mut config = config_trusting(&certified_key);
+        config.read_timeout = Some(Duration::from_millis(200));
+        let mut subject =
+            AsyncTlsClient::connect_with_config::<TokioRustlsConnection>(&address, &config)
+                .await
+                .unwrap();
+        // Nothing has been sent, so the server has nothing to answer
+        let res = subject.read_data().await;
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_gives_up() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = AtServerAddr::new(
+            String::from("127.0.0.1"),
+            listener.local_addr().unwrap().port(),
+        );
+        drop(listener);
+        let retry_policy =
+            RetryPolicy::new(3, Duration::from_millis(10), Duration::from_millis(20));
+        let res = AsyncTlsClient::connect_with_retry::<TokioRustlsConnection>(
+            &address,
+            &TlsConnectionConfig::default(),
+            &retry_policy,
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use std::future::Future;
+use std::io::Result;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::at_server_addr::AtServerAddr;
+use crate::tls_connection_config::TlsConnectionConfig;
+
+/// The async counterpart of `TlsConnection`.
+///
+/// This trait is used to abstract the underlying async TLS library used to connect to the server.
+/// The super traits `AsyncRead` and `AsyncWrite` are used to read and write data to the server.
+pub trait AsyncTlsConnection: AsyncRead + AsyncWrite + Unpin + Send {
+    /// Creates a new TLS connection to the specified server address.
+    ///
+    /// Implementations should fail with `std::io::ErrorKind::TimedOut` when `config.connect_timeout` elapses.
+    /// Read and write timeouts are enforced by `AsyncTlsClient`.
+    fn connect_with_config(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> impl Future<Output = Result<Self>> + Send
+    where
+        Self: Sized;
+}
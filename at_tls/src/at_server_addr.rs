@@ -1,6 +1,6 @@
 use std::net::{SocketAddr, ToSocketAddrs};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AtServerAddr {
     pub host: String,
     pub port: u16,
@@ -0,0 +1,223 @@
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+/// Returns the SHA-256 hash of the DER encoded SubjectPublicKeyInfo of a DER encoded X.509 certificate.
+///
+/// This is the value expected when pinning a server's public key.
+pub fn spki_sha256(certificate_der: &[u8]) -> Result<[u8; 32]> {
+    let spki = extract_spki(certificate_der).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            "Unable to find the public key in the certificate",
+        )
+    })?;
+    Ok(Sha256::digest(spki).into())
+}
+
+/// A single DER encoded tag-length-value element.
+struct DerElement<'a> {
+    tag: u8,
+    /// The whole element including the tag and length.
+    encoded: &'a [u8],
+    content: &'a [u8],
+    /// The input following this element.
+    rest: &'a [u8],
+}
+
+fn read_der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let tag = *input.first()?;
+    let first_len_byte = *input.get(1)?;
+    let (content_len, header_len) = if first_len_byte < 0x80 {
+        (first_len_byte as usize, 2)
+    } else {
+        let num_len_bytes = (first_len_byte & 0x7f) as usize;
+        if num_len_bytes == 0 || num_len_bytes > 4 {
+            return None;
+        }
+        let len_bytes = input.get(2..2 + num_len_bytes)?;
+        let content_len = len_bytes
+            .iter()
+            .fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+        (content_len, 2 + num_len_bytes)
+    };
+    let end = header_len.checked_add(content_len)?;
+    let encoded = input.get(..end)?;
+    Some(DerElement {
+        tag,
+        encoded,
+        content: &encoded[header_len..],
+        rest: &input[end..],
+    })
+}
+
+/// Walks `Certificate -> TBSCertificate` and returns the encoded `subjectPublicKeyInfo` field.
+fn extract_spki(certificate_der: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+    const EXPLICIT_VERSION: u8 = 0xa0;
+
+    let certificate = read_der_element(certificate_der)?;
+    if certificate.tag != SEQUENCE {
+        return None;
+    }
+    let tbs_certificate = read_der_element(certificate.content)?;
+    if tbs_certificate.tag != SEQUENCE {
+        return None;
+    }
+    let mut fields = tbs_certificate.content;
+    if fields.first() == Some(&EXPLICIT_VERSION) {
+        fields = read_der_element(fields)?.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        fields = read_der_element(fields)?.rest;
+    }
+    let spki = read_der_element(fields)?;
+    (spki.tag == SEQUENCE).then_some(spki.encoded)
+}
+
+/// Verifies the certificate chain as normal and then checks that the server's public key is one of the pinned keys.
+#[derive(Debug)]
+pub(crate) struct PinnedCertificateVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pinned_spki_hashes: Vec<[u8; 32]>,
+}
+
+impl PinnedCertificateVerifier {
+    pub(crate) fn new(inner: Arc<WebPkiServerVerifier>, pinned_spki_hashes: Vec<[u8; 32]>) -> Self {
+        Self {
+            inner,
+            pinned_spki_hashes,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let spki_hash = spki_sha256(end_entity)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        if self.pinned_spki_hashes.contains(&spki_hash) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Accepts any certificate the server presents. Handshake signatures are still checked.
+///
+/// Only for talking to local test servers with throwaway certificates. Never use this in production.
+#[cfg(feature = "dangerous-insecure")]
+#[derive(Debug)]
+pub(crate) struct AcceptAnyCertificate {
+    supported_algorithms: rustls::crypto::WebPkiSupportedAlgorithms,
+}
+
+#[cfg(feature = "dangerous-insecure")]
+impl AcceptAnyCertificate {
+    pub(crate) fn new() -> Self {
+        Self {
+            supported_algorithms: rustls::crypto::ring::default_provider()
+                .signature_verification_algorithms,
+        }
+    }
+}
+
+#[cfg(feature = "dangerous-insecure")]
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.supported_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.supported_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spki_sha256_matches_key_pair() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+        let expected: [u8; 32] = Sha256::digest(certified_key.key_pair.public_key_der()).into();
+        let result = spki_sha256(certified_key.cert.der()).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_spki_sha256_invalid_certificate() {
+        assert!(spki_sha256(b"not a certificate").is_err());
+        assert!(spki_sha256(&[0x30, 0x82, 0xff]).is_err());
+    }
+}
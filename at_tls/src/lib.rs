@@ -1,38 +1,111 @@
 use std::io::{BufRead, BufReader};
+use std::time::Duration;
 
 use at_server_addr::AtServerAddr;
-use log::debug;
+use log::{debug, warn};
+use recording_connection::RecordingConnection;
+use retry_policy::RetryPolicy;
+use tls_connection_config::TlsConnectionConfig;
 use tls_connection_trait::TlsConnection;
 
 pub mod at_server_addr;
+pub mod certificate_verifier;
+pub mod recording_connection;
+pub mod replay_connection;
+pub mod retry_policy;
 pub mod rustls_connection;
+pub mod tls_connection_config;
 pub mod tls_connection_trait;
+pub mod trace;
 
+#[cfg(feature = "tokio")]
+pub mod async_tls_client;
+#[cfg(feature = "tokio")]
+pub mod async_tls_connection_trait;
+#[cfg(feature = "tokio")]
+pub mod tokio_rustls_connection;
+
+#[cfg(test)]
+mod test_utils;
+
+/// How long each write may block while closing a connection because its client is being dropped.
+///
+/// Dropping shouldn't hang on a server which has stopped reading, so closing then gives up quickly.
+pub const DROP_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A line based connection to an atServer or root server.
+///
+/// The connection is closed cleanly with `close`, or on a best-effort basis when the client is dropped,
+/// waiting at most `DROP_WRITE_TIMEOUT` for each write.
 pub struct TlsClient {
     tls_connection: Box<dyn TlsConnection>,
+    closed: bool,
 }
 
 impl TlsClient {
     /// Create a new client. Only for testing.
     pub fn new(tls_connection: Box<dyn TlsConnection>) -> Self {
-        Self { tls_connection }
+        Self {
+            tls_connection,
+            closed: false,
+        }
     }
 
     /// Connects to the specified server address using TLS.
     ///
     /// Returns a new `TlsClient` if the connection is successful.
     pub fn connect<T: TlsConnection + 'static>(address: &AtServerAddr) -> std::io::Result<Self> {
-        // TODO: Add a retry mechanism
-        let tls_connection = T::connect(address)?;
-        Ok(Self {
-            tls_connection: Box::new(tls_connection),
-        })
+        Self::connect_with_config::<T>(address, &TlsConnectionConfig::default())
+    }
+
+    /// Connects to the specified server address using TLS with the given timeouts.
+    ///
+    /// Reads and writes that exceed the configured timeouts fail with `std::io::ErrorKind::TimedOut`.
+    /// If `config.record_trace_to` is set, the session is recorded to that file.
+    pub fn connect_with_config<T: TlsConnection + 'static>(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> std::io::Result<Self> {
+        let tls_connection: Box<dyn TlsConnection> = match config.record_trace_to {
+            Some(_) => Box::new(RecordingConnection::<T>::connect_with_config(
+                address, config,
+            )?),
+            None => Box::new(T::connect_with_config(address, config)?),
+        };
+        Ok(Self::new(tls_connection))
+    }
+
+    /// Connects to the specified server address, retrying failed attempts according to `retry_policy`.
+    ///
+    /// Returns the error of the last attempt if all attempts fail.
+    pub fn connect_with_retry<T: TlsConnection + 'static>(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+        retry_policy: &RetryPolicy,
+    ) -> std::io::Result<Self> {
+        let mut attempt = 1;
+        loop {
+            match Self::connect_with_config::<T>(address, config) {
+                Ok(client) => return Ok(client),
+                Err(e) if attempt < retry_policy.max_attempts => {
+                    let delay = retry_policy.delay_for_retry(attempt);
+                    warn!(
+                        "Failed to connect to {}:{} (attempt {}): {}. Retrying in {:?}",
+                        address.host, address.port, attempt, e, delay
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
     }
 
     /// Sends data to the server.
     /// Data is expected to be one line (no newlines in the middle of the data).
     /// Appends a newline to the data as this is the delimiter in the protocol.
     pub fn send_data<U: AsRef<[u8]>>(&mut self, data: U) -> std::io::Result<()> {
+        self.check_open()?;
         let data_slice = data.as_ref();
         let mut data_with_newline = Vec::with_capacity(data_slice.len() + 1);
         data_with_newline.extend_from_slice(data_slice);
@@ -46,25 +119,99 @@ impl TlsClient {
 
     /// Reads a line from the stream and returns the bytes.
     pub fn read_data(&mut self) -> std::io::Result<Vec<u8>> {
+        self.check_open()?;
         let mut res = vec![];
         let mut reader = BufReader::new(&mut self.tls_connection);
         // Newline is the delimiter in the protocol
-        let _ = reader.read_until(b'\n', &mut res)?;
+        let bytes_read = reader.read_until(b'\n', &mut res)?;
+        if bytes_read == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Connection closed by server",
+            ));
+        }
         debug!("Reading data: {:?}", String::from_utf8_lossy(&res));
         Ok(res)
     }
+
+    /// Closes the connection cleanly, e.g. with a TLS close_notify, so the server doesn't have to wait for it to time out.
+    ///
+    /// Doesn't send any protocol command first, like the atServer's `exit`, which is up to the caller.
+    /// Sending or reading afterwards fails with `std::io::ErrorKind::NotConnected`. Closing again does nothing.
+    pub fn close(&mut self) -> std::io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        debug!("Closing connection");
+        self.tls_connection.close()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Changes how long a write may block before failing with `std::io::ErrorKind::TimedOut`.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.tls_connection.set_write_timeout(timeout)
+    }
+
+    /// Changes how long a read may block before failing with `std::io::ErrorKind::TimedOut`.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.tls_connection.set_read_timeout(timeout)
+    }
+
+    /// Stops using the connection without closing it, for a connection which has already failed
+    /// and where closing would only wait for writes to time out.
+    ///
+    /// Like after `close`, sending or reading fails with `std::io::ErrorKind::NotConnected` and dropping does nothing.
+    pub fn abandon(&mut self) {
+        debug!("Abandoning connection");
+        self.closed = true;
+    }
+
+    fn check_open(&self) -> std::io::Result<()> {
+        if self.closed {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "Connection already closed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TlsClient {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        if let Err(e) = self.set_write_timeout(Some(DROP_WRITE_TIMEOUT)) {
+            debug!("Failed to limit how long closing takes: {}", e);
+        }
+        if let Err(e) = self.close() {
+            debug!("Failed to close connection: {}", e);
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
 
+    use std::sync::{Arc, Mutex};
+
     use self::rustls_connection::RustlsConnection;
 
     use super::*;
+    use crate::test_utils::*;
 
     fn create_subject() -> std::io::Result<TlsClient> {
-        let address = AtServerAddr::new(String::from("root.atsign.org"), 64);
-        TlsClient::connect::<RustlsConnection>(&address)
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        TlsClient::connect_with_config::<RustlsConnection>(
+            &address,
+            &config_trusting(&certified_key),
+        )
     }
 
     #[test]
@@ -90,7 +237,135 @@ mod test {
         assert!(res.is_ok());
         assert_eq!(
             String::from_utf8_lossy(&res.unwrap()).trim(),
-            String::from("@null")
+            String::from("data:Hello, World!")
+        );
+    }
+
+    #[test]
+    fn test_close() {
+        let mut subject = create_subject().unwrap();
+        subject.close().unwrap();
+        assert!(subject.is_closed());
+        subject.close().unwrap();
+        let res = subject.send_data("Hello, World!");
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::NotConnected);
+        let res = subject.read_data();
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::NotConnected);
+    }
+
+    /// Records how it is closed, to check what dropping a client does.
+    struct ClosingConnection {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl std::io::Read for ClosingConnection {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl std::io::Write for ClosingConnection {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl TlsConnection for ClosingConnection {
+        fn connect_with_config(
+            _address: &AtServerAddr,
+            _config: &TlsConnectionConfig,
+        ) -> std::io::Result<Self> {
+            unimplemented!()
+        }
+
+        fn close(&mut self) -> std::io::Result<()> {
+            self.calls.lock().unwrap().push(String::from("close"));
+            Ok(())
+        }
+
+        fn set_write_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+            let call = format!("set_write_timeout({:?})", timeout);
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+    }
+
+    fn closing_subject() -> (TlsClient, Arc<Mutex<Vec<String>>>) {
+        let calls = Arc::default();
+        let connection = ClosingConnection {
+            calls: Arc::clone(&calls),
+        };
+        (TlsClient::new(Box::new(connection)), calls)
+    }
+
+    #[test]
+    fn test_drop_closes_with_a_short_write_timeout() {
+        let (subject, calls) = closing_subject();
+        drop(subject);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                format!("set_write_timeout({:?})", Some(DROP_WRITE_TIMEOUT)),
+                String::from("close")
+            ]
+        );
+
+        let (mut subject, calls) = closing_subject();
+        subject.close().unwrap();
+        drop(subject);
+        assert_eq!(*calls.lock().unwrap(), vec![String::from("close")]);
+    }
+
+    #[test]
+    fn test_abandon_does_not_close() {
+        let (mut subject, calls) = closing_subject();
+        subject.abandon();
+        let res = subject.send_data("Hello, World!");
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::NotConnected);
+        drop(subject);
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_data_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = AtServerAddr::new(
+            String::from("127.0.0.1"),
+            listener.local_addr().unwrap().port(),
+        );
+        let config = TlsConnectionConfig::new(
+            None,
+            Some(std::time::Duration::from_millis(200)),
+            Some(std::time::Duration::from_millis(200)),
+        );
+        let mut subject =
+            TlsClient::connect_with_config::<RustlsConnection>(&address, &config).unwrap();
+        let res = subject.read_data();
+        assert_eq!(res.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_connect_with_retry_gives_up() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = AtServerAddr::new(
+            String::from("127.0.0.1"),
+            listener.local_addr().unwrap().port(),
+        );
+        drop(listener);
+        let retry_policy = RetryPolicy::new(
+            3,
+            std::time::Duration::from_millis(10),
+            std::time::Duration::from_millis(20),
+        );
+        let res = TlsClient::connect_with_retry::<RustlsConnection>(
+            &address,
+            &TlsConnectionConfig::default(),
+            &retry_policy,
         );
+        assert!(res.is_err());
     }
 }
@@ -0,0 +1,173 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::time::Duration;
+
+use crate::at_server_addr::AtServerAddr;
+use crate::tls_connection_config::TlsConnectionConfig;
+use crate::tls_connection_trait::TlsConnection;
+use crate::trace::{RedactionRules, TraceRecorder};
+
+/// A `TlsConnection` which writes every line sent and received over `T` to a trace, redacting secrets.
+///
+/// The trace can be played back with `ReplayConnection`. Failing to write the trace is logged
+/// but never fails the connection itself.
+pub struct RecordingConnection<T: TlsConnection> {
+    inner: T,
+    recorder: TraceRecorder,
+    /// Bytes sent since the last complete line.
+    sent: Vec<u8>,
+    /// Bytes received since the last complete line.
+    received: Vec<u8>,
+}
+
+impl<T: TlsConnection> RecordingConnection<T> {
+    /// Wraps `inner`, which is connected to `address`, starting a new session in `trace`.
+    pub fn new(
+        inner: T,
+        address: &AtServerAddr,
+        trace: Box<dyn Write + Send>,
+        redaction_rules: RedactionRules,
+    ) -> Self {
+        Self::with_recorder(inner, TraceRecorder::new(address, trace, redaction_rules))
+    }
+
+    fn with_recorder(inner: T, recorder: TraceRecorder) -> Self {
+        Self {
+            inner,
+            recorder,
+            sent: vec![],
+            received: vec![],
+        }
+    }
+}
+
+/// Removes and returns the complete lines at the start of `buffer`, without their newlines.
+fn take_lines(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = vec![];
+    while let Some(newline) = buffer.iter().position(|byte| *byte == b'\n') {
+        let line: Vec<u8> = buffer.drain(..=newline).collect();
+        lines.push(String::from_utf8_lossy(&line[..line.len() - 1]).into_owned());
+    }
+    lines
+}
+
+impl<T: TlsConnection> Read for RecordingConnection<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.received.extend_from_slice(&buf[..len]);
+        for line in take_lines(&mut self.received) {
+            self.recorder.record_received(&line);
+        }
+        Ok(len)
+    }
+}
+
+impl<T: TlsConnection> Write for RecordingConnection<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.sent.extend_from_slice(&buf[..len]);
+        for line in take_lines(&mut self.sent) {
+            self.recorder.record_sent(&line);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: TlsConnection> TlsConnection for RecordingConnection<T> {
+    /// Connects using `T`, appending the trace to the file set in `config.record_trace_to`
+    /// with the default redaction rules.
+    fn connect_with_config(address: &AtServerAddr, config: &TlsConnectionConfig) -> Result<Self> {
+        let path = config.record_trace_to.as_ref().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "No trace file set in the connection config",
+            )
+        })?;
+        let recorder = TraceRecorder::append_to_file(path, address)?;
+        let inner = T::connect_with_config(address, config)?;
+        Ok(Self::with_recorder(inner, recorder))
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.inner.set_write_timeout(timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::rustls_connection::RustlsConnection;
+    use crate::test_utils::*;
+
+    /// A trace which can still be read after being moved into the connection.
+    #[derive(Clone, Default)]
+    struct SharedTrace(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedTrace {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedTrace {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_records_redacted_session() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let inner =
+            RustlsConnection::connect_with_config(&address, &config_trusting(&certified_key))
+                .unwrap();
+        let trace = SharedTrace::default();
+        let mut subject = BufReader::new(RecordingConnection::new(
+            inner,
+            &address,
+            Box::new(trace.clone()),
+            RedactionRules::default(),
+        ));
+
+        let mut response = String::new();
+        for command in [
+            "from:@alice\n",
+            "pkam:c2lnbmF0dXJl\n",
+            "llookup:phone@alice\n",
+        ] {
+            subject.get_mut().write_all(command.as_bytes()).unwrap();
+            subject.read_line(&mut response).unwrap();
+        }
+
+        // The echo server repeats the signature, a real atServer answers `data:success`
+        assert_eq!(
+            trace.contents(),
+            format!(
+                "# session localhost:{}\n\
+                 > from:@alice\n< data:from:@alice\n\
+                 > pkam:<redacted>\n< data:pkam:c2lnbmF0dXJl\n\
+                 > llookup:phone@alice\n< data:<redacted>\n",
+                address.port
+            )
+        );
+    }
+}
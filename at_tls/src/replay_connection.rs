@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use crate::at_server_addr::AtServerAddr;
+use crate::tls_connection_config::TlsConnectionConfig;
+use crate::tls_connection_trait::TlsConnection;
+use crate::trace::{TraceEvent, TraceSession, REDACTED};
+
+/// A `TlsConnection` which plays back a session recorded by `RecordingConnection`.
+///
+/// Every line the client sends must match the next line sent in the trace,
+/// after which the lines received in the trace up to the next sent line are returned by reads.
+/// A redacted line in the trace matches any line with the same prefix.
+/// Panics when the client sends something else, like `MockTlsConnection`,
+/// unless the thread is already panicking, when the write fails instead so the original panic is reported.
+pub struct ReplayConnection {
+    events: VecDeque<TraceEvent>,
+    /// Bytes sent since the last complete line.
+    written: Vec<u8>,
+    /// Bytes waiting to be read by the client.
+    to_be_read: VecDeque<u8>,
+}
+
+impl ReplayConnection {
+    pub fn new(session: TraceSession) -> Self {
+        let mut connection = Self {
+            events: session.events.into(),
+            written: vec![],
+            to_be_read: VecDeque::new(),
+        };
+        connection.play_received();
+        connection
+    }
+
+    /// Whether every line in the session has been sent and received.
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty() && self.to_be_read.is_empty()
+    }
+
+    /// Queues the received lines at the front of the session.
+    fn play_received(&mut self) {
+        while let Some(TraceEvent::Received(line)) = self.events.front() {
+            self.to_be_read.extend(line.as_bytes());
+            self.to_be_read.push_back(b'\n');
+            self.events.pop_front();
+        }
+    }
+
+    fn receive_line(&mut self, line: &str) -> Result<()> {
+        let mismatch = match self.events.pop_front() {
+            Some(TraceEvent::Sent(expected)) => {
+                let matches = match expected.strip_suffix(REDACTED) {
+                    Some(prefix) => line.starts_with(prefix),
+                    None => line == expected,
+                };
+                (!matches).then(|| {
+                    format!(
+                        "ReplayConnection received {:?} but the trace has {:?}",
+                        line, expected
+                    )
+                })
+            }
+            event => Some(format!(
+                "ReplayConnection received {:?} but the next event is {:?}",
+                line, event
+            )),
+        };
+        if let Some(mismatch) = mismatch {
+            // Panicking again would abort, losing the first panic's message
+            if std::thread::panicking() {
+                return Err(Error::new(ErrorKind::InvalidData, mismatch));
+            }
+            panic!("{}", mismatch);
+        }
+        self.play_received();
+        Ok(())
+    }
+}
+
+impl Read for ReplayConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(self.to_be_read.len());
+        for (byte, read) in buf.iter_mut().zip(self.to_be_read.drain(..len)) {
+            *byte = read;
+        }
+        Ok(len)
+    }
+}
+
+impl Write for ReplayConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.written.extend_from_slice(buf);
+        while let Some(newline) = self.written.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.written.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line[..line.len() - 1]).into_owned();
+            self.receive_line(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl TlsConnection for ReplayConnection {
+    /// A replay has no server to connect to, use `ReplayConnection::new` with `TlsClient::new` instead.
+    fn connect_with_config(address: &AtServerAddr, _config: &TlsConnectionConfig) -> Result<Self> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "Cannot connect to {}:{} while replaying a trace",
+                address.host, address.port
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn create_subject() -> ReplayConnection {
+        let trace = "# session localhost:1234\n< @\n> from:@alice\n< @data:challenge\n> pkam:<redacted>\n< @alice@data:success\n";
+        ReplayConnection::new(TraceSession::parse_all(trace).unwrap().remove(0))
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut subject = create_subject();
+        let mut response = String::new();
+        subject.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "@\n");
+
+        subject.write_all(b"from:@alice\n").unwrap();
+        subject.write_all(b"pkam:c2lnbmF0dXJl\n").unwrap();
+        response.clear();
+        subject.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "@data:challenge\n@alice@data:success\n");
+        assert!(subject.is_finished());
+    }
+
+    #[test]
+    #[should_panic(expected = "the trace has")]
+    fn test_unexpected_command() {
+        let mut subject = create_subject();
+        let _ = subject.write_all(b"from:@bob\n");
+    }
+
+    #[test]
+    fn test_connect_unsupported() {
+        let address = AtServerAddr::new(String::from("localhost"), 1234);
+        let result =
+            ReplayConnection::connect_with_config(&address, &TlsConnectionConfig::default());
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::Unsupported);
+    }
+
+    /// Sends an unexpected command when dropped, like a client closing its connection.
+    struct ExitOnDrop(ReplayConnection);
+
+    impl Drop for ExitOnDrop {
+        fn drop(&mut self) {
+            let result = self.0.write_all(b"exit\n");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_unexpected_line_while_panicking() {
+        let result = std::panic::catch_unwind(|| {
+            let _subject = ExitOnDrop(create_subject());
+            panic!("test failed");
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Controls how many times, and how often, a failed connection attempt is retried.
+///
+/// Delays grow exponentially from `initial_delay` up to `max_delay`.
+/// Half of each delay is randomised (jitter) so that many clients reconnecting at the same time don't all hit the server together.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. A value of 1 disables retrying.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_delay,
+            max_delay,
+        }
+    }
+
+    /// A policy which only makes a single attempt.
+    pub fn no_retry() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Returns how long to wait before the given retry (starting at 1 for the first retry).
+    pub fn delay_for_retry(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        let half = backoff / 2;
+        let jitter_nanos = rand::thread_rng().gen_range(0..=half.as_nanos() as u64);
+        half + Duration::from_nanos(jitter_nanos)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(5, Duration::from_millis(250), Duration::from_secs(10))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay_grows_exponentially() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100), Duration::from_secs(60));
+        for retry in 1..=5 {
+            let backoff = Duration::from_millis(100 * (1 << (retry - 1)));
+            let delay = policy.delay_for_retry(retry);
+            assert!(delay >= backoff / 2, "retry {}: {:?}", retry, delay);
+            assert!(delay <= backoff, "retry {}: {:?}", retry, delay);
+        }
+    }
+
+    #[test]
+    fn test_delay_is_capped() {
+        let policy = RetryPolicy::new(100, Duration::from_millis(100), Duration::from_secs(1));
+        let delay = policy.delay_for_retry(64);
+        assert!(delay >= Duration::from_millis(500));
+        assert!(delay <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_no_retry() {
+        let policy = RetryPolicy::no_retry();
+        assert_eq!(policy.max_attempts, 1);
+        assert_eq!(policy.delay_for_retry(1), Duration::ZERO);
+    }
+}
@@ -1,9 +1,13 @@
-use rustls::{ClientConfig, ClientConnection, StreamOwned};
-use std::io::{Read, Result, Write};
-use std::net::TcpStream;
+use rustls::client::{WantsClientCert, WebPkiServerVerifier};
+use rustls::{ClientConfig, ClientConnection, ConfigBuilder, StreamOwned};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::at_server_addr::AtServerAddr;
+use crate::certificate_verifier::PinnedCertificateVerifier;
+use crate::tls_connection_config::TlsConnectionConfig;
 use crate::tls_connection_trait::TlsConnection;
 
 pub struct RustlsConnection {
@@ -14,19 +18,91 @@ impl RustlsConnection {
     fn new(stream: StreamOwned<ClientConnection, TcpStream>) -> Self {
         RustlsConnection { stream }
     }
+
+    /// Opens a TCP stream to the first reachable address, giving up on each address after `timeout`.
+    fn connect_tcp(address: &AtServerAddr, timeout: Option<Duration>) -> Result<TcpStream> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return TcpStream::connect(address),
+        };
+        let mut last_error = Error::new(ErrorKind::InvalidInput, "Could not resolve address");
+        for socket_addr in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket_addr, timeout) {
+                Ok(tcp_stream) => return Ok(tcp_stream),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Creates the rustls config, choosing how the server's certificate is verified for the given host.
+    pub(crate) fn build_client_config(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> Result<ClientConfig> {
+        #[cfg(feature = "dangerous-insecure")]
+        if config.dangerous_accept_any_certificate {
+            log::warn!(
+                "Certificate verification is disabled for {}. Only use this for local testing.",
+                address.host
+            );
+            let builder = ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(
+                    crate::certificate_verifier::AcceptAnyCertificate::new(),
+                ));
+            return Self::with_client_auth(builder, config);
+        }
+
+        let root_store = config.root_certificates.to_root_store()?;
+        let builder = ClientConfig::builder();
+        let builder = match config.pinned_public_keys.get(&address.host) {
+            Some(pinned_spki_hashes) => {
+                let webpki_verifier = WebPkiServerVerifier::builder(Arc::new(root_store))
+                    .build()
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(PinnedCertificateVerifier::new(
+                        webpki_verifier,
+                        pinned_spki_hashes.clone(),
+                    )))
+            }
+            None => builder.with_root_certificates(root_store),
+        };
+        Self::with_client_auth(builder, config)
+    }
+
+    /// Finishes the rustls config, presenting the client certificate if one is configured.
+    fn with_client_auth(
+        builder: ConfigBuilder<ClientConfig, WantsClientCert>,
+        config: &TlsConnectionConfig,
+    ) -> Result<ClientConfig> {
+        match &config.client_certificate {
+            Some(client_certificate) => builder
+                .with_client_auth_cert(
+                    client_certificate.certificate_chain.clone(),
+                    client_certificate.private_key.clone_key(),
+                )
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e)),
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+}
+
+/// Sockets with a timeout report `WouldBlock` on some platforms, so normalise it to `TimedOut`.
+fn map_timeout(error: Error) -> Error {
+    match error.kind() {
+        ErrorKind::WouldBlock => Error::new(ErrorKind::TimedOut, error),
+        _ => error,
+    }
 }
 
 impl TlsConnection for RustlsConnection {
-    fn connect(address: &AtServerAddr) -> Result<Self> {
-        // TODO: Allow custom root certificates
-        // Create the config with default root certificates
-        let mut root_store = rustls::RootCertStore::empty();
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-        let config = ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-
-        let rc_config = Arc::new(config);
+    fn connect_with_config(address: &AtServerAddr, config: &TlsConnectionConfig) -> Result<Self> {
+        let client_config = Self::build_client_config(address, config)?;
+
+        let rc_config = Arc::new(client_config);
         // Create a DNS name from the at sign server address (i.e. the host)
         let dns_name = address.host.to_owned().try_into().map_err(|_| {
             std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid DNS name")
@@ -35,44 +111,71 @@ impl TlsConnection for RustlsConnection {
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::ConnectionRefused, e))?;
 
         // Create a standard TCP stream and wrap it with the TLS session
-        let tcp_stream = TcpStream::connect(address)?;
+        let tcp_stream = Self::connect_tcp(address, config.connect_timeout)?;
+        tcp_stream.set_read_timeout(config.read_timeout)?;
+        tcp_stream.set_write_timeout(config.write_timeout)?;
 
         // StreamOwned is a wrapper around a stream that implements Read and Write
         let tls_stream = StreamOwned::new(session, tcp_stream);
 
         Ok(RustlsConnection::new(tls_stream))
     }
+
+    /// Sends a close_notify alert, so the server sees a clean shutdown rather than a dropped connection.
+    fn close(&mut self) -> Result<()> {
+        self.stream.conn.send_close_notify();
+        while self.stream.conn.wants_write() {
+            self.stream
+                .conn
+                .write_tls(&mut self.stream.sock)
+                .map_err(map_timeout)?;
+        }
+        self.stream.sock.flush().map_err(map_timeout)
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.sock.set_write_timeout(timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.sock.set_read_timeout(timeout)
+    }
 }
 
 impl Read for RustlsConnection {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.stream.read(buf)
+        self.stream.read(buf).map_err(map_timeout)
     }
 }
 
 impl Write for RustlsConnection {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        self.stream.write(buf)
+        self.stream.write(buf).map_err(map_timeout)
     }
 
     fn flush(&mut self) -> Result<()> {
-        self.stream.flush()
+        self.stream.flush().map_err(map_timeout)
     }
 }
 
 #[cfg(test)]
 mod test {
 
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::time::Instant;
+
     use super::*;
+    use crate::certificate_verifier::spki_sha256;
+    use crate::test_utils::*;
+    use crate::tls_connection_config::ClientCertificate;
 
     #[test]
     fn test_connect() {
-        fn create_subject() -> Result<RustlsConnection> {
-            let address = AtServerAddr::new(String::from("google.com"), 80);
-            RustlsConnection::connect(&address)
-        }
-
-        let subject = create_subject();
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let subject =
+            RustlsConnection::connect_with_config(&address, &config_trusting(&certified_key));
         assert!(subject.is_ok())
     }
 
@@ -86,4 +189,171 @@ mod test {
         let subject = create_subject();
         assert!(subject.is_err())
     }
+
+    /// Accepts connections but never sends a byte back, like a half-dead server.
+    fn start_silent_listener() -> (TcpListener, AtServerAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (listener, AtServerAddr::new(String::from("127.0.0.1"), port))
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let (_listener, address) = start_silent_listener();
+        let config = TlsConnectionConfig::new(None, Some(Duration::from_millis(200)), None);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+
+        let mut buf = [0u8; 16];
+        let start = Instant::now();
+        let result = subject.read(&mut buf);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_write_times_out_waiting_for_handshake() {
+        let (_listener, address) = start_silent_listener();
+        let config = TlsConnectionConfig::new(
+            Some(Duration::from_secs(1)),
+            Some(Duration::from_millis(200)),
+            Some(Duration::from_millis(200)),
+        );
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+
+        // The handshake has to complete before any application data is written
+        let result = subject.write_all(b"from:alice\n");
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_connect_timeout_refused() {
+        let (listener, address) = start_silent_listener();
+        drop(listener);
+        let config = TlsConnectionConfig::new(Some(Duration::from_millis(200)), None, None);
+        let subject = RustlsConnection::connect_with_config(&address, &config);
+        assert!(subject.is_err());
+    }
+
+    #[test]
+    fn test_close_sends_close_notify() {
+        let certified_key = create_certificate();
+        let config = config_trusting(&certified_key);
+
+        let (address, clean_close) = start_tls_server_reporting_close(&certified_key);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+        subject.close().unwrap();
+        drop(subject);
+        assert!(clean_close.recv().unwrap());
+
+        let (address, clean_close) = start_tls_server_reporting_close(&certified_key);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+        drop(subject);
+        assert!(!clean_close.recv().unwrap());
+    }
+
+    fn send_and_receive(subject: &mut RustlsConnection) -> Result<String> {
+        subject.write_all(b"hello\n")?;
+        let mut response = String::new();
+        BufReader::new(subject).read_line(&mut response)?;
+        Ok(response)
+    }
+
+    #[test]
+    fn test_custom_root_certificate() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let config = config_trusting(&certified_key);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+    }
+
+    #[test]
+    fn test_untrusted_certificate_rejected() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let config = TlsConnectionConfig::new(None, Some(Duration::from_secs(5)), None);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        let result = send_and_receive(&mut subject);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_pinned_public_key_matches() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let mut config = config_trusting(&certified_key);
+        let pin = spki_sha256(certified_key.cert.der()).unwrap();
+        config.pin_public_key("localhost", pin);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+    }
+
+    #[test]
+    fn test_pinned_public_key_mismatch() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let mut config = config_trusting(&certified_key);
+        config.pin_public_key("localhost", [0; 32]);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        let result = send_and_receive(&mut subject);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "dangerous-insecure")]
+    #[test]
+    fn test_dangerous_accept_any_certificate() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let mut config = TlsConnectionConfig::new(None, Some(Duration::from_secs(5)), None);
+        config.dangerous_accept_any_certificate = true;
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+    }
+
+    #[test]
+    fn test_client_certificate_accepted() {
+        let server_key = create_certificate();
+        let (client_ca, client_key) = create_client_certificate();
+        let address = start_tls_server_with_client_auth(&server_key, Some(client_ca));
+        let mut config = config_trusting(&server_key);
+        config.client_certificate = Some(
+            ClientCertificate::from_pem(
+                client_key.cert.pem().as_bytes(),
+                client_key.key_pair.serialize_pem().as_bytes(),
+            )
+            .unwrap(),
+        );
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert_eq!(send_and_receive(&mut subject).unwrap(), "data:hello\n");
+    }
+
+    #[test]
+    fn test_missing_client_certificate_rejected() {
+        let server_key = create_certificate();
+        let (client_ca, _) = create_client_certificate();
+        let address = start_tls_server_with_client_auth(&server_key, Some(client_ca));
+        let config = config_trusting(&server_key);
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert!(send_and_receive(&mut subject).is_err());
+    }
+
+    #[test]
+    fn test_untrusted_client_certificate_rejected() {
+        let server_key = create_certificate();
+        let (client_ca, _) = create_client_certificate();
+        let (_, other_client_key) = create_client_certificate();
+        let address = start_tls_server_with_client_auth(&server_key, Some(client_ca));
+        let mut config = config_trusting(&server_key);
+        config.client_certificate = Some(
+            ClientCertificate::from_der(
+                vec![other_client_key.cert.der().to_vec()],
+                other_client_key.key_pair.serialize_der(),
+            )
+            .unwrap(),
+        );
+        let mut subject = RustlsConnection::connect_with_config(&address, &config).unwrap();
+        assert!(send_and_receive(&mut subject).is_err());
+    }
 }
@@ -0,0 +1,135 @@
+//! Helpers for tests which need a real TLS server on localhost.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use crate::at_server_addr::AtServerAddr;
+use crate::tls_connection_config::{RootCertificates, TlsConnectionConfig};
+
+pub(crate) fn create_certificate() -> rcgen::CertifiedKey {
+    rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap()
+}
+
+/// Starts a TLS server for a single connection which answers each line with `data:<line>`.
+pub(crate) fn start_tls_server(certified_key: &rcgen::CertifiedKey) -> AtServerAddr {
+    start_tls_server_with_client_auth(certified_key, None)
+}
+
+/// Like `start_tls_server` but, if `client_ca` is given, requires a client certificate signed by it.
+pub(crate) fn start_tls_server_with_client_auth(
+    certified_key: &rcgen::CertifiedKey,
+    client_ca: Option<CertificateDer<'static>>,
+) -> AtServerAddr {
+    let server_config = server_config(certified_key, client_ca);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        let (tcp_stream, _) = listener.accept().unwrap();
+        let session = ServerConnection::new(Arc::new(server_config)).unwrap();
+        let mut tls_stream = BufReader::new(StreamOwned::new(session, tcp_stream));
+        let mut line = String::new();
+        while tls_stream.read_line(&mut line).unwrap_or(0) > 0 {
+            let response = format!("data:{}", line);
+            if tls_stream.get_mut().write_all(response.as_bytes()).is_err() {
+                break;
+            }
+            line.clear();
+        }
+    });
+    AtServerAddr::new(String::from("localhost"), port)
+}
+
+/// Like `start_tls_server`, but once the client goes away sends whether it closed the TLS session cleanly with close_notify.
+pub(crate) fn start_tls_server_reporting_close(
+    certified_key: &rcgen::CertifiedKey,
+) -> (AtServerAddr, mpsc::Receiver<bool>) {
+    let server_config = server_config(certified_key, None);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let (tcp_stream, _) = listener.accept().unwrap();
+        let session = ServerConnection::new(Arc::new(server_config)).unwrap();
+        let mut tls_stream = BufReader::new(StreamOwned::new(session, tcp_stream));
+        let mut line = String::new();
+        // Without close_notify, rustls reports the end of the stream as an error
+        let clean = loop {
+            match tls_stream.read_line(&mut line) {
+                Ok(0) => break true,
+                Ok(_) => {
+                    let response = format!("data:{}", line);
+                    if tls_stream.get_mut().write_all(response.as_bytes()).is_err() {
+                        break false;
+                    }
+                    line.clear();
+                }
+                Err(_) => break false,
+            }
+        };
+        let _ = sender.send(clean);
+    });
+    (AtServerAddr::new(String::from("localhost"), port), receiver)
+}
+
+fn server_config(
+    certified_key: &rcgen::CertifiedKey,
+    client_ca: Option<CertificateDer<'static>>,
+) -> ServerConfig {
+    let builder = ServerConfig::builder();
+    let builder = match client_ca {
+        Some(client_ca) => {
+            let mut client_roots = rustls::RootCertStore::empty();
+            client_roots.add(client_ca).unwrap();
+            let client_verifier = WebPkiClientVerifier::builder(Arc::new(client_roots))
+                .build()
+                .unwrap();
+            builder.with_client_cert_verifier(client_verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    builder
+        .with_single_cert(
+            vec![certified_key.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(certified_key.key_pair.serialize_der().into()),
+        )
+        .unwrap()
+}
+
+pub(crate) fn config_trusting(certified_key: &rcgen::CertifiedKey) -> TlsConnectionConfig {
+    let mut config = TlsConnectionConfig::new(
+        None,
+        Some(Duration::from_secs(5)),
+        Some(Duration::from_secs(5)),
+    );
+    config.root_certificates =
+        RootCertificates::from_pem(certified_key.cert.pem().as_bytes()).unwrap();
+    config
+}
+
+/// Creates a CA and a client certificate signed by it.
+pub(crate) fn create_client_certificate() -> (CertificateDer<'static>, rcgen::CertifiedKey) {
+    let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca_key_pair = rcgen::KeyPair::generate().unwrap();
+    let ca_cert = ca_params.self_signed(&ca_key_pair).unwrap();
+
+    let mut client_params = rcgen::CertificateParams::new(vec![String::from("client")]).unwrap();
+    client_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth];
+    let client_key_pair = rcgen::KeyPair::generate().unwrap();
+    let client_cert = client_params
+        .signed_by(&client_key_pair, &ca_cert, &ca_key_pair)
+        .unwrap();
+    (
+        ca_cert.der().clone(),
+        rcgen::CertifiedKey {
+            cert: client_cert,
+            key_pair: client_key_pair,
+        },
+    )
+}
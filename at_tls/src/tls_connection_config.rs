@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+/// Options used when establishing a TLS connection to a server.
+///
+/// A timeout of `None` means the operation will block indefinitely.
+#[derive(Debug, Clone, Default)]
+pub struct TlsConnectionConfig {
+    /// Maximum time to wait for the TCP connection to be established.
+    pub connect_timeout: Option<Duration>,
+
+    /// Maximum time a single read can block before failing with a timeout error.
+    pub read_timeout: Option<Duration>,
+
+    /// Maximum time a single write can block before failing with a timeout error.
+    pub write_timeout: Option<Duration>,
+
+    /// The certificate authorities trusted to sign the server's certificate.
+    pub root_certificates: RootCertificates,
+
+    /// SHA-256 hashes of the SubjectPublicKeyInfo the server is allowed to present, keyed by host.
+    /// Hosts without an entry are not pinned.
+    pub pinned_public_keys: HashMap<String, Vec<[u8; 32]>>,
+
+    /// The certificate presented to servers which require client authentication (mutual TLS).
+    pub client_certificate: Option<ClientCertificate>,
+
+    /// Appends a trace of every line sent and received to this file, with secrets redacted.
+    /// Used by `TlsClient`, see `recording_connection::RecordingConnection`.
+    pub record_trace_to: Option<PathBuf>,
+
+    /// Skips verification of the server's certificate entirely.
+    /// Only intended for local testing against servers using throwaway certificates.
+    #[cfg(feature = "dangerous-insecure")]
+    pub dangerous_accept_any_certificate: bool,
+}
+
+impl TlsConnectionConfig {
+    pub fn new(
+        connect_timeout: Option<Duration>,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            connect_timeout,
+            read_timeout,
+            write_timeout,
+            ..Default::default()
+        }
+    }
+
+    /// Only accept a server certificate for `host` whose public key hashes to `spki_sha256`.
+    ///
+    /// Can be called multiple times for the same host to allow several keys (e.g. during key rotation).
+    /// See `certificate_verifier::spki_sha256` for computing the hash from a certificate.
+    pub fn pin_public_key(&mut self, host: &str, spki_sha256: [u8; 32]) {
+        self.pinned_public_keys
+            .entry(host.to_owned())
+            .or_default()
+            .push(spki_sha256);
+    }
+}
+
+/// The set of root certificates used to verify the server's certificate.
+#[derive(Debug, Clone, Default)]
+pub enum RootCertificates {
+    /// The Mozilla root certificates bundled with `webpki-roots`.
+    #[default]
+    WebPki,
+    /// Only the given certificates, e.g. an internal CA or a self-signed test certificate.
+    Custom(Vec<CertificateDer<'static>>),
+}
+
+impl RootCertificates {
+    /// Reads all certificates from PEM encoded data.
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        let certificates = rustls_pemfile::certs(&mut &pem[..]).collect::<Result<Vec<_>>>()?;
+        if certificates.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "No certificates found in PEM data",
+            ));
+        }
+        Ok(RootCertificates::Custom(certificates))
+    }
+
+    /// Creates a root store containing these certificates.
+    pub(crate) fn to_root_store(&self) -> Result<rustls::RootCertStore> {
+        let mut root_store = rustls::RootCertStore::empty();
+        match self {
+            RootCertificates::WebPki => {
+                root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+            RootCertificates::Custom(certificates) => {
+                for certificate in certificates {
+                    root_store
+                        .add(certificate.clone())
+                        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                }
+            }
+        }
+        Ok(root_store)
+    }
+}
+
+/// A certificate chain and the matching private key used to authenticate the client to the server.
+#[derive(Debug)]
+pub struct ClientCertificate {
+    /// The client's certificate first, followed by any intermediates.
+    pub certificate_chain: Vec<CertificateDer<'static>>,
+    pub private_key: PrivateKeyDer<'static>,
+}
+
+impl ClientCertificate {
+    pub fn new(
+        certificate_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Self {
+        Self {
+            certificate_chain,
+            private_key,
+        }
+    }
+
+    /// Reads the certificate chain and private key (PKCS#1, PKCS#8 or SEC1) from PEM encoded data.
+    pub fn from_pem(certificate_chain_pem: &[u8], private_key_pem: &[u8]) -> Result<Self> {
+        let certificate_chain =
+            rustls_pemfile::certs(&mut &certificate_chain_pem[..]).collect::<Result<Vec<_>>>()?;
+        if certificate_chain.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "No certificates found in PEM data",
+            ));
+        }
+        let private_key =
+            rustls_pemfile::private_key(&mut &private_key_pem[..])?.ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, "No private key found in PEM data")
+            })?;
+        Ok(Self::new(certificate_chain, private_key))
+    }
+
+    /// Creates a client certificate from DER encoded certificates and a DER encoded private key (PKCS#1, PKCS#8 or SEC1).
+    pub fn from_der(certificate_chain_der: Vec<Vec<u8>>, private_key_der: Vec<u8>) -> Result<Self> {
+        if certificate_chain_der.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "No certificates in the certificate chain",
+            ));
+        }
+        let certificate_chain = certificate_chain_der
+            .into_iter()
+            .map(CertificateDer::from)
+            .collect();
+        let private_key = PrivateKeyDer::try_from(private_key_der)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(Self::new(certificate_chain, private_key))
+    }
+}
+
+impl Clone for ClientCertificate {
+    fn clone(&self) -> Self {
+        Self::new(self.certificate_chain.clone(), self.private_key.clone_key())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_root_certificates_from_pem() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+        let pem = certified_key.cert.pem();
+        let result = RootCertificates::from_pem(pem.as_bytes()).unwrap();
+        match result {
+            RootCertificates::Custom(certificates) => {
+                assert_eq!(certificates.len(), 1);
+                assert_eq!(certificates[0], *certified_key.cert.der());
+            }
+            RootCertificates::WebPki => panic!("Expected custom root certificates"),
+        }
+    }
+
+    #[test]
+    fn test_root_certificates_from_pem_empty() {
+        let result = RootCertificates::from_pem(b"not a certificate");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_pin_public_key() {
+        let mut config = TlsConnectionConfig::default();
+        config.pin_public_key("example.com", [1; 32]);
+        config.pin_public_key("example.com", [2; 32]);
+        assert_eq!(
+            config.pinned_public_keys.get("example.com"),
+            Some(&vec![[1; 32], [2; 32]])
+        );
+        assert!(!config.pinned_public_keys.contains_key("other.com"));
+    }
+
+    #[test]
+    fn test_client_certificate_from_pem() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("client")]).unwrap();
+        let result = ClientCertificate::from_pem(
+            certified_key.cert.pem().as_bytes(),
+            certified_key.key_pair.serialize_pem().as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.certificate_chain,
+            vec![certified_key.cert.der().clone()]
+        );
+        assert_eq!(
+            result.private_key.secret_der(),
+            certified_key.key_pair.serialize_der()
+        );
+    }
+
+    #[test]
+    fn test_client_certificate_from_pem_missing_key() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("client")]).unwrap();
+        let result = ClientCertificate::from_pem(certified_key.cert.pem().as_bytes(), b"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_client_certificate_from_der() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("client")]).unwrap();
+        let result = ClientCertificate::from_der(
+            vec![certified_key.cert.der().to_vec()],
+            certified_key.key_pair.serialize_der(),
+        )
+        .unwrap();
+        assert_eq!(result.certificate_chain.len(), 1);
+        assert!(ClientCertificate::from_der(vec![], vec![1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_client_certificate_from_der_empty_chain() {
+        let certified_key =
+            rcgen::generate_simple_self_signed(vec![String::from("client")]).unwrap();
+        let result = ClientCertificate::from_der(vec![], certified_key.key_pair.serialize_der());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
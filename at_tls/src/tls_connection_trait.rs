@@ -1,14 +1,47 @@
 use std::io::{Read, Result, Write};
+use std::time::Duration;
 
 use crate::at_server_addr::AtServerAddr;
+use crate::tls_connection_config::TlsConnectionConfig;
 
 /// Represents a TLS connection to a server for sending and receiving data.
 ///
 /// This trait is used to abstract the underlying TLS library used to connect to the server.
 /// The super trait `Read` and `Write` are used to read and write data to the server.
-pub trait TlsConnection: Read + Write {
-    /// Creates a new TLS connection to the specified server address.
+pub trait TlsConnection: Read + Write + Send {
+    /// Creates a new TLS connection to the specified server address using the default config.
     fn connect(address: &AtServerAddr) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::connect_with_config(address, &TlsConnectionConfig::default())
+    }
+
+    /// Creates a new TLS connection to the specified server address.
+    ///
+    /// Implementations should fail with `std::io::ErrorKind::TimedOut` when one of the timeouts in `config` elapses.
+    fn connect_with_config(address: &AtServerAddr, config: &TlsConnectionConfig) -> Result<Self>
     where
         Self: Sized;
+
+    /// Tells the server the connection is closing, e.g. with a TLS close_notify alert.
+    ///
+    /// Nothing should be sent or received afterwards. By default only flushes what has been written.
+    fn close(&mut self) -> Result<()> {
+        self.flush()
+    }
+
+    /// Changes how long a write may block before failing with `std::io::ErrorKind::TimedOut`.
+    ///
+    /// By default does nothing, for connections whose writes don't block.
+    fn set_write_timeout(&mut self, _timeout: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Changes how long a read may block before failing with `std::io::ErrorKind::TimedOut`.
+    ///
+    /// By default does nothing, for connections whose reads don't block.
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,142 @@
+use std::io::{Error, ErrorKind, Result};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use crate::async_tls_connection_trait::AsyncTlsConnection;
+use crate::at_server_addr::AtServerAddr;
+use crate::rustls_connection::RustlsConnection;
+use crate::tls_connection_config::TlsConnectionConfig;
+
+/// An async TLS connection using rustls on top of a tokio `TcpStream`.
+///
+/// Certificate verification and client authentication are configured exactly like `RustlsConnection`.
+pub struct TokioRustlsConnection {
+    stream: TlsStream<TcpStream>,
+}
+
+impl TokioRustlsConnection {
+    /// Opens the TCP connection and performs the TLS handshake.
+    async fn connect_and_handshake(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> Result<Self> {
+        let client_config = RustlsConnection::build_client_config(address, config)?;
+        let connector = TlsConnector::from(Arc::new(client_config));
+        // Create a DNS name from the at sign server address (i.e. the host)
+        let dns_name = address
+            .host
+            .to_owned()
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Invalid DNS name"))?;
+        let tcp_stream = TcpStream::connect((address.host.as_str(), address.port)).await?;
+        let stream = connector.connect(dns_name, tcp_stream).await?;
+        Ok(Self { stream })
+    }
+}
+
+/// Runs `future`, failing with `ErrorKind::TimedOut` if it takes longer than `timeout`.
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|e| Error::new(ErrorKind::TimedOut, e))?,
+        None => future.await,
+    }
+}
+
+impl AsyncTlsConnection for TokioRustlsConnection {
+    async fn connect_with_config(
+        address: &AtServerAddr,
+        config: &TlsConnectionConfig,
+    ) -> Result<Self> {
+        with_timeout(
+            config.connect_timeout,
+            Self::connect_and_handshake(address, config),
+        )
+        .await
+    }
+}
+
+impl AsyncRead for TokioRustlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TokioRustlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    #[tokio::test]
+    async fn test_connect_and_echo() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let config = config_trusting(&certified_key);
+        let mut subject = TokioRustlsConnection::connect_with_config(&address, &config)
+            .await
+            .unwrap();
+        subject.write_all(b"hello\n").await.unwrap();
+        let mut response = String::new();
+        BufReader::new(&mut subject)
+            .read_line(&mut response)
+            .await
+            .unwrap();
+        assert_eq!(response, "data:hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_certificate_rejected() {
+        let certified_key = create_certificate();
+        let address = start_tls_server(&certified_key);
+        let config = TlsConnectionConfig::default();
+        let result = TokioRustlsConnection::connect_with_config(&address, &config).await;
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_during_handshake() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = AtServerAddr::new(
+            String::from("127.0.0.1"),
+            listener.local_addr().unwrap().port(),
+        );
+        let config = TlsConnectionConfig::new(Some(Duration::from_millis(200)), None, None);
+        let result = TokioRustlsConnection::connect_with_config(&address, &config).await;
+        assert_eq!(result.err().unwrap().kind(), ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,327 @@
+//! The text format of protocol traces written by `RecordingConnection` and played back by `ReplayConnection`.
+//!
+//! A trace holds one or more sessions. Each starts with a header naming the server,
+//! followed by one line per protocol line sent (`> `) or received (`< `):
+//!
+//! ```text
+//! # session vip.ve.atsign.zone:1234
+//! > from:@alice
+//! < @data:_70a8b1c4-...
+//! > pkam:<redacted>
+//! < @data:success
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use log::warn;
+
+use crate::at_server_addr::AtServerAddr;
+
+/// Replaces the secret part of a redacted line.
+pub const REDACTED: &str = "<redacted>";
+
+const SESSION_HEADER: &str = "# session ";
+const SENT_PREFIX: &str = "> ";
+const RECEIVED_PREFIX: &str = "< ";
+
+/// One protocol line, without the trailing newline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    Sent(String),
+    Received(String),
+}
+
+/// The lines exchanged over a single connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceSession {
+    /// The `host:port` the connection was made to.
+    pub address: String,
+    pub events: Vec<TraceEvent>,
+}
+
+impl TraceSession {
+    /// Parses all sessions in a trace.
+    pub fn parse_all(trace: &str) -> Result<Vec<TraceSession>> {
+        let mut sessions: Vec<TraceSession> = vec![];
+        for (index, line) in trace.lines().enumerate() {
+            if let Some(address) = line.strip_prefix(SESSION_HEADER) {
+                sessions.push(TraceSession {
+                    address: address.to_owned(),
+                    events: vec![],
+                });
+                continue;
+            }
+            let event = if let Some(sent) = line.strip_prefix(SENT_PREFIX) {
+                TraceEvent::Sent(sent.to_owned())
+            } else if let Some(received) = line.strip_prefix(RECEIVED_PREFIX) {
+                TraceEvent::Received(received.to_owned())
+            } else if line.is_empty() || line.starts_with('#') {
+                continue;
+            } else {
+                return Err(invalid_trace(index, "unknown line type"));
+            };
+            match sessions.last_mut() {
+                Some(session) => session.events.push(event),
+                None => return Err(invalid_trace(index, "line before the first session")),
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// Parses the first session in the trace made to `address`, e.g. to skip the root server lookup.
+    pub fn parse_for_address(trace: &str, address: &AtServerAddr) -> Result<TraceSession> {
+        let address = format!("{}:{}", address.host, address.port);
+        Self::parse_all(trace)?
+            .into_iter()
+            .find(|session| session.address == address)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("No session with {} in trace", address),
+                )
+            })
+    }
+}
+
+fn invalid_trace(index: usize, reason: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Invalid trace at line {}: {}", index + 1, reason),
+    )
+}
+
+/// The header line which starts a session with `address`.
+fn format_session_header(address: &AtServerAddr) -> String {
+    format!("{}{}:{}\n", SESSION_HEADER, address.host, address.port)
+}
+
+/// Formats an event as a line of the trace, including the newline.
+fn format_event(event: &TraceEvent) -> String {
+    match event {
+        TraceEvent::Sent(line) => format!("{}{}\n", SENT_PREFIX, line),
+        TraceEvent::Received(line) => format!("{}{}\n", RECEIVED_PREFIX, line),
+    }
+}
+
+/// Writes the lines of one session to a trace, redacted with `RedactionRules`.
+///
+/// Used by `RecordingConnection` and `AsyncTlsClient`. Failing to write the trace is logged
+/// but never fails the connection itself.
+pub(crate) struct TraceRecorder {
+    trace: Box<dyn Write + Send>,
+    redaction_rules: RedactionRules,
+    redact_next_response: bool,
+}
+
+impl TraceRecorder {
+    /// Starts a new session with `address` in `trace`.
+    pub(crate) fn new(
+        address: &AtServerAddr,
+        trace: Box<dyn Write + Send>,
+        redaction_rules: RedactionRules,
+    ) -> Self {
+        let mut recorder = Self {
+            trace,
+            redaction_rules,
+            redact_next_response: false,
+        };
+        recorder.write_trace(&format_session_header(address));
+        recorder
+    }
+
+    /// Starts a new session with `address`, appended to the trace file at `path` with the default redaction rules.
+    pub(crate) fn append_to_file(path: &Path, address: &AtServerAddr) -> Result<Self> {
+        let trace = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(
+            address,
+            Box::new(trace),
+            RedactionRules::default(),
+        ))
+    }
+
+    fn write_trace(&mut self, data: &str) {
+        if let Err(e) = self
+            .trace
+            .write_all(data.as_bytes())
+            .and_then(|_| self.trace.flush())
+        {
+            warn!("Failed to write protocol trace: {}", e);
+        }
+    }
+
+    /// Records a line sent to the server, without its newline.
+    pub(crate) fn record_sent(&mut self, line: &str) {
+        self.redact_next_response = self.redaction_rules.is_response_redacted(line);
+        let line = self.redaction_rules.redact_command(line);
+        self.write_trace(&format_event(&TraceEvent::Sent(line)));
+    }
+
+    /// Records a line received from the server, without its newline.
+    pub(crate) fn record_received(&mut self, line: &str) {
+        let line = if std::mem::take(&mut self.redact_next_response) {
+            RedactionRules::redact_response(line)
+        } else {
+            line.to_owned()
+        };
+        self.write_trace(&format_event(&TraceEvent::Received(line)));
+    }
+}
+
+/// Decides which parts of a session are replaced with `REDACTED` before being written to a trace.
+///
+/// Redacted lines still replay: `ReplayConnection` matches a redacted line against any line with the same prefix.
+#[derive(Debug, Clone)]
+pub struct RedactionRules {
+    /// Commands starting with one of these prefixes have everything after the prefix redacted.
+    pub redacted_commands: Vec<String>,
+
+    /// Commands starting with one of these prefixes have their value, everything after the first space, redacted.
+    pub redacted_values: Vec<String>,
+
+    /// Responses to commands starting with one of these prefixes have their value redacted.
+    pub redacted_responses: Vec<String>,
+
+    /// Responses to commands starting with one of these prefixes are kept, even if they match `redacted_responses`.
+    pub kept_responses: Vec<String>,
+}
+
+impl RedactionRules {
+    pub fn new(
+        redacted_commands: Vec<String>,
+        redacted_values: Vec<String>,
+        redacted_responses: Vec<String>,
+        kept_responses: Vec<String>,
+    ) -> Self {
+        Self {
+            redacted_commands,
+            redacted_values,
+            redacted_responses,
+            kept_responses,
+        }
+    }
+
+    /// Redacts nothing. Only use this for sessions with throwaway keys.
+    pub fn none() -> Self {
+        Self::new(vec![], vec![], vec![], vec![])
+    }
+
+    /// Returns the line to write to the trace for a command sent to the server.
+    pub fn redact_command(&self, command: &str) -> String {
+        if let Some(prefix) = find_prefix(&self.redacted_commands, command) {
+            return format!("{}{}", prefix, REDACTED);
+        }
+        match command.split_once(' ') {
+            Some((before_value, _)) if find_prefix(&self.redacted_values, command).is_some() => {
+                format!("{} {}", before_value, REDACTED)
+            }
+            _ => command.to_owned(),
+        }
+    }
+
+    /// Whether the response to `command` needs to be passed through `redact_response`.
+    pub fn is_response_redacted(&self, command: &str) -> bool {
+        find_prefix(&self.redacted_responses, command).is_some()
+            && find_prefix(&self.kept_responses, command).is_none()
+    }
+
+    /// Replaces the value of a `data:` response, keeping the prompt and errors readable.
+    pub fn redact_response(response: &str) -> String {
+        match response.find("data:") {
+            Some(index) => format!("{}{}", &response[..index + "data:".len()], REDACTED),
+            None => response.to_owned(),
+        }
+    }
+}
+
+/// The first of `prefixes` which `line` starts with.
+fn find_prefix<'p>(prefixes: &'p [String], line: &str) -> Option<&'p String> {
+    prefixes
+        .iter()
+        .find(|prefix| line.starts_with(prefix.as_str()))
+}
+
+impl Default for RedactionRules {
+    /// Redacts PKAM and CRAM signatures, the values sent with `update`, which include encrypted symmetric keys,
+    /// the whole of `batch` commands, which can hold the same `update` commands,
+    /// and the values returned by `llookup` and `sync`, which can be private data other SDKs stored unencrypted.
+    /// The metadata returned by `llookup:meta` is kept, so sessions which check it can be replayed.
+    fn default() -> Self {
+        Self::new(
+            vec![
+                String::from("pkam:"),
+                String::from("cram:"),
+                String::from("batch:"),
+            ],
+            vec![String::from("update:")],
+            vec![String::from("llookup:"), String::from("sync:")],
+            vec![String::from("llookup:meta:")],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_all() {
+        let trace = "# session root.atsign.org:64\n> alice\n< @vip.ve.atsign.zone:1234\n\n# session vip.ve.atsign.zone:1234\n> from:@alice\n< @data:challenge\n";
+        let sessions = TraceSession::parse_all(trace).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].address, "root.atsign.org:64");
+        assert_eq!(
+            sessions[1].events,
+            vec![
+                TraceEvent::Sent(String::from("from:@alice")),
+                TraceEvent::Received(String::from("@data:challenge")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(TraceSession::parse_all("> scan\n").is_err());
+        assert!(TraceSession::parse_all("# session host:1\nscan\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_for_address() {
+        let trace = "# session localhost:64\n> alice\n# session localhost:1234\n> scan\n";
+        let address = AtServerAddr::new(String::from("localhost"), 1234);
+        let session = TraceSession::parse_for_address(trace, &address).unwrap();
+        assert_eq!(session.events, vec![TraceEvent::Sent(String::from("scan"))]);
+        let address = AtServerAddr::new(String::from("localhost"), 4321);
+        assert!(TraceSession::parse_for_address(trace, &address).is_err());
+    }
+
+    #[test]
+    fn test_default_redaction() {
+        let rules = RedactionRules::default();
+        assert_eq!(rules.redact_command("pkam:c2lnbmF0dXJl"), "pkam:<redacted>");
+        assert_eq!(rules.redact_command("from:@alice"), "from:@alice");
+        assert_eq!(
+            rules.redact_command("update:ttr:86400:@bob:shared_key@alice c2VjcmV0"),
+            "update:ttr:86400:@bob:shared_key@alice <redacted>"
+        );
+        assert_eq!(
+            rules.redact_command(
+                r#"batch:[{"id":1,"command":"update:@bob:message@alice c2VjcmV0"}]"#
+            ),
+            "batch:<redacted>"
+        );
+        assert!(rules.is_response_redacted("llookup:phone@alice"));
+        assert!(!rules.is_response_redacted("llookup:meta:cached:@alice:phone@bob"));
+        assert!(rules.is_response_redacted("sync:from:-1:limit:100"));
+        assert!(!rules.is_response_redacted("scan"));
+        assert_eq!(
+            RedactionRules::redact_response("@alice@data:secret"),
+            "@alice@data:<redacted>"
+        );
+        assert_eq!(
+            RedactionRules::redact_response("@alice@error:AT0015-key not found"),
+            "@alice@error:AT0015-key not found"
+        );
+    }
+}
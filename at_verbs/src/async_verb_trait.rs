@@ -0,0 +1,120 @@
+use std::future::Future;
+
+use at_tls::async_tls_client::AsyncTlsClient;
+
+use super::prelude::*;
+
+/// The async counterpart of `Verb`.
+///
+/// Implemented for every `Verb` whose inputs and output can be sent between threads,
+/// so commands are formatted and responses parsed exactly like the sync verbs.
+pub trait AsyncVerb<'a>: Verb<'a> {
+    /// Execute the verb with the given inputs and async tls_client.
+    fn execute_async(
+        tls_client: &mut AsyncTlsClient,
+        input: Self::Inputs,
+    ) -> impl Future<Output = Result<Self::Output>> + Send;
+}
+
+impl<'a, V> AsyncVerb<'a> for V
+where
+    V: Verb<'a>,
+    V::Inputs: Send,
+    V::Output: Send,
+{
+    fn execute_async(
+        tls_client: &mut AsyncTlsClient,
+        input: Self::Inputs,
+    ) -> impl Future<Output = Result<Self::Output>> + Send {
+        // Not an `async fn`: that would require `Inputs: Send` for every lifetime, not just `'a`
+        let command = V::format_command(&input);
+        async move {
+            tls_client.send_data(command?).await?;
+            if !V::expects_response() {
+                return V::parse_response(&[], &input);
+            }
+            let response_data = tls_client.read_data().await?;
+            V::parse_response(&response_data, &input)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at_sign::AtSign;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    use super::*;
+    use crate::exit_verb::ExitVerb;
+    use crate::from_verb::{FromVerb, FromVerbInputs};
+    use crate::mocks::mock_async_tls_connection::MockAsyncTlsConnection;
+
+    #[tokio::test]
+    async fn test_from_verb_execute_async() {
+        // Arrange
+        let (connection, server) = MockAsyncTlsConnection::pair();
+        let mut tls_client = AsyncTlsClient::new(Box::new(connection));
+        let server_task = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut command = String::new();
+            server.read_line(&mut command).await.unwrap();
+            server
+                .get_mut()
+                .write_all(b"@data:challenge-text\n")
+                .await
+                .unwrap();
+            command
+        });
+        let at_sign = AtSign::new(String::from("atsign123"));
+
+        // Act
+        let result = FromVerb::execute_async(&mut tls_client, FromVerbInputs::new(&at_sign))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(result, "challenge-text");
+        assert_eq!(server_task.await.unwrap(), "from:atsign123\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_async_server_error() {
+        let (connection, server) = MockAsyncTlsConnection::pair();
+        let mut tls_client = AsyncTlsClient::new(Box::new(connection));
+        tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut command = String::new();
+            server.read_line(&mut command).await.unwrap();
+            server
+                .get_mut()
+                .write_all(b"error:AT0001-Server exception\n")
+                .await
+                .unwrap();
+        });
+        let at_sign = AtSign::new(String::from("atsign123"));
+
+        let result = FromVerb::execute_async(&mut tls_client, FromVerbInputs::new(&at_sign)).await;
+
+        assert_eq!(
+            result.err().unwrap().server_code(),
+            Some(&at_errors::ServerErrorCode::ServerException)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_async_does_not_wait_for_a_reply_when_none_is_expected() {
+        let (connection, server) = MockAsyncTlsConnection::pair();
+        let mut tls_client = AsyncTlsClient::new(Box::new(connection));
+        let server_task = tokio::spawn(async move {
+            let mut server = BufReader::new(server);
+            let mut command = String::new();
+            server.read_line(&mut command).await.unwrap();
+            // Closes the connection without replying, like the atServer does
+            command
+        });
+
+        ExitVerb::execute_async(&mut tls_client, ()).await.unwrap();
+
+        assert_eq!(server_task.await.unwrap(), "exit\n");
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+
+/// Commands to run together with one round trip, e.g. the output of `UpdateVerb::format_command`.
+#[derive(Debug, Default)]
+pub struct BatchVerbInputs {
+    pub commands: Vec<String>,
+}
+
+impl BatchVerbInputs {
+    pub fn new(commands: Vec<String>) -> Self {
+        Self { commands }
+    }
+}
+
+#[derive(Serialize)]
+struct BatchRequestJson<'a> {
+    id: usize,
+    command: &'a str,
+}
+
+#[derive(Deserialize)]
+struct BatchResponseJson {
+    id: usize,
+    response: CommandResponseJson,
+}
+
+#[derive(Deserialize)]
+struct CommandResponseJson {
+    data: Option<String>,
+    error_code: Option<String>,
+    error_message: Option<String>,
+}
+
+/// The batch verb runs several `update` and `delete` commands with one round trip.
+/// Each command succeeds or fails on its own, so the output has the result of each command in the order they were given.
+pub struct BatchVerb;
+
+impl<'a> Verb<'a> for BatchVerb {
+    type Inputs = BatchVerbInputs;
+    type Output = Vec<Result<String>>;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        // Example command: batch:[{"id":1,"command":"update:phone@alice 123"}]
+        let requests: Vec<BatchRequestJson> = input
+            .commands
+            .iter()
+            .enumerate()
+            .map(|(index, command)| BatchRequestJson {
+                id: index + 1,
+                command,
+            })
+            .collect();
+        Ok(format!("batch:{}", serde_json::to_string(&requests)?))
+    }
+
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: [{"id":1,"response":{"data":"5"}},{"id":2,"response":{"error_code":"AT0015","error_message":"key not found"}}]
+        let responses: Vec<BatchResponseJson> = serde_json::from_str(&response_string)?;
+        let mut responses: HashMap<usize, CommandResponseJson> = responses
+            .into_iter()
+            .map(|response| (response.id, response.response))
+            .collect();
+        Ok((1..=input.commands.len())
+            .map(|id| match responses.remove(&id) {
+                Some(CommandResponseJson {
+                    error_code: Some(code),
+                    error_message,
+                    ..
+                }) => Err(AtError::from_server_error(&format!(
+                    "{}-{}",
+                    code,
+                    error_message.unwrap_or_default()
+                ))),
+                Some(CommandResponseJson {
+                    data: Some(data), ..
+                }) => Ok(data),
+                _ => Err(AtError::UnexpectedResponse(format!(
+                    "No response to batch command {}",
+                    id
+                ))),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at_errors::ServerErrorCode;
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_batch_verb_execute() {
+        let connection = Box::new(MockTlsConnection::new(vec![MockStep::exchange(
+            r#"batch:[{"id":1,"command":"update:phone@alice 123"},{"id":2,"command":"delete:email@alice"},{"id":3,"command":"delete:other@alice"}]"#,
+            r#"@alice@data:[{"id":2,"response":{"error_code":"AT0015","error_message":"key not found"}},{"id":1,"response":{"data":"5"}}]"#,
+        )]));
+        let mut tls_client = TlsClient::new(connection);
+
+        let input = BatchVerbInputs::new(vec![
+            String::from("update:phone@alice 123"),
+            String::from("delete:email@alice"),
+            String::from("delete:other@alice"),
+        ]);
+        let results = BatchVerb::execute(&mut tls_client, input).unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), "5");
+        assert_eq!(
+            results[1].as_ref().unwrap_err().server_code(),
+            Some(&ServerErrorCode::KeyNotFound)
+        );
+        assert!(matches!(results[2], Err(AtError::UnexpectedResponse(_))));
+    }
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use at_sign::AtSign;
+
+use super::prelude::*;
+
+/// The lists of atSigns the atServer keeps for its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigList {
+    /// atSigns which may not send the owner notifications or connect with `pol`.
+    Block,
+    /// atSigns which may, when the atServer only accepts listed atSigns.
+    Allow,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOperation {
+    Add(Vec<AtSign>),
+    Remove(Vec<AtSign>),
+    Show,
+}
+
+pub struct ConfigVerbInputs {
+    pub list: ConfigList,
+    pub operation: ConfigOperation,
+}
+
+impl ConfigVerbInputs {
+    pub fn new(list: ConfigList, operation: ConfigOperation) -> Self {
+        Self { list, operation }
+    }
+}
+
+/// The config verb changes or shows the owner's block and allow lists.
+/// Adding or removing atSigns outputs nothing, showing outputs the atSigns on the list.
+pub struct ConfigVerb;
+
+impl<'a> Verb<'a> for ConfigVerb {
+    type Inputs = ConfigVerbInputs;
+    type Output = Vec<AtSign>;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        let mut string_buf = String::from("config:");
+        match input.list {
+            ConfigList::Block => string_buf.push_str("block:"),
+            ConfigList::Allow => string_buf.push_str("allow:"),
+        }
+        let at_signs = match &input.operation {
+            ConfigOperation::Add(at_signs) => {
+                string_buf.push_str("add:");
+                at_signs
+            }
+            ConfigOperation::Remove(at_signs) => {
+                string_buf.push_str("remove:");
+                at_signs
+            }
+            ConfigOperation::Show => {
+                string_buf.push_str("show");
+                return Ok(string_buf);
+            }
+        };
+        let at_signs: Vec<String> = at_signs.iter().map(|at_sign| at_sign.to_string()).collect();
+        string_buf.push_str(&at_signs.join(" "));
+        Ok(string_buf)
+    }
+
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        if input.operation != ConfigOperation::Show {
+            return Ok(Vec::new());
+        }
+        // Example response: ["@alice","@bob"], or null if the list is empty
+        let at_signs: Option<Vec<String>> = serde_json::from_str(&response_string)?;
+        at_signs
+            .unwrap_or_default()
+            .iter()
+            .map(|at_sign| {
+                AtSign::from_str(at_sign)
+                    .map_err(|_| AtError::UnexpectedResponse(response_string.clone()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_config_verb_execute() {
+        let connection = Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange("config:block:add:@bob @carol", "@alice@data:success"),
+            MockStep::exchange("config:block:show", r#"@alice@data:["@bob","@carol"]"#),
+            MockStep::exchange("config:allow:remove:@bob", "@alice@data:success"),
+            MockStep::exchange("config:allow:show", "@alice@data:null"),
+        ]));
+        let mut tls_client = TlsClient::new(connection);
+        let bob = AtSign::from_str("bob").unwrap();
+        let carol = AtSign::from_str("carol").unwrap();
+
+        let input = ConfigVerbInputs::new(
+            ConfigList::Block,
+            ConfigOperation::Add(vec![bob.clone(), carol.clone()]),
+        );
+        assert!(ConfigVerb::execute(&mut tls_client, input)
+            .unwrap()
+            .is_empty());
+        let input = ConfigVerbInputs::new(ConfigList::Block, ConfigOperation::Show);
+        assert_eq!(
+            ConfigVerb::execute(&mut tls_client, input).unwrap(),
+            vec![bob.clone(), carol]
+        );
+        let input = ConfigVerbInputs::new(ConfigList::Allow, ConfigOperation::Remove(vec![bob]));
+        assert!(ConfigVerb::execute(&mut tls_client, input)
+            .unwrap()
+            .is_empty());
+        let input = ConfigVerbInputs::new(ConfigList::Allow, ConfigOperation::Show);
+        assert!(ConfigVerb::execute(&mut tls_client, input)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+use at_records::at_key::AtKey;
+
+use super::prelude::*;
+
+pub struct DeleteVerbInputs<'a> {
+    /// The AtKey of the key-value pair to be deleted.
+    at_key: &'a AtKey,
+}
+
+impl<'a> DeleteVerbInputs<'a> {
+    pub fn new(at_key: &'a AtKey) -> Self {
+        Self { at_key }
+    }
+}
+
+/// The delete verb removes a key-value pair from the owner's atServer.
+/// The atServer responds with the commit ID of the deletion.
+pub struct DeleteVerb;
+
+impl<'a> Verb<'a> for DeleteVerb {
+    type Inputs = DeleteVerbInputs<'a>;
+    type Output = String;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        Ok(format!("delete:{}", input.at_key.full_key()))
+    }
+
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string =
+            Self::parse_server_response(response, "data").map_err(|e| e.with_key(input.at_key))?;
+
+        Ok(response_string)
+    }
+}
@@ -0,0 +1,40 @@
+use super::prelude::*;
+
+/// The exit verb tells the atServer the client is leaving, so it can close the connection straight away
+/// rather than counting it as open until it times out.
+///
+/// The atServer closes the connection without replying, so executing it only sends the command.
+pub struct ExitVerb;
+
+impl<'a> Verb<'a> for ExitVerb {
+    type Inputs = ();
+    type Output = ();
+
+    fn format_command(_input: &Self::Inputs) -> Result<String> {
+        Ok(String::from("exit"))
+    }
+
+    fn parse_response(_response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        Ok(())
+    }
+
+    fn expects_response() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_exit_verb_does_not_wait_for_a_reply() {
+        let mut tls_client = TlsClient::new(Box::new(MockTlsConnection::new(vec![
+            MockStep::Expect(String::from("exit")),
+            MockStep::Close,
+        ])));
+        ExitVerb::execute(&mut tls_client, ()).unwrap();
+        assert!(tls_client.read_data().is_err());
+    }
+}
@@ -24,22 +24,24 @@ impl<'a> Verb<'a> for FromVerb {
     type Inputs = FromVerbInputs<'a>;
     type Output = String;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         debug!("Starting PKAM authentication");
         // AtSign can be with or without the "@"
-        let data_to_send = format!("from:{}\n", input.at_sign.get_at_sign_without_prefix());
+        let data_to_send = format!("from:{}", input.at_sign.get_at_sign_without_prefix());
         debug!("Sending: {}", &data_to_send);
-        tls_client.send_data(data_to_send)?;
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "@data")?;
+        Ok(data_to_send)
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
         debug!("Challenge: {}", response_string);
-        Ok(response_string.to_owned())
+        Ok(response_string)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::mocks::mock_tls_connection::MockTlsConnection;
+    use crate::mocks::{MockStep, MockTlsConnection};
     use at_sign::AtSign;
     use at_tls::TlsClient;
 
@@ -49,10 +51,15 @@ mod tests {
     fn test_from_verb_execute() {
         // Arrange
         // What the server will typically respond with
-        let to_be_read = b"@data:_7089d2f7-b783-474e-826e-0f0561ef70b7@atsign123:bdf16168-c2c8-488c-937a-b0acfb6662a0\n";
-        let connection = Box::new(MockTlsConnection::new(vec![], to_be_read.to_vec()));
+        let connection = Box::new(MockTlsConnection::new(vec![
+            MockStep::Send(String::from("@")),
+            MockStep::exchange(
+                "from:atsign123",
+                "data:_7089d2f7-b783-474e-826e-0f0561ef70b7@atsign123:bdf16168-c2c8-488c-937a-b0acfb6662a0",
+            ),
+        ]));
         let mut mock_tls_client = TlsClient::new(connection);
-        let at_sign = AtSign::new(String::from("@atsign123"));
+        let at_sign = AtSign::new(String::from("atsign123"));
 
         // Act
         let from_verb_inputs = FromVerbInputs::new(&at_sign);
@@ -64,4 +71,31 @@ mod tests {
             "_7089d2f7-b783-474e-826e-0f0561ef70b7@atsign123:bdf16168-c2c8-488c-937a-b0acfb6662a0"
         )
     }
+
+    #[test]
+    fn test_from_verb_parses_data_response() {
+        let at_sign = AtSign::new(String::from("atsign123"));
+        let from_verb_inputs = FromVerbInputs::new(&at_sign);
+        // The challenge follows `data:`, with or without the atServer's prompt
+        for response in [
+            "data:_abc@atsign123:def",
+            "@data:_abc@atsign123:def",
+            "@atsign123@data:_abc@atsign123:def",
+        ] {
+            assert_eq!(
+                FromVerb::parse_response(response.as_bytes(), &from_verb_inputs).unwrap(),
+                "_abc@atsign123:def"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_verb_normalizes_at_sign() {
+        let at_sign = AtSign::new(String::from("@AtSign123"));
+        let from_verb_inputs = FromVerbInputs::new(&at_sign);
+        assert_eq!(
+            FromVerb::format_command(&from_verb_inputs).unwrap(),
+            "from:atsign123"
+        );
+    }
 }
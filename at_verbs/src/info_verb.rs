@@ -0,0 +1,82 @@
+use serde::Deserialize;
+
+use super::prelude::*;
+
+/// What the atServer says about itself in response to the `info` verb.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    /// The atServer's version, e.g. `3.0.42`.
+    pub version: String,
+    /// How long the atServer has been running, in words, e.g. `2 days 3 hours`.
+    #[serde(default)]
+    pub uptime_as_words: Option<String>,
+    /// The optional features the atServer supports.
+    #[serde(default)]
+    pub features: Vec<ServerFeature>,
+}
+
+impl ServerInfo {
+    /// Whether the atServer supports the feature with the given name.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f.name == feature)
+    }
+}
+
+/// An optional feature of the atServer, e.g. `ntfns:ns:patterns`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ServerFeature {
+    pub name: String,
+    /// How ready the feature is, e.g. `Preview` or `GA`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// The info verb asks the atServer for its version and features.
+/// It doesn't need authentication, so is a cheap way to check the atServer is up.
+pub struct InfoVerb;
+
+impl<'a> Verb<'a> for InfoVerb {
+    type Inputs = ();
+    type Output = ServerInfo;
+
+    fn format_command(_input: &Self::Inputs) -> Result<String> {
+        Ok(String::from("info"))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: {"version":"3.0.42","uptimeAsWords":"2 days","features":[{"name":"ntfns:ns:patterns","status":"Preview","description":"..."}]}
+        Ok(serde_json::from_str(&response_string)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_info_verb() {
+        let mut tls_client = TlsClient::new(Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange(
+                "info",
+                r#"data:{"version":"3.0.42","uptimeAsWords":"2 days","features":[{"name":"ntfns:ns:patterns","status":"Preview","description":"Notification patterns"}]}"#,
+            ),
+        ])));
+        let info = InfoVerb::execute(&mut tls_client, ()).unwrap();
+        assert_eq!(info.version, "3.0.42");
+        assert_eq!(info.uptime_as_words.as_deref(), Some("2 days"));
+        assert!(info.supports("ntfns:ns:patterns"));
+        assert!(!info.supports("batch"));
+
+        let parse = |response: &str| InfoVerb::parse_response(response.as_bytes(), &());
+        assert_eq!(
+            parse(r#"data:{"version":"3.0.0"}"#).unwrap().features,
+            vec![]
+        );
+        assert!(parse(r#"data:{"features":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Checks how every verb that takes an `AtKey` writes each kind of key to the atServer.
+
+use std::str::FromStr;
+
+use at_records::{at_key::AtKey, at_record::AtValue};
+
+use crate::delete_verb::{DeleteVerb, DeleteVerbInputs};
+use crate::llookup_verb::{LlookupReturnType, LlookupVerb, LlookupVerbInputs};
+use crate::lookup_verb::{LookupReturnType, LookupVerb, LookupVerbInputs};
+use crate::plookup_verb::{PlookupReturnType, PlookupVerb, PlookupVerbInputs};
+use crate::update_verb::{UpdateVerb, UpdateVerbInputs};
+use crate::verb_trait::Verb;
+
+/// The key, followed by the command each verb sends for it: update, llookup, lookup and plookup.
+const MATRIX: [(&str, [&str; 4]); 7] = [
+    (
+        "public:phone.wavi@alice",
+        [
+            "update:public:phone.wavi@alice value",
+            "llookup:public:phone.wavi@alice",
+            "lookup:phone.wavi@alice",
+            "plookup:phone.wavi@alice",
+        ],
+    ),
+    (
+        "private:phone.wavi@alice",
+        [
+            "update:phone.wavi@alice value",
+            "llookup:phone.wavi@alice",
+            "lookup:phone.wavi@alice",
+            "plookup:phone.wavi@alice",
+        ],
+    ),
+    (
+        "shared_key.bob@alice",
+        [
+            "update:shared_key.bob@alice value",
+            "llookup:shared_key.bob@alice",
+            "lookup:shared_key.bob@alice",
+            "plookup:shared_key.bob@alice",
+        ],
+    ),
+    (
+        "_latestnotificationid.wavi@alice",
+        [
+            "update:_latestnotificationid.wavi@alice value",
+            "llookup:_latestnotificationid.wavi@alice",
+            "lookup:_latestnotificationid.wavi@alice",
+            "plookup:_latestnotificationid.wavi@alice",
+        ],
+    ),
+    (
+        "@bob:phone.wavi@alice",
+        [
+            "update:@bob:phone.wavi@alice value",
+            "llookup:@bob:phone.wavi@alice",
+            "lookup:phone.wavi@alice",
+            "plookup:phone.wavi@alice",
+        ],
+    ),
+    (
+        "cached:@bob:phone.wavi@alice",
+        [
+            "update:cached:@bob:phone.wavi@alice value",
+            "llookup:cached:@bob:phone.wavi@alice",
+            "lookup:phone.wavi@alice",
+            "plookup:phone.wavi@alice",
+        ],
+    ),
+    (
+        "cached:public:publickey@bob",
+        [
+            "update:cached:public:publickey@bob value",
+            "llookup:cached:public:publickey@bob",
+            "lookup:publickey@bob",
+            "plookup:publickey@bob",
+        ],
+    ),
+];
+
+fn format_commands(at_key: &AtKey) -> [String; 4] {
+    let value = AtValue::Text(String::from("value"));
+    [
+        UpdateVerb::format_command(&UpdateVerbInputs::new(at_key, &value)).unwrap(),
+        LlookupVerb::format_command(&LlookupVerbInputs::new(at_key, LlookupReturnType::Data))
+            .unwrap(),
+        LookupVerb::format_command(&LookupVerbInputs::new(at_key, LookupReturnType::Data)).unwrap(),
+        PlookupVerb::format_command(&PlookupVerbInputs::new(at_key, PlookupReturnType::Data))
+            .unwrap(),
+    ]
+}
+
+#[test]
+fn test_key_format_matrix() {
+    for (key, expected) in MATRIX {
+        let at_key = AtKey::from_str(key).unwrap();
+        assert_eq!(format_commands(&at_key), expected, "{}", key);
+    }
+}
+
+#[test]
+fn test_return_type_prefixes() {
+    let at_key = AtKey::from_str("@bob:phone.wavi@alice").unwrap();
+    assert_eq!(
+        LlookupVerb::format_command(&LlookupVerbInputs::new(&at_key, LlookupReturnType::All))
+            .unwrap(),
+        "llookup:all:@bob:phone.wavi@alice"
+    );
+    assert_eq!(
+        LookupVerb::format_command(&LookupVerbInputs::new(&at_key, LookupReturnType::Metadata))
+            .unwrap(),
+        "lookup:meta:phone.wavi@alice"
+    );
+    assert_eq!(
+        PlookupVerb::format_command(&PlookupVerbInputs::new(&at_key, PlookupReturnType::Meta))
+            .unwrap(),
+        "plookup:meta:phone.wavi@alice"
+    );
+}
+
+#[test]
+fn test_bypass_cache_prefix() {
+    let at_key = AtKey::from_str("cached:public:publickey@bob").unwrap();
+    assert_eq!(
+        LookupVerb::format_command(
+            &LookupVerbInputs::new(&at_key, LookupReturnType::Metadata).with_bypass_cache(true)
+        )
+        .unwrap(),
+        "lookup:bypassCache:true:meta:publickey@bob"
+    );
+    assert_eq!(
+        PlookupVerb::format_command(
+            &PlookupVerbInputs::new(&at_key, PlookupReturnType::Data).with_bypass_cache(true)
+        )
+        .unwrap(),
+        "plookup:bypassCache:true:publickey@bob"
+    );
+    assert_eq!(
+        PlookupVerb::format_command(
+            &PlookupVerbInputs::new(&at_key, PlookupReturnType::Data).with_bypass_cache(false)
+        )
+        .unwrap(),
+        "plookup:publickey@bob"
+    );
+}
+
+#[test]
+fn test_delete_uses_full_key() {
+    for key in ["cached:@bob:phone.wavi@alice", "private:phone.wavi@alice"] {
+        let at_key = AtKey::from_str(key).unwrap();
+        assert_eq!(
+            DeleteVerb::format_command(&DeleteVerbInputs::new(&at_key)).unwrap(),
+            format!("delete:{}", at_key.full_key())
+        );
+    }
+}
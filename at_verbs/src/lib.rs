@@ -2,17 +2,36 @@
 //! Each verb interacts with the atServer in a different way.
 //! Some are for authentication, some are for data retrieval and some are for data manipulation.
 
+pub mod batch_verb;
+pub mod config_verb;
+pub mod delete_verb;
+pub mod exit_verb;
 pub mod from_verb;
+pub mod info_verb;
 pub mod llookup_verb;
 pub mod lookup_verb;
+pub mod noop_verb;
+pub mod notify_all_verb;
+pub mod notify_list_verb;
+pub mod notify_remove_verb;
+pub mod notify_status_verb;
 pub mod pkam_verb;
 pub mod plookup_verb;
+pub mod pol_verb;
 pub mod scan_verb;
+pub mod stats_verb;
+pub mod sync_verb;
 pub mod update_verb;
 pub mod verb_trait;
 
+#[cfg(feature = "tokio")]
+pub mod async_verb_trait;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mocks;
+
 #[cfg(test)]
-mod mocks;
+mod key_format_tests;
 
 mod prelude {
     pub use crate::verb_trait::Verb;
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use at_records::{
     at_key::AtKey,
     at_record::{AtRecord, AtValue},
@@ -47,7 +49,7 @@ impl<'a> Verb<'a> for LlookupVerb {
     type Inputs = LlookupVerbInputs<'a>;
     type Output = LlookupVerbOutput;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         let mut string_buf = String::from("llookup:");
         match input.return_type {
             LlookupReturnType::Data => {}
@@ -55,45 +57,53 @@ impl<'a> Verb<'a> for LlookupVerb {
             LlookupReturnType::All => string_buf.push_str("all:"),
         }
 
-        let is_cached = if input.at_key.is_cached {
-            "cached:"
-        } else {
-            ""
-        };
-
-        // Private and internal are not supported
-        let visibility = match &input.at_key.visibility_scope {
-            at_records::at_key::Visibility::Private => String::from(""),
-            at_records::at_key::Visibility::Internal => String::from(""),
-            at_records::at_key::Visibility::Public => String::from("public:"),
-            at_records::at_key::Visibility::Shared(shared_with) => {
-                format!("{}:", shared_with.get_at_sign_with_prefix())
-            }
-        };
+        string_buf.push_str(&input.at_key.full_key());
 
-        let formatted_at_key = format!(
-            "{is_cached}{visibility}{record_id}{namespace}{owner}",
-            is_cached = &is_cached,
-            visibility = visibility,
-            record_id = &input.at_key.record_id,
-            namespace = match input.at_key.namespace.as_ref() {
-                Some(namespace) => format!(".{}", namespace),
-                None => String::from(""),
-            },
-            owner = &input.at_key.owner.get_at_sign_with_prefix()
-        );
-        string_buf.push_str(formatted_at_key.as_str());
-
-        tls_client.send_data(string_buf)?;
+        Ok(string_buf)
+    }
 
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string =
+            Self::parse_server_response(response, "data").map_err(|e| e.with_key(input.at_key))?;
 
         // TODO: Parse the response_string into the appropriate type.
         match input.return_type {
             LlookupReturnType::Data => Ok(LlookupVerbOutput::Data(AtValue::Text(response_string))),
-            LlookupReturnType::Meta => todo!(),
-            LlookupReturnType::All => todo!(),
+            LlookupReturnType::Meta => Ok(LlookupVerbOutput::Meta(RecordMetadata::from_str(
+                &response_string,
+            )?)),
+            LlookupReturnType::All => Err(AtError::Unsupported(String::from(
+                "Parsing llookup records",
+            ))),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_llookup_verb_execute_meta() {
+        let connection = Box::new(MockTlsConnection::new(vec![MockStep::exchange(
+            "llookup:meta:cached:@alice:phone.wavi@bob",
+            r#"data:{"ttr":3600000,"refreshAt":"2024-02-06 11:15:30.123Z","isBinary":false}"#,
+        )]));
+        let mut tls_client = TlsClient::new(connection);
+
+        let at_key = AtKey::from_str("cached:@alice:phone.wavi@bob").unwrap();
+        let input = LlookupVerbInputs::new(&at_key, LlookupReturnType::Meta);
+        let LlookupVerbOutput::Meta(metadata) =
+            LlookupVerb::execute(&mut tls_client, input).unwrap()
+        else {
+            panic!("Expected metadata");
+        };
+        assert_eq!(metadata.ttr(), Some(3_600_000));
+        assert_eq!(metadata.refresh_at(), Some(1_707_218_130_123));
+    }
+}
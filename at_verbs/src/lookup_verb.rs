@@ -8,6 +8,9 @@ pub struct LookupVerbInputs<'a> {
 
     /// The type of data to request from the server.
     return_type: LookupReturnType,
+
+    /// Fetch the key from its owner's atServer even if our atServer has a cached copy.
+    bypass_cache: bool,
 }
 
 impl<'a> LookupVerbInputs<'a> {
@@ -15,10 +18,17 @@ impl<'a> LookupVerbInputs<'a> {
         Self {
             at_key,
             return_type,
+            bypass_cache: false,
         }
     }
+
+    pub fn with_bypass_cache(mut self, bypass_cache: bool) -> Self {
+        self.bypass_cache = bypass_cache;
+        self
+    }
 }
 
+#[derive(Debug, Clone)]
 pub enum LookupReturnType {
     /// Just the data.
     Data,
@@ -47,8 +57,11 @@ impl<'a> Verb<'a> for LookupVerb {
     type Inputs = LookupVerbInputs<'a>;
     type Output = LookupVerbOutput;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         let mut string_buf = String::from("lookup:");
+        if input.bypass_cache {
+            string_buf.push_str("bypassCache:true:");
+        }
         match input.return_type {
             LookupReturnType::Data => {}
             LookupReturnType::Metadata => string_buf.push_str("meta:"),
@@ -56,27 +69,21 @@ impl<'a> Verb<'a> for LookupVerb {
         }
 
         // Lookup verb shouldn't include who the data is shared with
-        let formatted_at_key = format!(
-            "{record_id}{namespace}{owner}",
-            record_id = &input.at_key.record_id,
-            namespace = match &input.at_key.namespace {
-                Some(value) => format!(".{}", value),
-                None => String::from(""),
-            },
-            owner = &input.at_key.owner.get_at_sign_with_prefix()
-        );
-        string_buf.push_str(formatted_at_key.as_str());
+        string_buf.push_str(&input.at_key.lookup_key());
 
-        tls_client.send_data(string_buf)?;
+        Ok(string_buf)
+    }
 
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string =
+            Self::parse_server_response(response, "data").map_err(|e| e.with_key(input.at_key))?;
 
         // TODO: Parse the response_string into the appropriate type.
         match input.return_type {
             LookupReturnType::Data => Ok(LookupVerbOutput::Data(response_string)),
-            LookupReturnType::Metadata => todo!(),
-            LookupReturnType::All => todo!(),
+            LookupReturnType::Metadata | LookupReturnType::All => Err(AtError::Unsupported(
+                String::from("Parsing lookup metadata"),
+            )),
         }
     }
 }
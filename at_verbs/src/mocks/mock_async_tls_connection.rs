@@ -0,0 +1,58 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use at_tls::{
+    async_tls_connection_trait::AsyncTlsConnection, at_server_addr::AtServerAddr,
+    tls_connection_config::TlsConnectionConfig,
+};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+/// An in-memory async connection. The other end of the pipe plays the atServer.
+pub struct MockAsyncTlsConnection {
+    stream: DuplexStream,
+}
+
+impl MockAsyncTlsConnection {
+    /// Returns the client side connection and the server side stream.
+    pub fn pair() -> (Self, DuplexStream) {
+        let (client, server) = tokio::io::duplex(4096);
+        (Self { stream: client }, server)
+    }
+}
+
+impl AsyncRead for MockAsyncTlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockAsyncTlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+impl AsyncTlsConnection for MockAsyncTlsConnection {
+    async fn connect_with_config(
+        _address: &AtServerAddr,
+        _config: &TlsConnectionConfig,
+    ) -> std::io::Result<Self> {
+        Ok(Self::pair().0)
+    }
+}
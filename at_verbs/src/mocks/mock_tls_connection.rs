@@ -1,35 +1,158 @@
-use at_tls::{at_server_addr::AtServerAddr, tls_connection_trait};
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
 
-// Mock implementation for TlsConnection (copied from ChatGPT)
+use at_tls::{
+    at_server_addr::AtServerAddr, tls_connection_config::TlsConnectionConfig, tls_connection_trait,
+};
+
+/// One step of the conversation a `MockTlsConnection` expects to have with the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockStep {
+    /// Expect the client to send `command` (without the trailing newline), then answer with `response`.
+    Exchange { command: String, response: String },
+    /// Expect the client to send `command` without answering, e.g. before a `ReadError` or `Close`.
+    Expect(String),
+    /// Send `data` without waiting for a command, e.g. the prompt the atServer sends on connect.
+    Send(String),
+    /// Fail the next read with the given error kind.
+    ReadError(ErrorKind),
+    /// Fail the next write with the given error kind.
+    WriteError(ErrorKind),
+    /// Behave as if the server closed the connection: reads return 0 bytes.
+    Close,
+}
+
+impl MockStep {
+    pub fn exchange(command: &str, response: &str) -> Self {
+        MockStep::Exchange {
+            command: command.to_owned(),
+            response: response.to_owned(),
+        }
+    }
+}
+
+/// Options for simulating a less well behaved connection.
+#[derive(Debug, Clone, Default)]
+pub struct MockTlsConnectionOptions {
+    /// Return at most this many bytes from each read, to exercise partial reads.
+    pub max_read_size: Option<usize>,
+}
+
+/// A `TlsConnection` which follows a script of expected commands and canned responses.
+///
+/// Panics when the client sends something other than the next expected command,
+/// and when dropped before the whole script has been played.
+/// While the thread is already panicking, unexpected commands fail the write instead, so the original panic is reported.
+///
+/// An `exit` sent after the whole script has been played is accepted, as `AtClient` sends one when dropped,
+/// so scripts don't need to end with `MockStep::Expect("exit")`.
 pub struct MockTlsConnection {
-    // Add more fields as needed to simulate state and behavior
-    pub written_data: Vec<u8>, // For inspecting what was written
-    pub to_be_read: Vec<u8>,   // Data that will be "read" by the client
+    script: VecDeque<MockStep>,
+    options: MockTlsConnectionOptions,
+    /// Bytes written since the last complete line.
+    written: Vec<u8>,
+    /// Bytes waiting to be read by the client.
+    to_be_read: VecDeque<u8>,
+    closed: bool,
 }
 
 impl MockTlsConnection {
-    pub fn new(written_data: Vec<u8>, to_be_read: Vec<u8>) -> Self {
-        Self {
-            written_data,
-            to_be_read,
+    pub fn new(script: Vec<MockStep>) -> Self {
+        Self::new_with_options(script, MockTlsConnectionOptions::default())
+    }
+
+    pub fn new_with_options(script: Vec<MockStep>, options: MockTlsConnectionOptions) -> Self {
+        let mut connection = Self {
+            script: script.into(),
+            options,
+            written: vec![],
+            to_be_read: VecDeque::new(),
+            closed: false,
+        };
+        connection.play_sends();
+        connection
+    }
+
+    /// Queues any `Send` steps at the front of the script.
+    fn play_sends(&mut self) {
+        while let Some(MockStep::Send(data)) = self.script.front() {
+            self.to_be_read.extend(data.as_bytes());
+            self.script.pop_front();
+        }
+    }
+
+    fn receive_line(&mut self, line: &str) -> std::io::Result<()> {
+        let mismatch = match self.script.pop_front() {
+            Some(MockStep::Exchange { command, response }) if line == command => {
+                self.to_be_read.extend(response.as_bytes());
+                self.to_be_read.push_back(b'\n');
+                None
+            }
+            Some(MockStep::Expect(command)) if line == command => None,
+            Some(MockStep::Exchange { command, .. } | MockStep::Expect(command)) => Some(format!(
+                "MockTlsConnection received an unexpected command {:?}, expected {:?}",
+                line, command
+            )),
+            None if line == "exit" => None,
+            step => Some(format!(
+                "MockTlsConnection received {:?} but the next step is {:?}",
+                line, step
+            )),
+        };
+        if let Some(mismatch) = mismatch {
+            // Panicking again would abort, losing the first panic's message
+            if std::thread::panicking() {
+                return Err(Error::new(ErrorKind::InvalidData, mismatch));
+            }
+            panic!("{}", mismatch);
         }
+        self.play_sends();
+        Ok(())
     }
 }
 
 impl std::io::Read for MockTlsConnection {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        // Implement mock read behavior here
-        let len = std::cmp::min(buf.len(), self.to_be_read.len());
-        buf[..len].copy_from_slice(&self.to_be_read[..len]);
-        self.to_be_read.drain(..len);
+        if self.to_be_read.is_empty() {
+            match self.script.front() {
+                Some(MockStep::ReadError(kind)) => {
+                    let kind = *kind;
+                    self.script.pop_front();
+                    return Err(Error::new(kind, "Simulated read error"));
+                }
+                Some(MockStep::Close) => {
+                    self.script.pop_front();
+                    self.closed = true;
+                }
+                _ => {}
+            }
+            return Ok(0);
+        }
+        let max_read_size = self.options.max_read_size.unwrap_or(usize::MAX);
+        let len = buf.len().min(self.to_be_read.len()).min(max_read_size);
+        for (byte, read) in buf.iter_mut().zip(self.to_be_read.drain(..len)) {
+            *byte = read;
+        }
         Ok(len)
     }
 }
 
 impl std::io::Write for MockTlsConnection {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        // Record data that is being written for inspection
-        self.written_data.extend_from_slice(buf);
+        if let Some(MockStep::WriteError(kind)) = self.script.front() {
+            let kind = *kind;
+            self.script.pop_front();
+            return Err(Error::new(kind, "Simulated write error"));
+        }
+        if self.closed {
+            return Err(Error::new(ErrorKind::BrokenPipe, "Connection closed"));
+        }
+        self.written.extend_from_slice(buf);
+        while let Some(newline) = self.written.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.written.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line[..line.len() - 1]).into_owned();
+            self.receive_line(&line)?;
+        }
         Ok(buf.len())
     }
 
@@ -39,14 +162,121 @@ impl std::io::Write for MockTlsConnection {
 }
 
 impl tls_connection_trait::TlsConnection for MockTlsConnection {
-    fn connect(_address: &AtServerAddr) -> std::io::Result<Self>
+    fn connect_with_config(
+        _address: &AtServerAddr,
+        _config: &TlsConnectionConfig,
+    ) -> std::io::Result<Self>
     where
         Self: Sized,
     {
-        // Return a mock object. Customize as necessary for the test scenario.
-        Ok(Self {
-            written_data: vec![],
-            to_be_read: vec![],
-        })
+        Ok(Self::new(vec![]))
+    }
+}
+
+impl Drop for MockTlsConnection {
+    fn drop(&mut self) {
+        if !std::thread::panicking() && !self.script.is_empty() {
+            panic!(
+                "MockTlsConnection dropped with unplayed steps: {:?}",
+                self.script
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_exchange() {
+        let mut subject = MockTlsConnection::new(vec![
+            MockStep::Send(String::from("@")),
+            MockStep::exchange("from:alice", "data:challenge"),
+        ]);
+        subject.write_all(b"from:alice\n").unwrap();
+        let mut response = String::new();
+        subject.read_to_string(&mut response).unwrap();
+        assert_eq!(response, "@data:challenge\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected command")]
+    fn test_unexpected_command() {
+        let mut subject = MockTlsConnection::new(vec![MockStep::exchange("scan", "data:[]")]);
+        let _ = subject.write_all(b"llookup:phone@alice\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "unplayed steps")]
+    fn test_unplayed_steps() {
+        MockTlsConnection::new(vec![MockStep::exchange("scan", "data:[]")]);
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let mut subject = MockTlsConnection::new_with_options(
+            vec![MockStep::exchange("scan", "data:[]")],
+            MockTlsConnectionOptions {
+                max_read_size: Some(2),
+            },
+        );
+        subject.write_all(b"scan\n").unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(subject.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"da");
+    }
+
+    #[test]
+    fn test_errors_and_close() {
+        let mut subject = MockTlsConnection::new(vec![
+            MockStep::WriteError(ErrorKind::BrokenPipe),
+            MockStep::ReadError(ErrorKind::ConnectionReset),
+            MockStep::Close,
+        ]);
+        let mut buf = [0; 16];
+        assert_eq!(
+            subject.write(b"scan\n").unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+        assert_eq!(
+            subject.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::ConnectionReset
+        );
+        assert_eq!(subject.read(&mut buf).unwrap(), 0);
+        assert_eq!(
+            subject.write(b"scan\n").unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+
+    #[test]
+    fn test_trailing_exit() {
+        let mut subject = MockTlsConnection::new(vec![MockStep::exchange("scan", "data:[]")]);
+        subject.write_all(b"scan\n").unwrap();
+        subject.write_all(b"exit\n").unwrap();
+    }
+
+    /// Sends an unexpected command when dropped, like a client closing its connection.
+    struct ExitOnDrop(MockTlsConnection);
+
+    impl Drop for ExitOnDrop {
+        fn drop(&mut self) {
+            let result = self.0.write_all(b"exit\n");
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_unexpected_command_while_panicking() {
+        let result = std::panic::catch_unwind(|| {
+            let _subject = ExitOnDrop(MockTlsConnection::new(vec![MockStep::Expect(
+                String::from("scan"),
+            )]));
+            panic!("test failed");
+        });
+        assert!(result.is_err());
     }
 }
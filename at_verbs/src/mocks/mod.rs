@@ -1 +1,7 @@
+//! Test doubles for code built on `Verb` implementations. Enabled with the `mock` feature.
+
+#[cfg(feature = "tokio")]
+pub mod mock_async_tls_connection;
 pub mod mock_tls_connection;
+
+pub use mock_tls_connection::{MockStep, MockTlsConnection, MockTlsConnectionOptions};
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use super::prelude::*;
+
+pub struct NoopVerbInputs {
+    /// How long the atServer should wait before replying.
+    pub delay: Duration,
+}
+
+impl NoopVerbInputs {
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+/// The noop verb does nothing, after waiting for the given delay.
+/// It is used to measure round trip latency and to keep idle connections open.
+pub struct NoopVerb;
+
+impl<'a> Verb<'a> for NoopVerb {
+    type Inputs = NoopVerbInputs;
+    type Output = ();
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        Ok(format!("noop:{}", input.delay.as_millis()))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        match response_string.as_str() {
+            "ok" => Ok(()),
+            _ => Err(AtError::UnexpectedResponse(response_string)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_noop_verb() {
+        let mut tls_client = TlsClient::new(Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange("noop:0", "data:ok"),
+            MockStep::exchange("noop:1500", "data:ok"),
+        ])));
+        NoopVerb::execute(&mut tls_client, NoopVerbInputs::new(Duration::ZERO)).unwrap();
+        NoopVerb::execute(
+            &mut tls_client,
+            NoopVerbInputs::new(Duration::from_millis(1500)),
+        )
+        .unwrap();
+
+        let parse = |response: &str| {
+            NoopVerb::parse_response(response.as_bytes(), &NoopVerbInputs::new(Duration::ZERO))
+        };
+        assert!(parse("data:maybe").is_err());
+    }
+}
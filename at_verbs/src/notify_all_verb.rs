@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use at_records::at_key::AtKey;
+use at_sign::AtSign;
+
+use super::prelude::*;
+use crate::notify_list_verb::NotificationOperation;
+
+pub struct NotifyAllVerbInputs<'a> {
+    /// Who to notify.
+    pub recipients: &'a [AtSign],
+    /// The key the notification is about. Who it is shared with is ignored, as each recipient gets their own copy.
+    pub at_key: &'a AtKey,
+    pub operation: NotificationOperation,
+    /// Sent as is, so encrypt it first unless it is public.
+    pub value: Option<&'a str>,
+}
+
+impl<'a> NotifyAllVerbInputs<'a> {
+    /// Notifies `recipients` that `at_key` was updated.
+    pub fn new(recipients: &'a [AtSign], at_key: &'a AtKey) -> Self {
+        Self {
+            recipients,
+            at_key,
+            operation: NotificationOperation::Update,
+            value: None,
+        }
+    }
+
+    pub fn with_operation(mut self, operation: NotificationOperation) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    pub fn with_value(mut self, value: &'a str) -> Self {
+        self.value = Some(value);
+        self
+    }
+}
+
+/// The notify:all verb sends the same notification to several atSigns at once.
+/// It outputs the ID of each recipient's notification, in the order of the recipients, for checking with `notify:status`.
+pub struct NotifyAllVerb;
+
+impl<'a> Verb<'a> for NotifyAllVerb {
+    type Inputs = NotifyAllVerbInputs<'a>;
+    type Output = Vec<(AtSign, String)>;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        let recipients: Vec<String> = input
+            .recipients
+            .iter()
+            .map(|at_sign| at_sign.to_string())
+            .collect();
+        let mut string_buf = format!(
+            "notify:all:{}:{}:{}",
+            input.operation.as_str(),
+            recipients.join(","),
+            input.at_key.lookup_key()
+        );
+        if let Some(value) = input.value {
+            string_buf.push_str(&format!(":{}", value));
+        }
+        Ok(string_buf)
+    }
+
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: {"@bob":"1a","@carol":"2b"}
+        let mut ids: HashMap<String, String> = serde_json::from_str(&response_string)?;
+        input
+            .recipients
+            .iter()
+            .map(|at_sign| match ids.remove(&at_sign.to_string()) {
+                Some(id) => Ok((at_sign.clone(), id)),
+                None => Err(AtError::UnexpectedResponse(response_string.clone())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_notify_all_verb_execute() {
+        let mut tls_client = TlsClient::new(Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange(
+                "notify:all:update:@bob,@carol:phone.wavi@alice:123",
+                r#"@alice@data:{"@carol":"2b","@bob":"1a"}"#,
+            ),
+            MockStep::exchange(
+                "notify:all:delete:@bob:phone.wavi@alice",
+                r#"@alice@data:{}"#,
+            ),
+        ])));
+        let bob = AtSign::from_str("bob").unwrap();
+        let carol = AtSign::from_str("carol").unwrap();
+        let at_key = AtKey::from_str("@bob:phone.wavi@alice").unwrap();
+        let recipients = [bob.clone(), carol.clone()];
+
+        let input = NotifyAllVerbInputs::new(&recipients, &at_key).with_value("123");
+        assert_eq!(
+            NotifyAllVerb::execute(&mut tls_client, input).unwrap(),
+            vec![
+                (bob.clone(), String::from("1a")),
+                (carol, String::from("2b"))
+            ]
+        );
+
+        let recipients = [bob];
+        let input = NotifyAllVerbInputs::new(&recipients, &at_key)
+            .with_operation(NotificationOperation::Delete);
+        assert!(NotifyAllVerb::execute(&mut tls_client, input).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use at_sign::AtSign;
+use serde::Deserialize;
+
+use super::prelude::*;
+
+/// A UTC calendar day, used to limit `notify:list` to a time range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NotificationDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl NotificationDate {
+    pub fn new(year: u16, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+}
+
+impl Display for NotificationDate {
+    /// Formats the date as the atServer expects, e.g. `2024-02-06`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// What a notification says happened to its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationOperation {
+    Update,
+    Delete,
+    /// An operation this client doesn't know about, as the atServer sent it.
+    Other(String),
+}
+
+impl NotificationOperation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            NotificationOperation::Update => "update",
+            NotificationOperation::Delete => "delete",
+            NotificationOperation::Other(operation) => operation,
+        }
+    }
+}
+
+impl From<&str> for NotificationOperation {
+    fn from(operation: &str) -> Self {
+        match operation {
+            "update" => NotificationOperation::Update,
+            "delete" => NotificationOperation::Delete,
+            _ => NotificationOperation::Other(operation.to_owned()),
+        }
+    }
+}
+
+/// A notification received by the atServer's owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub id: String,
+    pub from: AtSign,
+    pub to: AtSign,
+    /// The key the notification is about, as the sender wrote it, e.g. `@bob:phone.wavi@alice`.
+    pub key: String,
+    pub value: Option<String>,
+    pub operation: NotificationOperation,
+    /// When the atServer received the notification, in milliseconds since the Unix epoch.
+    pub epoch_millis: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationJson {
+    id: String,
+    from: String,
+    to: String,
+    key: String,
+    #[serde(default)]
+    value: Option<String>,
+    operation: String,
+    epoch_millis: u64,
+}
+
+impl TryFrom<NotificationJson> for Notification {
+    type Error = AtError;
+
+    fn try_from(json: NotificationJson) -> Result<Self> {
+        let at_sign = |at_sign: &str| {
+            AtSign::from_str(at_sign).map_err(|_| AtError::UnexpectedResponse(at_sign.to_owned()))
+        };
+        Ok(Notification {
+            from: at_sign(&json.from)?,
+            to: at_sign(&json.to)?,
+            id: json.id,
+            key: json.key,
+            value: json.value,
+            operation: NotificationOperation::from(json.operation.as_str()),
+            epoch_millis: json.epoch_millis,
+        })
+    }
+}
+
+/// Which received notifications to list. By default every one is listed.
+#[derive(Debug, Clone, Default)]
+pub struct NotifyListVerbInputs {
+    /// Only notifications whose key matches this regex.
+    pub regex: Option<String>,
+    /// Only notifications received on or after this day.
+    pub from_date: Option<NotificationDate>,
+    /// Only notifications received on or before this day.
+    pub to_date: Option<NotificationDate>,
+}
+
+impl NotifyListVerbInputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_regex(mut self, regex: &str) -> Self {
+        self.regex = Some(regex.to_owned());
+        self
+    }
+
+    pub fn with_from_date(mut self, from_date: NotificationDate) -> Self {
+        self.from_date = Some(from_date);
+        self
+    }
+
+    pub fn with_to_date(mut self, to_date: NotificationDate) -> Self {
+        self.to_date = Some(to_date);
+        self
+    }
+}
+
+/// The notify:list verb lists the notifications the atServer has received for its owner.
+pub struct NotifyListVerb;
+
+impl<'a> Verb<'a> for NotifyListVerb {
+    type Inputs = NotifyListVerbInputs;
+    type Output = Vec<Notification>;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        let mut string_buf = String::from("notify:list");
+        // The dates are positional, so an end date needs a start date before it
+        if input.from_date.is_some() || input.to_date.is_some() {
+            let from_date = input.from_date.unwrap_or(NotificationDate::new(1970, 1, 1));
+            string_buf.push_str(&format!(":{}", from_date));
+        }
+        if let Some(to_date) = input.to_date {
+            string_buf.push_str(&format!(":{}", to_date));
+        }
+        if let Some(regex) = &input.regex {
+            string_buf.push_str(&format!(":{}", regex));
+        }
+        Ok(string_buf)
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: [{"id":"...","from":"@alice","to":"@bob","key":"@bob:phone.wavi@alice","value":"123","operation":"update","epochMillis":1707214530123}]
+        // or null if there are none
+        let notifications: Option<Vec<NotificationJson>> = serde_json::from_str(&response_string)?;
+        notifications
+            .unwrap_or_default()
+            .into_iter()
+            .map(Notification::try_from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_list_verb_format() {
+        let format = |input: NotifyListVerbInputs| NotifyListVerb::format_command(&input).unwrap();
+        assert_eq!(format(NotifyListVerbInputs::new()), "notify:list");
+        assert_eq!(
+            format(NotifyListVerbInputs::new().with_regex("wavi")),
+            "notify:list:wavi"
+        );
+        assert_eq!(
+            format(
+                NotifyListVerbInputs::new()
+                    .with_from_date(NotificationDate::new(2024, 2, 6))
+                    .with_to_date(NotificationDate::new(2024, 12, 31))
+                    .with_regex("wavi")
+            ),
+            "notify:list:2024-02-06:2024-12-31:wavi"
+        );
+        assert_eq!(
+            format(NotifyListVerbInputs::new().with_to_date(NotificationDate::new(2024, 2, 6))),
+            "notify:list:1970-01-01:2024-02-06"
+        );
+    }
+
+    #[test]
+    fn test_notify_list_verb_parse() {
+        let parse = |response: &str| {
+            NotifyListVerb::parse_response(response.as_bytes(), &NotifyListVerbInputs::new())
+        };
+        let notifications = parse(
+            r#"data:[{"id":"1a","from":"@alice","to":"@bob","key":"@bob:phone.wavi@alice","value":"123","operation":"update","epochMillis":1707214530123,"messageType":"MessageType.key"},{"id":"2b","from":"@alice","to":"@bob","key":"@bob:phone.wavi@alice","value":null,"operation":"delete","epochMillis":1707214530124}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            notifications[0],
+            Notification {
+                id: String::from("1a"),
+                from: AtSign::from_str("alice").unwrap(),
+                to: AtSign::from_str("bob").unwrap(),
+                key: String::from("@bob:phone.wavi@alice"),
+                value: Some(String::from("123")),
+                operation: NotificationOperation::Update,
+                epoch_millis: 1_707_214_530_123,
+            }
+        );
+        assert_eq!(notifications[1].operation, NotificationOperation::Delete);
+        assert_eq!(notifications[1].value, None);
+
+        assert!(parse("data:null").unwrap().is_empty());
+        assert!(parse(r#"data:[{"id":"1a"}]"#).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use super::prelude::*;
+
+pub struct NotifyRemoveVerbInputs<'a> {
+    /// The ID of the received notification, from `notify:list`.
+    pub id: &'a str,
+}
+
+impl<'a> NotifyRemoveVerbInputs<'a> {
+    pub fn new(id: &'a str) -> Self {
+        Self { id }
+    }
+}
+
+/// The notify:remove verb deletes a received notification from the atServer.
+/// Removing a notification which doesn't exist succeeds.
+pub struct NotifyRemoveVerb;
+
+impl<'a> Verb<'a> for NotifyRemoveVerb {
+    type Inputs = NotifyRemoveVerbInputs<'a>;
+    type Output = ();
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        Ok(format!("notify:remove:{}", input.id))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        Self::parse_server_response(response, "data")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_notify_remove_verb_execute() {
+        let mut tls_client =
+            TlsClient::new(Box::new(MockTlsConnection::new(vec![MockStep::exchange(
+                "notify:remove:1a",
+                "@bob@data:success",
+            )])));
+        NotifyRemoveVerb::execute(&mut tls_client, NotifyRemoveVerbInputs::new("1a")).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use super::prelude::*;
+
+/// How far the atServer has got delivering a notification we sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationStatus {
+    /// Waiting to be sent to the recipient's atServer.
+    Queued,
+    /// Received by the recipient's atServer.
+    Delivered,
+    /// Sending failed, and will be retried.
+    Undelivered,
+    /// Sending failed and won't be retried.
+    Errored,
+    /// The atServer no longer knows about the notification, e.g. because it expired or the ID is wrong.
+    Expired,
+}
+
+pub struct NotifyStatusVerbInputs<'a> {
+    /// The ID the atServer gave the notification when it was sent.
+    pub id: &'a str,
+}
+
+impl<'a> NotifyStatusVerbInputs<'a> {
+    pub fn new(id: &'a str) -> Self {
+        Self { id }
+    }
+}
+
+/// The notify:status verb asks the atServer about the delivery of a notification we sent.
+pub struct NotifyStatusVerb;
+
+impl<'a> Verb<'a> for NotifyStatusVerb {
+    type Inputs = NotifyStatusVerbInputs<'a>;
+    type Output = NotificationStatus;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        Ok(format!("notify:status:{}", input.id))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        match response_string.as_str() {
+            "queued" => Ok(NotificationStatus::Queued),
+            "delivered" => Ok(NotificationStatus::Delivered),
+            "undelivered" => Ok(NotificationStatus::Undelivered),
+            "errored" => Ok(NotificationStatus::Errored),
+            "expired" => Ok(NotificationStatus::Expired),
+            _ => Err(AtError::UnexpectedResponse(response_string)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_notify_status_verb_execute() {
+        let mut tls_client = TlsClient::new(Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange("notify:status:1a", "@alice@data:delivered"),
+            MockStep::exchange("notify:status:2b", "@alice@data:expired"),
+        ])));
+        let status = NotifyStatusVerb::execute(&mut tls_client, NotifyStatusVerbInputs::new("1a"));
+        assert_eq!(status.unwrap(), NotificationStatus::Delivered);
+        let status = NotifyStatusVerb::execute(&mut tls_client, NotifyStatusVerbInputs::new("2b"));
+        assert_eq!(status.unwrap(), NotificationStatus::Expired);
+
+        let parse = |response: &str| {
+            NotifyStatusVerb::parse_response(
+                response.as_bytes(),
+                &NotifyStatusVerbInputs::new("1a"),
+            )
+        };
+        assert!(parse("data:lost").is_err());
+    }
+}
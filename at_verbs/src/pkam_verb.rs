@@ -30,28 +30,27 @@ impl<'a> Verb<'a> for PkamVerb {
     type Inputs = PkamVerbInputs<'a>;
     type Output = ();
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         debug!("Signing challenge");
 
         let signed_challenge = input
             .at_chops
             .sign_challenge(input.challenge)
-            .map_err(|e| AtError::UnknownAtClientException(e.to_string()))?;
+            .map_err(|e| AtError::CryptoError(e.into()))?;
 
-        let data_to_send = format!("pkam:{}\n", signed_challenge);
+        let data_to_send = format!("pkam:{}", signed_challenge);
         debug!("Sending challenge response: {}", &data_to_send);
-        tls_client.send_data(data_to_send)?;
+        Ok(data_to_send)
+    }
 
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
         debug!("Received: {}", &response_string);
 
         if response_string.contains("success") {
             Ok(())
         } else {
-            Err(AtError::UnknownAtClientException(String::from(
-                "Server did not respond with success message after pkam verb.",
-            )))
+            Err(AtError::UnexpectedResponse(response_string))
         }
     }
 }
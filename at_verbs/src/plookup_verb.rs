@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use at_records::{
     at_key::AtKey,
     at_record::{AtRecord, AtValue},
@@ -12,6 +14,9 @@ pub struct PlookupVerbInputs<'a> {
 
     /// The type of data to request from the server.
     return_type: PlookupReturnType,
+
+    /// Fetch the key from its owner's atServer even if our atServer has a cached copy.
+    bypass_cache: bool,
 }
 
 impl<'a> PlookupVerbInputs<'a> {
@@ -19,8 +24,14 @@ impl<'a> PlookupVerbInputs<'a> {
         Self {
             at_key,
             return_type,
+            bypass_cache: false,
         }
     }
+
+    pub fn with_bypass_cache(mut self, bypass_cache: bool) -> Self {
+        self.bypass_cache = bypass_cache;
+        self
+    }
 }
 
 pub enum PlookupReturnType {
@@ -46,37 +57,35 @@ impl<'a> Verb<'a> for PlookupVerb {
     type Inputs = PlookupVerbInputs<'a>;
     type Output = PlookupVerbOutput;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
-        // TODO: Implement bypassCache
-
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         let mut string_buf = String::from("plookup:");
+        if input.bypass_cache {
+            string_buf.push_str("bypassCache:true:");
+        }
         match input.return_type {
             PlookupReturnType::Data => {}
             PlookupReturnType::Meta => string_buf.push_str("meta:"),
             PlookupReturnType::All => string_buf.push_str("all:"),
         }
 
-        let formatted_at_key = format!(
-            "{record_id}{namespace}{owner}",
-            record_id = &input.at_key.record_id,
-            namespace = match input.at_key.namespace.as_ref() {
-                Some(namespace) => format!(".{}", namespace),
-                None => String::from(""),
-            },
-            owner = &input.at_key.owner.get_at_sign_with_prefix()
-        );
-        string_buf.push_str(formatted_at_key.as_str());
+        string_buf.push_str(&input.at_key.lookup_key());
 
-        tls_client.send_data(string_buf)?;
+        Ok(string_buf)
+    }
 
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string =
+            Self::parse_server_response(response, "data").map_err(|e| e.with_key(input.at_key))?;
 
         // TODO: Parse the response_string into the appropriate type.
         match input.return_type {
             PlookupReturnType::Data => Ok(PlookupVerbOutput::Data(AtValue::Text(response_string))),
-            PlookupReturnType::Meta => todo!(),
-            PlookupReturnType::All => todo!(),
+            PlookupReturnType::Meta => Ok(PlookupVerbOutput::Meta(RecordMetadata::from_str(
+                &response_string,
+            )?)),
+            PlookupReturnType::All => Err(AtError::Unsupported(String::from(
+                "Parsing plookup records",
+            ))),
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+use super::prelude::*;
+
+/// What another atSign's atServer responds to the from verb with when we aren't its owner:
+/// `proof:<key>:<value>`, e.g. `proof:_70a8b1c4@alice:5f2e9d`.
+///
+/// To prove we are @alice, we publish `public:<key>` with the value on our own atServer before sending the pol verb.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolChallenge {
+    /// The key to publish, without the `public:` prefix, e.g. `_70a8b1c4@alice`.
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for PolChallenge {
+    type Err = AtError;
+
+    fn from_str(challenge: &str) -> Result<Self> {
+        challenge
+            .strip_prefix("proof:")
+            .and_then(|proof| proof.rsplit_once(':'))
+            .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+            .map(|(key, value)| Self {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            })
+            .ok_or_else(|| AtError::UnexpectedResponse(challenge.to_owned()))
+    }
+}
+
+/// The pol (proof of life) verb follows the from verb when connecting to someone else's atServer.
+/// Their atServer looks up the proof key we published on our atServer and, if it has the expected value,
+/// lets us connect as our atSign, e.g. to look up keys shared with us.
+pub struct PolVerb;
+
+impl<'a> Verb<'a> for PolVerb {
+    type Inputs = ();
+    type Output = ();
+
+    fn format_command(_input: &Self::Inputs) -> Result<String> {
+        Ok(String::from("pol"))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        debug!("pol response: {}", response_string);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at_errors::ServerErrorCode;
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_parse_challenge() {
+        let challenge = PolChallenge::from_str("proof:_70a8b1c4@alice:5f2e9d").unwrap();
+        assert_eq!(challenge.key, "_70a8b1c4@alice");
+        assert_eq!(challenge.value, "5f2e9d");
+        assert!(PolChallenge::from_str("_70a8b1c4@alice:5f2e9d").is_err());
+        assert!(PolChallenge::from_str("proof:_70a8b1c4@alice:").is_err());
+    }
+
+    #[test]
+    fn test_pol_verb_execute() {
+        let connection = Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange("pol", "@alice@data:success"),
+            MockStep::exchange("pol", "error:AT0401-Client authentication failed"),
+        ]));
+        let mut tls_client = TlsClient::new(connection);
+
+        PolVerb::execute(&mut tls_client, ()).unwrap();
+        let error = PolVerb::execute(&mut tls_client, ()).unwrap_err();
+        assert_eq!(
+            error.server_code(),
+            Some(&ServerErrorCode::ClientAuthenticationFailed)
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use std::str::FromStr;
 
-use at_records::at_key::AtKey;
+use at_records::{
+    at_key::{AtKey, AtKeyError},
+    at_key_filter::AtKeyFilter,
+};
 use at_sign::AtSign;
 use log::warn;
 use serde::Deserialize;
@@ -11,6 +14,8 @@ pub struct ScanVerbInputs {
     pub show_hidden: bool,
     pub for_at_sign: Option<AtSign>,
     pub regex: Option<String>,
+    /// Only return this page of the keys, numbered from 1. The atServer decides how many keys are on a page.
+    pub page: Option<usize>,
 }
 
 impl ScanVerbInputs {
@@ -19,54 +24,134 @@ impl ScanVerbInputs {
             show_hidden,
             for_at_sign,
             regex,
+            page: None,
         }
     }
+
+    pub fn with_page(mut self, page: usize) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// Only asks for keys which could match `filter`. The results still need checking with `AtKeyFilter::matches`.
+    pub fn new_with_filter(
+        show_hidden: bool,
+        for_at_sign: Option<AtSign>,
+        filter: &AtKeyFilter,
+    ) -> Self {
+        Self::new(show_hidden, for_at_sign, Some(filter.to_regex()))
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct AtIdListJson(Vec<String>);
 
+/// The keys returned by the scan verb.
+#[derive(Debug, Default)]
+pub struct ScanVerbOutput {
+    pub keys: Vec<AtKey>,
+    /// The keys which aren't valid `AtKey`s, with why they couldn't be parsed.
+    pub invalid_keys: Vec<(String, AtKeyError)>,
+}
+
+impl ScanVerbOutput {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.invalid_keys.is_empty()
+    }
+}
+
 /// The scan verb is used to see the AtId's (old: keys) in an atSign’s atServer.
 pub struct ScanVerb;
 
 impl<'a> Verb<'a> for ScanVerb {
     type Inputs = ScanVerbInputs;
-    type Output = Vec<AtKey>;
+    type Output = ScanVerbOutput;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         let mut string_buf = String::from("scan");
         if input.show_hidden {
             string_buf.push_str(":showhidden:true");
         }
-        if let Some(at_sign) = input.for_at_sign {
+        if let Some(at_sign) = &input.for_at_sign {
             string_buf.push_str(&format!(":forAtSign:{}", at_sign));
         }
-        if let Some(regex) = input.regex {
+        if let Some(page) = input.page {
+            string_buf.push_str(&format!(":page:{}", page));
+        }
+        if let Some(regex) = &input.regex {
             string_buf.push_str(&format!(" {}", regex));
         }
-        tls_client.send_data(string_buf)?;
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+        Ok(string_buf)
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
         // Example response: ["at_id_1", "at_id_2", "at_id_3"]
-        let at_ids_json: AtIdListJson = serde_json::from_str(&response_string)
-            .map_err(|e| AtError::UnknownAtClientException(e.to_string()))?;
-        let at_ids: Vec<AtKey> = at_ids_json
-            .0
-            .iter()
-            // Filtering out any invalid AtKeys as I don't want the entire scan to fail if one AtKey is invalid.
-            .filter_map(|at_id| {
-                AtKey::from_str(at_id)
-                    .map_err(|e| {
-                        warn!(
-                            "Failed to convert {} to an AtKey with error {}",
-                            at_id,
-                            e.to_owned()
-                        );
-                        AtError::UnknownAtClientException(e.to_string())
-                    })
-                    .ok()
-            })
-            .collect();
-        Ok(at_ids)
+        let at_ids_json: AtIdListJson = serde_json::from_str(&response_string)?;
+        // Invalid AtKeys are returned separately as I don't want the entire scan to fail if one AtKey is invalid.
+        let mut output = ScanVerbOutput::default();
+        for at_id in at_ids_json.0 {
+            match AtKey::from_str(&at_id) {
+                Ok(at_key) => output.keys.push(at_key),
+                Err(e) => {
+                    warn!("Failed to convert {} to an AtKey with error {}", at_id, e);
+                    output.invalid_keys.push((at_id, e));
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_scan_verb_execute() {
+        let connection = Box::new(MockTlsConnection::new(vec![MockStep::exchange(
+            "scan:showhidden:true .*wavi",
+            r#"data:["public:phone.wavi@alice","not a key"]"#,
+        )]));
+        let mut tls_client = TlsClient::new(connection);
+
+        let input = ScanVerbInputs::new(true, None, Some(String::from(".*wavi")));
+        let result = ScanVerb::execute(&mut tls_client, input).unwrap();
+
+        let keys: Vec<String> = result.keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(keys, vec![String::from("public:phone.wavi@alice")]);
+        assert_eq!(result.invalid_keys.len(), 1);
+        assert_eq!(result.invalid_keys[0].0, "not a key");
+    }
+
+    #[test]
+    fn test_scan_verb_format_all_options() {
+        let input = ScanVerbInputs::new(
+            true,
+            Some(AtSign::new(String::from("bob"))),
+            Some(String::from(".*wavi")),
+        )
+        .with_page(2);
+        assert_eq!(
+            ScanVerb::format_command(&input).unwrap(),
+            "scan:showhidden:true:forAtSign:@bob:page:2 .*wavi"
+        );
+    }
+
+    #[test]
+    fn test_scan_verb_connection_reset() {
+        let connection = Box::new(MockTlsConnection::new(vec![
+            MockStep::Expect(String::from("scan")),
+            MockStep::ReadError(ErrorKind::ConnectionReset),
+        ]));
+        let mut tls_client = TlsClient::new(connection);
+
+        let result = ScanVerb::execute(&mut tls_client, ScanVerbInputs::new(false, None, None));
+        assert!(matches!(result, Err(AtError::IoError(_))));
     }
 }
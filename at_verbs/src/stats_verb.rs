@@ -0,0 +1,66 @@
+use serde::Deserialize;
+
+use super::prelude::*;
+
+/// The ID of the stat holding the latest commit ID.
+const LAST_COMMIT_ID: &str = "3";
+
+#[derive(Debug, Deserialize)]
+struct StatJson {
+    id: String,
+    value: String,
+}
+
+/// The stats verb reports statistics about the atServer.
+/// Only the latest commit ID is asked for, which tells the client whether there are changes to sync.
+pub struct StatsVerb;
+
+impl<'a> Verb<'a> for StatsVerb {
+    type Inputs = ();
+    /// The latest commit ID, or `None` if nothing has been committed yet.
+    type Output = Option<u64>;
+
+    fn format_command(_input: &Self::Inputs) -> Result<String> {
+        Ok(format!("stats:{}", LAST_COMMIT_ID))
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: [{"id":"3","name":"lastCommitID","value":"15"}]
+        let stats: Vec<StatJson> = serde_json::from_str(&response_string)?;
+        let stat = stats
+            .into_iter()
+            .find(|stat| stat.id == LAST_COMMIT_ID)
+            .ok_or_else(|| AtError::UnexpectedResponse(response_string.clone()))?;
+        match stat.value.as_str() {
+            "null" | "-1" => Ok(None),
+            value => value
+                .parse()
+                .map(Some)
+                .map_err(|_| AtError::UnexpectedResponse(response_string)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_verb_parse() {
+        let parse = |response: &str| StatsVerb::parse_response(response.as_bytes(), &());
+        assert_eq!(
+            parse(r#"data:[{"id":"3","name":"lastCommitID","value":"15"}]"#).unwrap(),
+            Some(15)
+        );
+        assert_eq!(
+            parse(r#"data:[{"id":"3","name":"lastCommitID","value":"-1"}]"#).unwrap(),
+            None
+        );
+        assert!(parse(r#"data:[{"id":"3","name":"lastCommitID","value":"many"}]"#).is_err());
+        assert!(
+            parse(r#"data:[{"id":"1","name":"activeInboundConnectionCount","value":"2"}]"#)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Deserializer};
+
+use super::prelude::*;
+
+pub struct SyncVerbInputs {
+    /// Only changes after this commit ID, or every change if `None`.
+    pub from_commit_id: Option<u64>,
+    /// At most this many changes. The atServer decides if `None`.
+    pub limit: Option<usize>,
+}
+
+impl SyncVerbInputs {
+    pub fn new(from_commit_id: Option<u64>) -> Self {
+        Self {
+            from_commit_id,
+            limit: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// What a commit did to its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOperation {
+    /// The key was created or its value changed (`+` or `*`).
+    Update,
+    /// Only the metadata changed (`#`).
+    UpdateMeta,
+    /// The key was deleted (`-`).
+    Delete,
+}
+
+impl<'de> Deserialize<'de> for SyncOperation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "+" | "*" => Ok(SyncOperation::Update),
+            "#" => Ok(SyncOperation::UpdateMeta),
+            "-" => Ok(SyncOperation::Delete),
+            other => Err(serde::de::Error::custom(format!(
+                "unknown sync operation: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// One commit from the atServer's commit log.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncEntry {
+    /// The key as the atServer stores it, e.g. `public:phone.wavi@alice`.
+    #[serde(rename = "atKey")]
+    pub key: String,
+    /// The value after the commit. Deletions have none.
+    #[serde(default)]
+    pub value: Option<String>,
+    pub commit_id: u64,
+    pub operation: SyncOperation,
+}
+
+/// The sync verb fetches the changes committed to the atServer after a commit ID, oldest first,
+/// so a client can bring its local copy of the keys up to date.
+pub struct SyncVerb;
+
+impl<'a> Verb<'a> for SyncVerb {
+    type Inputs = SyncVerbInputs;
+    type Output = Vec<SyncEntry>;
+
+    fn format_command(input: &Self::Inputs) -> Result<String> {
+        let mut string_buf = String::from("sync:from:");
+        match input.from_commit_id {
+            Some(commit_id) => string_buf.push_str(&commit_id.to_string()),
+            None => string_buf.push_str("-1"),
+        }
+        if let Some(limit) = input.limit {
+            string_buf.push_str(&format!(":limit:{}", limit));
+        }
+        Ok(string_buf)
+    }
+
+    fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string = Self::parse_server_response(response, "data")?;
+        // Example response: [{"atKey":"public:phone@alice","value":"123","metadata":{...},"commitId":5,"operation":"+"}]
+        if response_string == "null" {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&response_string)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use at_tls::TlsClient;
+
+    use super::*;
+    use crate::mocks::{MockStep, MockTlsConnection};
+
+    #[test]
+    fn test_sync_verb_execute() {
+        let connection = Box::new(MockTlsConnection::new(vec![
+            MockStep::exchange(
+                "sync:from:4:limit:10",
+                r#"@alice@data:[{"atKey":"public:phone@alice","value":"123","metadata":{"ttr":-1},"commitId":5,"operation":"+"},{"atKey":"email@alice","commitId":6,"operation":"-"}]"#,
+            ),
+            MockStep::exchange("sync:from:-1", "@alice@data:[]"),
+        ]));
+        let mut tls_client = TlsClient::new(connection);
+
+        let entries =
+            SyncVerb::execute(&mut tls_client, SyncVerbInputs::new(Some(4)).with_limit(10))
+                .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                SyncEntry {
+                    key: String::from("public:phone@alice"),
+                    value: Some(String::from("123")),
+                    commit_id: 5,
+                    operation: SyncOperation::Update,
+                },
+                SyncEntry {
+                    key: String::from("email@alice"),
+                    value: None,
+                    commit_id: 6,
+                    operation: SyncOperation::Delete,
+                },
+            ]
+        );
+
+        let entries = SyncVerb::execute(&mut tls_client, SyncVerbInputs::new(None)).unwrap();
+        assert!(entries.is_empty());
+    }
+}
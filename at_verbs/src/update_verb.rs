@@ -68,10 +68,10 @@ impl<'a> Verb<'a> for UpdateVerb {
     type Inputs = UpdateVerbInputs<'a>;
     type Output = String;
 
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+    fn format_command(input: &Self::Inputs) -> Result<String> {
         let mut string_buf = String::from("update:");
 
-        if let Some(update_options) = input.update_options {
+        if let Some(update_options) = &input.update_options {
             if let Some(ttl) = update_options.ttl {
                 string_buf.push_str(format!("ttl:{}:", ttl).as_str());
             }
@@ -89,39 +89,23 @@ impl<'a> Verb<'a> for UpdateVerb {
             }
         }
 
-        // Private and internal are not supported. Throw error?
-        let visibility = match &input.at_key.visibility_scope {
-            at_records::at_key::Visibility::Private => String::from(""),
-            at_records::at_key::Visibility::Internal => String::from("_"),
-            at_records::at_key::Visibility::Public => String::from("public:"),
-            at_records::at_key::Visibility::Shared(shared_with) => {
-                format!("{}:", shared_with.get_at_sign_with_prefix())
-            }
-        };
-
-        let formatted_at_key = format!(
-            "{visibility}{record_id}{namespace}{owner}",
-            visibility = visibility,
-            record_id = &input.at_key.record_id,
-            namespace = match input.at_key.namespace.as_ref() {
-                Some(namespace) => format!(".{}", namespace),
-                None => String::from(""),
-            },
-            owner = &input.at_key.owner.get_at_sign_with_prefix()
-        );
-        string_buf.push_str(formatted_at_key.as_str());
+        string_buf.push_str(&input.at_key.full_key());
 
         let value = match input.value {
             AtValue::Text(text) => text,
-            AtValue::Binary(_) => todo!(),
+            AtValue::Binary(_) => {
+                return Err(AtError::Unsupported(String::from("Updating binary values")))
+            }
         };
 
         string_buf.push_str(format!(" {}", value).as_str());
 
-        tls_client.send_data(string_buf)?;
+        Ok(string_buf)
+    }
 
-        let response_data = tls_client.read_data()?;
-        let response_string = Self::parse_server_response(&response_data, "data")?;
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output> {
+        let response_string =
+            Self::parse_server_response(response, "data").map_err(|e| e.with_key(input.at_key))?;
 
         Ok(response_string)
     }
@@ -8,8 +8,29 @@ pub trait Verb<'a> {
     type Inputs: 'a;
     type Output;
 
+    /// Format the command sent to the atServer, without the trailing newline.
+    fn format_command(input: &Self::Inputs) -> Result<String>;
+
+    /// Parse the raw response line from the atServer into the verb's output.
+    fn parse_response(response: &[u8], input: &Self::Inputs) -> Result<Self::Output>;
+
+    /// Whether the atServer replies to the command.
+    ///
+    /// If not, `execute` only sends the command and parses an empty response.
+    fn expects_response() -> bool {
+        true
+    }
+
     /// Execute the verb with the given inputs and tls_client.
-    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output>;
+    fn execute(tls_client: &mut TlsClient, input: Self::Inputs) -> Result<Self::Output> {
+        let command = Self::format_command(&input)?;
+        tls_client.send_data(command)?;
+        if !Self::expects_response() {
+            return Self::parse_response(&[], &input);
+        }
+        let response_data = tls_client.read_data()?;
+        Self::parse_response(&response_data, &input)
+    }
 
     /// Parse the response from the atServer into a string checking for exception codes.
     /// Also trims the prefix from the response.
@@ -18,39 +39,44 @@ pub trait Verb<'a> {
         // Parse the response into a string
         let response = std::str::from_utf8(response).map_err(|e| {
             error!("Failed to parse server response. Not valid UTF-8");
-            AtError::UnknownAtClientException(e.to_string())
+            AtError::Utf8Error(e)
         })?;
 
+        // The atServer's prompt (`@` or `@<atSign>@`) may precede the response
+        let response = strip_prompt(response);
+
         // Check that it doesn't contain error codes
-        if response.starts_with("error") {
-            let code = response
-                .split_once(':')
-                .ok_or(AtError::UnknownAtClientException(String::from(
-                    "Unexpected formatting of error message from server",
-                )))?
-                .1
-                .split_at(6)
-                .0;
-            return Err(AtError::from_code(code));
+        if let Some(error) = response.strip_prefix("error:") {
+            return Err(AtError::from_server_error(error));
         }
 
-        // Check that it starts with the expected prefix
-        if !response.starts_with(prefix) {
-            return Err(AtError::UnknownAtClientException(format!(
-                "Unexpected response from server: {}",
-                response
-            )));
+        // Check that it starts with the expected prefix, then trim the prefix and ":" from the response
+        match response
+            .strip_prefix(prefix)
+            .and_then(|response| response.strip_prefix(':'))
+        {
+            Some(response) => Ok(response.trim().to_string()),
+            None => Err(AtError::UnexpectedResponse(response.trim().to_owned())),
         }
+    }
+}
 
-        // Trim the prefix and ":" from the response
-        let (_, response) = response.split_at(prefix.len() + 1);
-
-        Ok(response.trim().to_string())
+/// Removes a leading `@` or `@<atSign>@` prompt from a response line.
+fn strip_prompt(response: &str) -> &str {
+    let Some(rest) = response.strip_prefix('@') else {
+        return response;
+    };
+    let end_of_verb = rest.find(':').unwrap_or(rest.len());
+    match rest[..end_of_verb].find('@') {
+        Some(at_sign_end) => &rest[at_sign_end + 1..],
+        None => rest,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use at_errors::ServerErrorCode;
+
     use super::*;
 
     struct TestVerb;
@@ -59,8 +85,13 @@ mod tests {
         type Inputs = ();
         type Output = ();
 
-        fn execute(_tls_client: &mut TlsClient, _input: Self::Inputs) -> Result<Self::Output> {
-            unimplemented!() // Not needed for this test
+        fn format_command(_input: &Self::Inputs) -> Result<String> {
+            Ok(String::from("test"))
+        }
+
+        fn parse_response(response: &[u8], _input: &Self::Inputs) -> Result<Self::Output> {
+            Self::parse_server_response(response, "data")?;
+            Ok(())
         }
     }
 
@@ -72,24 +103,44 @@ mod tests {
         assert_eq!(result.unwrap(), "OK");
     }
 
+    #[test]
+    fn test_parse_server_response_strips_prompt() {
+        let result = TestVerb::parse_server_response(b"@data:OK", "data");
+        assert_eq!(result.unwrap(), "OK");
+        let result = TestVerb::parse_server_response(b"@alice@data:OK", "data");
+        assert_eq!(result.unwrap(), "OK");
+        let result = TestVerb::parse_server_response(b"@alice@error:AT0015-key not found", "data");
+        assert_eq!(
+            result.err().unwrap().server_code(),
+            Some(&ServerErrorCode::KeyNotFound)
+        );
+    }
+
     #[test]
     fn test_parse_server_response_invalid_utf8() {
         let response = &[0xf0, 0x28, 0x8c, 0xbc]; // Invalid UTF-8 sequence
         let result = TestVerb::parse_server_response(response, "data");
-        assert!(result.is_err());
-        assert_eq!(
-            result.err().unwrap(),
-            AtError::UnknownAtClientException(String::from(
-                "invalid utf-8 sequence of 1 bytes from index 0",
-            ))
-        );
+        assert!(matches!(result, Err(AtError::Utf8Error(_))));
     }
 
     #[test]
     fn test_parse_server_response_with_error_code() {
         let response = b"error:AT0001: Error Message";
         let result = TestVerb::parse_server_response(response, "data");
-        assert!(result.is_err());
-        assert_eq!(result.err().unwrap(), AtError::ServerException); // Assuming AT0001 maps to ServerException
+        match result {
+            Err(AtError::Server(server_error)) => {
+                assert_eq!(server_error.code, ServerErrorCode::ServerException);
+                assert_eq!(server_error.message, "Error Message");
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_server_response_unexpected_prefix() {
+        let result = TestVerb::parse_server_response(b"@alice@ok\n", "data");
+        assert!(matches!(result, Err(AtError::UnexpectedResponse(response)) if response == "ok"));
+        let result = TestVerb::parse_server_response(b"data", "data");
+        assert!(matches!(result, Err(AtError::UnexpectedResponse(_))));
     }
 }
@@ -42,7 +42,7 @@ fn main() {
     let secrets = AtSecrets::from_file(&contents).expect("Failed to create secrets");
 
     // Create the atSign of the client
-    let at_sign = AtSign::new(cli.at_sign);
+    let at_sign = AtSign::from_str(&cli.at_sign).expect("Invalid atSign");
 
     let mut at_client = AtClient::init(secrets, at_sign).expect("Failed to create AtClient");
     let at_key = AtKey::from_str(&cli.at_key).expect("Invalid at_key");
@@ -1,6 +1,6 @@
 extern crate env_logger;
 
-use std::{fs::File, io::Read};
+use std::{fs::File, io::Read, str::FromStr};
 
 use at_rust::at_client::AtClient;
 use at_secrets::AtSecrets;
@@ -33,7 +33,7 @@ fn main() {
     let secrets = AtSecrets::from_file(&contents).expect("Failed to create secrets");
 
     // Create the atSign of the client
-    let at_sign = AtSign::new(cli.at_sign);
+    let at_sign = AtSign::from_str(&cli.at_sign).expect("Invalid atSign");
 
     let mut at_client = AtClient::init(secrets, at_sign).expect("Failed to create AtClient");
     let result = at_client.scan(true).expect("Failed to scan");
@@ -0,0 +1,437 @@
+use std::time::{Duration, Instant};
+
+use at_chops::AtChops;
+use at_errors::ServerErrorCode;
+use at_records::{at_key::AtKey, at_key_filter::AtKeyFilter, at_record::AtValue};
+use at_secrets::AtSecrets;
+use at_sign::AtSign;
+use at_tls::{
+    async_tls_client::AsyncTlsClient, at_server_addr::AtServerAddr,
+    tokio_rustls_connection::TokioRustlsConnection,
+};
+use at_verbs::{
+    async_verb_trait::AsyncVerb,
+    config_verb::{ConfigList, ConfigOperation, ConfigVerb, ConfigVerbInputs},
+    exit_verb::ExitVerb,
+    from_verb::{FromVerb, FromVerbInputs},
+    info_verb::{InfoVerb, ServerInfo},
+    llookup_verb::{LlookupReturnType, LlookupVerb, LlookupVerbInputs},
+    lookup_verb::{LookupReturnType, LookupVerb, LookupVerbInputs, LookupVerbOutput},
+    noop_verb::{NoopVerb, NoopVerbInputs},
+    pkam_verb::{PkamVerb, PkamVerbInputs},
+    plookup_verb::{PlookupReturnType, PlookupVerb, PlookupVerbInputs},
+    scan_verb::{ScanVerb, ScanVerbInputs, ScanVerbOutput},
+    update_verb::{UpdateVerb, UpdateVerbInputs},
+    verb_trait::Verb,
+};
+use log::{debug, info, warn};
+
+use crate::at_client::{
+    cached_copy, create_at_chops, is_stale, parse_root_server_response, AtClientOptions,
+    GetRequestType, GetResponseType,
+};
+use crate::at_client_error::Result;
+use crate::scan::ScanOptions;
+use crate::shared_keys::{
+    check_gettable, decrypt_owner_copy, decrypt_recipient_copy, decrypt_shared_record,
+    encrypt_shared_value, new_shared_key, owner_copy_key, public_key, recipient_copy,
+    recipient_copy_key, recipient_copy_options, shared_with, text_to_put,
+};
+
+/// The async counterpart of `AtClient`, for use on a tokio runtime.
+///
+/// Like `AtClient` it reconnects automatically when the connection drops,
+/// but it can't close the connection cleanly when dropped, so call `close` when done with it.
+pub struct AsyncAtClient {
+    tls_client: AsyncTlsClient,
+    client_at_sign: AtSign,
+    at_chops: AtChops,
+    server_addr: AtServerAddr,
+    options: AtClientOptions,
+}
+
+impl AsyncAtClient {
+    /// Initialises a new `AsyncAtClient` with the specified secrets and at_sign.
+    ///
+    /// This will lookup the address of the given at_sign's server then connect and authenticate with it.
+    pub async fn init(at_secrets: AtSecrets, at_sign: AtSign) -> Result<Self> {
+        Self::init_with_options(at_secrets, at_sign, AtClientOptions::default()).await
+    }
+
+    /// Initialises a new `AsyncAtClient` with the specified secrets, at_sign and connection options.
+    pub async fn init_with_options(
+        at_secrets: AtSecrets,
+        at_sign: AtSign,
+        options: AtClientOptions,
+    ) -> Result<Self> {
+        debug!("Initialising async at_client");
+        debug!("Getting {} server address", at_sign);
+        let mut root_client = AsyncTlsClient::connect_with_retry::<TokioRustlsConnection>(
+            &options.root_server,
+            &options.connection_config,
+            &options.retry_policy,
+        )
+        .await?;
+        root_client
+            .send_data(at_sign.get_at_sign_without_prefix())
+            .await?;
+        let server_addr = parse_root_server_response(&at_sign, &root_client.read_data().await?)?;
+        debug!("Connecting to at_sign server");
+        let mut tls_client = AsyncTlsClient::connect_with_retry::<TokioRustlsConnection>(
+            &server_addr,
+            &options.connection_config,
+            &options.retry_policy,
+        )
+        .await?;
+        let at_chops = create_at_chops(&at_secrets)?;
+        Self::authenticate_with_server(&mut tls_client, &at_chops, &at_sign).await?;
+        info!("Initialised async at_client successfully");
+        Ok(Self {
+            tls_client,
+            client_at_sign: at_sign,
+            at_chops,
+            server_addr,
+            options,
+        })
+    }
+
+    /// Authenticates with the at_sign's server using PKAM.
+    async fn authenticate_with_server(
+        tls_client: &mut AsyncTlsClient,
+        at_chops: &AtChops,
+        at_sign: &AtSign,
+    ) -> at_errors::Result<()> {
+        let challenge = FromVerb::execute_async(tls_client, FromVerbInputs::new(at_sign)).await?;
+        PkamVerb::execute_async(tls_client, PkamVerbInputs::new(&challenge, at_chops)).await?;
+        Ok(())
+    }
+
+    /// Drops the current connection, connects to the atServer again and re-authenticates.
+    async fn reconnect(&mut self) -> at_errors::Result<()> {
+        info!("Reconnecting to {}'s server", self.client_at_sign);
+        let mut tls_client = AsyncTlsClient::connect_with_retry::<TokioRustlsConnection>(
+            &self.server_addr,
+            &self.options.connection_config,
+            &self.options.retry_policy,
+        )
+        .await?;
+        Self::authenticate_with_server(&mut tls_client, &self.at_chops, &self.client_at_sign)
+            .await?;
+        self.tls_client = tls_client;
+        info!("Reconnected successfully");
+        Ok(())
+    }
+
+    /// Executes the verb `V`, reconnecting like `AtClient::execute_with_reconnect` if it fails with a transient error.
+    ///
+    /// Idempotent verbs are then retried once on the new connection, others return the original error.
+    async fn execute<'a, V>(
+        &mut self,
+        idempotent: bool,
+        input: V::Inputs,
+    ) -> at_errors::Result<V::Output>
+    where
+        V: Verb<'a>,
+        V::Inputs: Send,
+        V::Output: Send,
+    {
+        let command = V::format_command(&input)?;
+        match Self::send_command::<V>(&mut self.tls_client, &command, &input).await {
+            Err(error) if error.is_retryable() => {
+                warn!("Connection to atServer broken: {}", error);
+                self.reconnect().await?;
+                if idempotent {
+                    Self::send_command::<V>(&mut self.tls_client, &command, &input).await
+                } else {
+                    Err(error)
+                }
+            }
+            result => result,
+        }
+    }
+
+    async fn send_command<'a, V>(
+        tls_client: &mut AsyncTlsClient,
+        command: &str,
+        input: &V::Inputs,
+    ) -> at_errors::Result<V::Output>
+    where
+        V: Verb<'a>,
+        V::Inputs: Send,
+    {
+        tls_client.send_data(command.to_owned()).await?;
+        let response_data = tls_client.read_data().await?;
+        V::parse_response(&response_data, input)
+    }
+
+    /// Execute the scan verb to fetch all at_ids.
+    pub async fn scan(&mut self, show_hidden: bool) -> Result<Vec<AtKey>> {
+        debug!("Fetching all at_ids");
+        let scan_verb_args = ScanVerbInputs::new(show_hidden, None, None);
+        let scan_results = self.execute::<ScanVerb>(true, scan_verb_args).await?;
+        debug!("Fetched at_ids successfully: {:?}", scan_results);
+        Ok(scan_results.keys)
+    }
+
+    /// Execute the scan verb to fetch all at_ids matching `options`, including those which aren't valid `AtKey`s.
+    pub async fn scan_with_options(&mut self, options: &ScanOptions) -> Result<ScanVerbOutput> {
+        let scan_verb_args = options.to_inputs(None);
+        Ok(self.execute::<ScanVerb>(true, scan_verb_args).await?)
+    }
+
+    /// Execute the scan verb to fetch the at_ids matching `filter`.
+    pub async fn scan_with_filter(
+        &mut self,
+        show_hidden: bool,
+        filter: &AtKeyFilter,
+    ) -> Result<Vec<AtKey>> {
+        debug!("Fetching at_ids matching {:?}", filter);
+        let scan_verb_args = ScanVerbInputs::new_with_filter(show_hidden, None, filter);
+        let scan_results = self.execute::<ScanVerb>(true, scan_verb_args).await?;
+        Ok(scan_results
+            .keys
+            .into_iter()
+            .filter(|at_key| filter.matches(at_key))
+            .collect())
+    }
+
+    /// Sends `exit` and shuts the connection down cleanly, so the atServer frees it straight away.
+    pub async fn close(mut self) -> Result<()> {
+        debug!("Closing connection to {}'s server", self.client_at_sign);
+        let exited = ExitVerb::execute_async(&mut self.tls_client, ()).await;
+        let closed = self.tls_client.close().await;
+        exited?;
+        Ok(closed?)
+    }
+
+    /// The atServer's version and the optional features it supports.
+    pub async fn info(&mut self) -> Result<ServerInfo> {
+        Ok(self.execute::<InfoVerb>(true, ()).await?)
+    }
+
+    /// Measures the round trip time to the atServer with a `noop`.
+    pub async fn ping(&mut self) -> Result<Duration> {
+        let start = Instant::now();
+        self.execute::<NoopVerb>(true, NoopVerbInputs::new(Duration::ZERO))
+            .await?;
+        Ok(start.elapsed())
+    }
+
+    /// Block the given atSigns, so our atServer drops their notifications.
+    pub async fn block_at_signs(&mut self, at_signs: &[AtSign]) -> Result<()> {
+        self.change_block_list(ConfigOperation::Add(at_signs.to_vec()))
+            .await
+    }
+
+    /// Stop blocking the given atSigns.
+    pub async fn unblock_at_signs(&mut self, at_signs: &[AtSign]) -> Result<()> {
+        self.change_block_list(ConfigOperation::Remove(at_signs.to_vec()))
+            .await
+    }
+
+    /// The atSigns we have blocked.
+    pub async fn blocked_at_signs(&mut self) -> Result<Vec<AtSign>> {
+        let config_verb_args = ConfigVerbInputs::new(ConfigList::Block, ConfigOperation::Show);
+        Ok(self.execute::<ConfigVerb>(true, config_verb_args).await?)
+    }
+
+    async fn change_block_list(&mut self, operation: ConfigOperation) -> Result<()> {
+        if let ConfigOperation::Add(at_signs) | ConfigOperation::Remove(at_signs) = &operation {
+            if at_signs.is_empty() {
+                return Ok(());
+            }
+        }
+        // Adding or removing the same atSigns twice is harmless, so safe to retry
+        let config_verb_args = ConfigVerbInputs::new(ConfigList::Block, operation);
+        self.execute::<ConfigVerb>(true, config_verb_args).await?;
+        Ok(())
+    }
+
+    /// Lookup the value of the given at_key.
+    ///
+    /// Our atServer answers from its cached copy, unless `refresh_stale_cache` is set and the copy's ttr says it needs refreshing.
+    async fn lookup(
+        &mut self,
+        at_key: &AtKey,
+        return_type: LookupReturnType,
+    ) -> Result<LookupVerbOutput> {
+        let bypass_cache = self.should_bypass_cache(at_key).await?;
+        let lookup_verb_args =
+            LookupVerbInputs::new(at_key, return_type).with_bypass_cache(bypass_cache);
+        Ok(self.execute::<LookupVerb>(true, lookup_verb_args).await?)
+    }
+
+    /// Whether to look up `at_key` from its owner's atServer rather than our atServer's cached copy.
+    /// Only checked when `refresh_stale_cache` is set in the options.
+    async fn should_bypass_cache(&mut self, at_key: &AtKey) -> Result<bool> {
+        if !self.options.refresh_stale_cache || at_key.owner == self.client_at_sign {
+            return Ok(false);
+        }
+        let cached_at_key = cached_copy(at_key, &self.client_at_sign);
+        let llookup_verb_args = LlookupVerbInputs::new(&cached_at_key, LlookupReturnType::Meta);
+        is_stale(self.execute::<LlookupVerb>(true, llookup_verb_args).await)
+    }
+
+    /// Get the data for the given AtKey.
+    pub async fn get_record(
+        &mut self,
+        request_type: GetRequestType,
+        at_key: &AtKey,
+    ) -> Result<GetResponseType> {
+        check_gettable(at_key)?;
+        // The symmetric key the owner of the data shared with us
+        let symm_key_at_key = recipient_copy_key(&at_key.owner, &self.client_at_sign);
+        let recipient_copy = self
+            .lookup(&symm_key_at_key, LookupReturnType::Data)
+            .await?;
+        let record = self.lookup(at_key, request_type.into()).await?;
+        let symm_key = decrypt_recipient_copy(&self.at_chops, recipient_copy)?;
+        decrypt_shared_record(&self.at_chops, &symm_key, record)
+    }
+
+    /// Put or update the data for the given AtKey.
+    ///
+    /// Follows the same steps as `AtClient::put_record`, creating and sharing a symmetric key on first use.
+    pub async fn put_record(&mut self, at_key: &AtKey, data: &AtValue) -> Result<String> {
+        let shared_with = shared_with(at_key)?.clone();
+        let text = text_to_put(data)?;
+        let symm_key_at_key = owner_copy_key(&at_key.owner, &shared_with);
+        let llookup_verb_args = LlookupVerbInputs::new(&symm_key_at_key, LlookupReturnType::Data);
+        let symm_key = match self.execute::<LlookupVerb>(true, llookup_verb_args).await {
+            Ok(owner_copy) => {
+                info!("Already have symm key");
+                decrypt_owner_copy(&self.at_chops, owner_copy)?
+            }
+            Err(error) if error.server_code() == Some(&ServerErrorCode::KeyNotFound) => {
+                info!("No shared key found. Creating a new one.");
+                self.create_shared_key(&at_key.owner, &shared_with).await?
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let encrypted_data = encrypt_shared_value(&self.at_chops, &symm_key, text)?;
+        let update_verb_args = UpdateVerbInputs::new(at_key, &encrypted_data);
+        Ok(self.execute::<UpdateVerb>(false, update_verb_args).await?)
+    }
+
+    /// Creates a new symmetric key, saves a copy for ourselves and shares it with `shared_with`.
+    async fn create_shared_key(&mut self, owner: &AtSign, shared_with: &AtSign) -> Result<String> {
+        let (new_symm_key, owner_copy) = new_shared_key(&self.at_chops)?;
+        info!("Encrypting and saving the new shared key.");
+        let owner_copy_key = owner_copy_key(owner, shared_with);
+        let update_verb_args = UpdateVerbInputs::new(&owner_copy_key, &owner_copy);
+        self.execute::<UpdateVerb>(false, update_verb_args).await?;
+
+        info!("Looking up recipient's public key.");
+        let public_key_at_key = public_key(shared_with);
+        let bypass_cache = self.should_bypass_cache(&public_key_at_key).await?;
+        let plookup_verb_args = PlookupVerbInputs::new(&public_key_at_key, PlookupReturnType::Data)
+            .with_bypass_cache(bypass_cache);
+        let their_public_key = self.execute::<PlookupVerb>(true, plookup_verb_args).await?;
+
+        info!("Encrypting and sending the new shared key.");
+        let recipient_copy = recipient_copy(&self.at_chops, their_public_key, &new_symm_key)?;
+        let recipient_copy_key = recipient_copy_key(owner, shared_with);
+        let update_verb_args = UpdateVerbInputs::new_with_options(
+            &recipient_copy_key,
+            &recipient_copy,
+            recipient_copy_options(),
+        );
+        self.execute::<UpdateVerb>(false, update_verb_args).await?;
+        Ok(new_symm_key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use at_mock_server::fixtures;
+
+    use super::*;
+    use crate::test_utils::{options, start_servers};
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_futures_are_send() {
+        // Only needs to compile: callers spawn these futures on a multi-threaded runtime
+        #[allow(dead_code)]
+        fn check(client: &mut AsyncAtClient, secrets: AtSecrets, at_key: &AtKey, value: &AtValue) {
+            assert_send(&AsyncAtClient::init(secrets, at_key.owner.clone()));
+            assert_send(&client.scan(false));
+            assert_send(&client.get_record(GetRequestType::Data, at_key));
+            assert_send(&client.put_record(at_key, value));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_put_and_get_record() {
+        let (root_server, at_server) = start_servers();
+        let alice = fixtures::alice();
+        let bob = fixtures::bob();
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+
+        let mut alice_client =
+            AsyncAtClient::init_with_options(alice.secrets(), alice.at_sign, options(&root_server))
+                .await
+                .unwrap();
+        alice_client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .await
+            .unwrap();
+        assert!(at_server.get("@bob:shared_key@alice").is_some());
+        assert!(at_server.get("@alice:shared_key@alice").is_none());
+
+        let mut bob_client =
+            AsyncAtClient::init_with_options(bob.secrets(), bob.at_sign, options(&root_server))
+                .await
+                .unwrap();
+        match bob_client
+            .get_record(GetRequestType::Data, &at_key)
+            .await
+            .unwrap()
+        {
+            GetResponseType::Data(AtValue::Text(text)) => assert_eq!(text, "hello bob"),
+            other => panic!("Unexpected response {:?}", other),
+        }
+        alice_client.close().await.unwrap();
+        bob_client.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let (root_server, at_server) = start_servers();
+        let alice = fixtures::alice();
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+        let mut client =
+            AsyncAtClient::init_with_options(alice.secrets(), alice.at_sign, options(&root_server))
+                .await
+                .unwrap();
+
+        // The llookup is retried once the new connection has authenticated again
+        at_server.drop_connection_after(0);
+        client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .await
+            .unwrap();
+        let stored = at_server.get("@bob:message.test@alice").unwrap().value;
+
+        // The update isn't retried, but the client has reconnected for the next call
+        at_server.drop_connection_after(1);
+        let value = AtValue::Text(String::from("hello again"));
+        let Err(error) = client.put_record(&at_key, &value).await else {
+            panic!("Expected the update to fail");
+        };
+        assert!(error.is_retryable());
+        assert_eq!(
+            at_server.get("@bob:message.test@alice").unwrap().value,
+            stored
+        );
+        client.put_record(&at_key, &value).await.unwrap();
+        assert_ne!(
+            at_server.get("@bob:message.test@alice").unwrap().value,
+            stored
+        );
+        client.close().await.unwrap();
+    }
+}
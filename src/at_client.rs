@@ -1,30 +1,106 @@
-use anyhow::Result;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use at_chops::{default_crypto_functions::DefaultCryptoFunctions, AtChops};
-use at_errors::AtError;
+use at_errors::{AtError, ServerErrorCode};
 use at_records::{
     at_key::{AtKey, Visibility},
+    at_key_filter::{AtKeyFilter, VisibilityKind},
     at_record::{AtRecord, AtValue},
     record_metadata::RecordMetadata,
 };
 use at_secrets::AtSecrets;
 use at_sign::AtSign;
-use at_tls::{at_server_addr::AtServerAddr, rustls_connection::RustlsConnection, TlsClient};
+use at_tls::{
+    at_server_addr::AtServerAddr, retry_policy::RetryPolicy, rustls_connection::RustlsConnection,
+    tls_connection_config::TlsConnectionConfig, TlsClient, DROP_WRITE_TIMEOUT,
+};
 use at_verbs::{
+    batch_verb::{BatchVerb, BatchVerbInputs},
+    config_verb::{ConfigList, ConfigOperation, ConfigVerb, ConfigVerbInputs},
+    exit_verb::ExitVerb,
     from_verb::{FromVerb, FromVerbInputs},
+    info_verb::{InfoVerb, ServerInfo},
     llookup_verb::{LlookupReturnType, LlookupVerb, LlookupVerbInputs, LlookupVerbOutput},
     lookup_verb::{LookupReturnType, LookupVerb, LookupVerbInputs, LookupVerbOutput},
+    noop_verb::{NoopVerb, NoopVerbInputs},
+    notify_all_verb::{NotifyAllVerb, NotifyAllVerbInputs},
+    notify_list_verb::{Notification, NotificationOperation, NotifyListVerb, NotifyListVerbInputs},
+    notify_remove_verb::{NotifyRemoveVerb, NotifyRemoveVerbInputs},
+    notify_status_verb::{NotificationStatus, NotifyStatusVerb, NotifyStatusVerbInputs},
     pkam_verb::{PkamVerb, PkamVerbInputs},
-    plookup_verb::{PlookupReturnType, PlookupVerb, PlookupVerbInputs, PlookupVerbOutput},
-    scan_verb::{ScanVerb, ScanVerbInputs},
+    plookup_verb::{PlookupReturnType, PlookupVerb, PlookupVerbInputs},
+    pol_verb::{PolChallenge, PolVerb},
+    scan_verb::{ScanVerb, ScanVerbInputs, ScanVerbOutput},
     update_verb::{UpdateOptions, UpdateVerb, UpdateVerbInputs},
     verb_trait::Verb,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
+
+use crate::at_client_error::{AtClientError, Result};
+use crate::local_key_store::LocalKeyStore;
+use crate::scan::{ScanIter, ScanOptions};
+use crate::shared_keys::{
+    check_gettable, decrypt_owner_copy, decrypt_recipient_copy, decrypt_shared_record,
+    encrypt_shared_value, new_shared_key, owner_copy_key, public_key, recipient_copy,
+    recipient_copy_key, recipient_copy_options, shared_with, text_to_put,
+};
+use crate::sync::SyncReport;
+
+/// How long, in milliseconds, the proof key published for the pol verb lives on our atServer.
+const POL_PROOF_TTL: usize = 60_000;
+
+/// How many updates `put_records` sends with each batch verb.
+const PUT_RECORDS_BATCH_SIZE: usize = 50;
+
+/// Options controlling how the `AtClient` connects to, and stays connected to, the atServer.
+#[derive(Debug, Clone)]
+pub struct AtClientOptions {
+    /// Timeouts used for every connection the client opens.
+    pub connection_config: TlsConnectionConfig,
+
+    /// How connecting (and reconnecting after the connection drops) is retried.
+    pub retry_policy: RetryPolicy,
+
+    /// The root server used to find the atServer of an atSign. Defaults to `root.atsign.org:64`.
+    pub root_server: AtServerAddr,
+
+    /// Whether to check the ttr of our atServer's cached copy before looking up another atSign's key,
+    /// and look the key up from its owner's atServer if the copy is stale.
+    /// Off by default, as the check costs an extra `llookup` round trip for each lookup.
+    pub refresh_stale_cache: bool,
+}
+
+impl AtClientOptions {
+    pub fn new(connection_config: TlsConnectionConfig, retry_policy: RetryPolicy) -> Self {
+        Self {
+            connection_config,
+            retry_policy,
+            root_server: root_server_addr(),
+            refresh_stale_cache: false,
+        }
+    }
+}
+
+impl Default for AtClientOptions {
+    fn default() -> Self {
+        Self::new(TlsConnectionConfig::default(), RetryPolicy::default())
+    }
+}
 
+/// A connection to an atSign's atServer, authenticated as that atSign.
+///
+/// Close it with `close` to tell the atServer the client is leaving. Dropping the client does the same on a best-effort basis,
+/// giving up after `at_tls::DROP_WRITE_TIMEOUT` if the atServer has stopped reading.
 pub struct AtClient {
     tls_client: TlsClient,
     client_at_sign: AtSign,
     at_chops: AtChops,
+    server_addr: AtServerAddr,
+    options: AtClientOptions,
+    /// When the connection was last used, so `KeepAlive` knows when it's idle.
+    last_used: Instant,
 }
 
 impl AtClient {
@@ -32,37 +108,114 @@ impl AtClient {
     ///
     /// This will lookup the address of the given at_sign's server then connect and authenticate with it.
     pub fn init(at_secrets: AtSecrets, at_sign: AtSign) -> Result<Self> {
+        Self::init_with_options(at_secrets, at_sign, AtClientOptions::default())
+    }
+
+    /// Initialises a new `AtClient` with the specified secrets, at_sign and connection options.
+    pub fn init_with_options(
+        at_secrets: AtSecrets,
+        at_sign: AtSign,
+        options: AtClientOptions,
+    ) -> Result<Self> {
         // TODO: Pass in the server address
         debug!("Initialising at_client");
-        let at_sign_server_address = Self::get_server_addr_for_at_sign(&at_sign)?;
+        let at_sign_server_address = Self::get_server_addr_for_at_sign(&at_sign, &options)?;
         debug!("Connecting to at_sign server");
-        let mut tls_client = TlsClient::connect::<RustlsConnection>(&at_sign_server_address)?;
-        debug!("Initialised at_sign server connection successfully");
-        let crypto_service = Box::new(DefaultCryptoFunctions::new());
-        debug!("Initialising at_chops");
-        let at_chops = AtChops::new(
-            crypto_service,
-            &at_secrets.encoded_self_encryption_key,
-            &at_secrets.encoded_and_encrypted_encrypt_private_key,
-            &at_secrets.encoded_and_encrypted_pkam_private_key,
+        let tls_client = TlsClient::connect_with_retry::<RustlsConnection>(
+            &at_sign_server_address,
+            &options.connection_config,
+            &options.retry_policy,
         )?;
-        debug!("Initialised at_chops successfully");
+        debug!("Initialised at_sign server connection successfully");
+        Self::init_with_tls_client(
+            at_secrets,
+            at_sign,
+            tls_client,
+            at_sign_server_address,
+            options,
+        )
+    }
+
+    /// Initialises a new `AtClient` which authenticates over an existing connection to the atServer at `server_addr`.
+    ///
+    /// Useful for running the client over a `ReplayConnection` in regression tests.
+    /// If the connection drops, the client reconnects to `server_addr`.
+    pub fn init_with_tls_client(
+        at_secrets: AtSecrets,
+        at_sign: AtSign,
+        mut tls_client: TlsClient,
+        server_addr: AtServerAddr,
+        options: AtClientOptions,
+    ) -> Result<Self> {
+        let at_chops = create_at_chops(&at_secrets)?;
         Self::authenticate_with_server(&mut tls_client, &at_chops, &at_sign)?;
         info!("Initialised at_client successfully");
         Ok(AtClient {
             tls_client,
             client_at_sign: at_sign,
             at_chops,
+            server_addr,
+            options,
+            last_used: Instant::now(),
         })
     }
 
+    /// Drops the current connection, connects to the atServer again and re-authenticates.
+    fn reconnect(&mut self) -> at_errors::Result<()> {
+        info!("Reconnecting to {}'s server", self.client_at_sign);
+        let mut tls_client = TlsClient::connect_with_retry::<RustlsConnection>(
+            &self.server_addr,
+            &self.options.connection_config,
+            &self.options.retry_policy,
+        )?;
+        Self::authenticate_with_server(&mut tls_client, &self.at_chops, &self.client_at_sign)?;
+        // The old connection has failed, so sending exit on it would only wait for the write to time out
+        self.tls_client.abandon();
+        self.tls_client = tls_client;
+        self.last_used = Instant::now();
+        info!("Reconnected successfully");
+        Ok(())
+    }
+
+    /// Runs `operation` against the current connection.
+    ///
+    /// If it fails with a transient error (see `AtError::is_retryable`), the client reconnects and re-authenticates so later calls work again.
+    /// Idempotent operations are then retried once on the new connection, others return the original error.
+    pub(crate) fn execute_with_reconnect<T, F>(
+        &mut self,
+        idempotent: bool,
+        mut operation: F,
+    ) -> at_errors::Result<T>
+    where
+        F: FnMut(&mut TlsClient) -> at_errors::Result<T>,
+    {
+        self.last_used = Instant::now();
+        match operation(&mut self.tls_client) {
+            Err(error) if error.is_retryable() => {
+                warn!("Connection to atServer broken: {}", error);
+                self.reconnect()?;
+                if idempotent {
+                    operation(&mut self.tls_client)
+                } else {
+                    Err(error)
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// How long since the connection was last used.
+    pub(crate) fn idle_for(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+
     /// Authenticates with the at_sign's server which requires an active tls connection.
     /// Also requires at_chops to be initialised and the at_sign.
     fn authenticate_with_server(
         tls_client: &mut TlsClient,
         at_chops: &AtChops,
         at_sign: &AtSign,
-    ) -> Result<()> {
+    ) -> at_errors::Result<()> {
         let from_verb_args = FromVerbInputs::new(at_sign);
         let challenge = FromVerb::execute(tls_client, from_verb_args)?;
         let pkam_verb_args = PkamVerbInputs::new(&challenge, at_chops);
@@ -71,220 +224,754 @@ impl AtClient {
     }
 
     /// Connects to the atsign "DNS" server to get the server address of the given at_sign.
-    fn get_server_addr_for_at_sign(at_sign: &AtSign) -> Result<AtServerAddr> {
+    fn get_server_addr_for_at_sign(
+        at_sign: &AtSign,
+        options: &AtClientOptions,
+    ) -> Result<AtServerAddr> {
         debug!("Getting {} server address", at_sign);
-        let address = AtServerAddr::new(String::from("root.atsign.org"), 64);
-        let mut client = TlsClient::connect::<RustlsConnection>(&address)?;
+        let mut client = TlsClient::connect_with_retry::<RustlsConnection>(
+            &options.root_server,
+            &options.connection_config,
+            &options.retry_policy,
+        )?;
         client.send_data(at_sign.get_at_sign_without_prefix())?;
-        let response = String::from_utf8(client.read_data()?)?;
-        let addr = response[1..].to_string();
-        debug!("Got {}'s server address: {}", at_sign, &addr);
-        // Trimming to remove the newline character
-        let addr = addr.trim().split(':').collect::<Vec<_>>();
-        let host = addr[0].to_string();
-        let port = addr[1].parse::<u16>()?;
-        Ok(AtServerAddr::new(host, port))
+        let addr = parse_root_server_response(at_sign, &client.read_data()?)?;
+        debug!(
+            "Got {}'s server address: {}:{}",
+            at_sign, addr.host, addr.port
+        );
+        Ok(addr)
     }
 
     /// Execute the scan verb to fetch all at_ids.
+    ///
+    /// Keys which aren't valid `AtKey`s are left out, use `scan_with_options` to see them.
     pub fn scan(&mut self, show_hidden: bool) -> Result<Vec<AtKey>> {
         debug!("Fetching all at_ids");
-        let scan_verb_args = ScanVerbInputs::new(show_hidden, None, None);
-        let scan_results = ScanVerb::execute(&mut self.tls_client, scan_verb_args)?;
+        let options = ScanOptions {
+            show_hidden,
+            ..Default::default()
+        };
+        let scan_results = self.scan_with_options(&options)?;
         debug!("Fetched at_ids successfully: {:?}", scan_results);
-        Ok(scan_results)
+        Ok(scan_results.keys)
+    }
+
+    /// Execute the scan verb to fetch all at_ids matching `options`, including those which aren't valid `AtKey`s.
+    pub fn scan_with_options(&mut self, options: &ScanOptions) -> Result<ScanVerbOutput> {
+        Ok(self.scan_page(options, None)?)
+    }
+
+    /// Iterate over the at_ids matching `options`, fetching a page at a time where the atServer supports it.
+    pub fn scan_iter(&mut self, options: ScanOptions) -> ScanIter<'_> {
+        ScanIter::new(self, options)
+    }
+
+    /// Scan another atSign's atServer for their public keys matching `filter`.
+    ///
+    /// Connects to their atServer without authenticating, so keys shared with us are not included.
+    pub fn scan_remote(&mut self, at_sign: &AtSign, filter: &AtKeyFilter) -> Result<Vec<AtKey>> {
+        debug!("Fetching {}'s public at_ids", at_sign);
+        let filter = filter
+            .clone()
+            .with_owner(at_sign.clone())
+            .with_visibility(VisibilityKind::Public);
+        let mut remote_client = self.connect_to_remote(at_sign)?;
+        let scan_verb_args = ScanVerbInputs::new_with_filter(false, None, &filter);
+        let scan_results = ScanVerb::execute(&mut remote_client, scan_verb_args);
+        close_remote(remote_client);
+        Ok(scan_results?
+            .keys
+            .into_iter()
+            .filter(|at_key| filter.matches(at_key))
+            .collect())
+    }
+
+    /// Opens an unauthenticated connection to another atSign's atServer.
+    fn connect_to_remote(&self, at_sign: &AtSign) -> Result<TlsClient> {
+        let server_addr = Self::get_server_addr_for_at_sign(at_sign, &self.options)?;
+        Ok(TlsClient::connect_with_retry::<RustlsConnection>(
+            &server_addr,
+            &self.options.connection_config,
+            &self.options.retry_policy,
+        )?)
+    }
+
+    /// Opens a connection to another atSign's atServer, authenticated as our atSign using the pol verb.
+    ///
+    /// Their atServer asks us to publish a proof key on our own atServer, which it then looks up to check we are who we say we are.
+    /// The proof key expires after a minute.
+    pub fn connect_to_at_sign(&mut self, at_sign: &AtSign) -> Result<TlsClient> {
+        debug!(
+            "Connecting to {}'s atServer as {}",
+            at_sign, self.client_at_sign
+        );
+        let mut remote_client = self.connect_to_remote(at_sign)?;
+        let from_verb_args = FromVerbInputs::new(&self.client_at_sign);
+        let challenge = FromVerb::execute(&mut remote_client, from_verb_args)?;
+        let challenge = PolChallenge::from_str(&challenge)?;
+        let proof_key = format!("public:{}", challenge.key);
+        let proof_key = AtKey::from_str(&proof_key)
+            .map_err(|error| AtClientError::InvalidAtKey(proof_key, error))?;
+        let proof_value = AtValue::Text(challenge.value);
+        self.execute_with_reconnect(false, |tls_client| {
+            let update_options = UpdateOptions::new(Some(POL_PROOF_TTL), None, None, None);
+            let update_verb_args =
+                UpdateVerbInputs::new_with_options(&proof_key, &proof_value, update_options);
+            UpdateVerb::execute(tls_client, update_verb_args)
+        })?;
+        PolVerb::execute(&mut remote_client, ())?;
+        debug!("Connected to {}'s atServer", at_sign);
+        Ok(remote_client)
+    }
+
+    /// Lookup a key shared with us directly on its owner's atServer, rather than through our own atServer.
+    pub fn lookup_remote(
+        &mut self,
+        at_key: &AtKey,
+        return_type: LookupReturnType,
+    ) -> Result<LookupVerbOutput> {
+        let mut remote_client = self.connect_to_at_sign(&at_key.owner)?;
+        let lookup_verb_args = LookupVerbInputs::new(at_key, return_type);
+        let lookup_result = LookupVerb::execute(&mut remote_client, lookup_verb_args);
+        close_remote(remote_client);
+        Ok(lookup_result?)
+    }
+
+    /// Pushes the changes made to `store` while offline to the atServer, and pulls the atServer's changes into `store`.
+    ///
+    /// When a key was changed in both places, the change committed to the atServer last wins.
+    pub fn sync(&mut self, store: &mut dyn LocalKeyStore) -> Result<SyncReport> {
+        crate::sync::sync(self, store)
+    }
+
+    pub(crate) fn scan_page(
+        &mut self,
+        options: &ScanOptions,
+        page: Option<usize>,
+    ) -> at_errors::Result<ScanVerbOutput> {
+        self.execute_with_reconnect(true, |tls_client| {
+            ScanVerb::execute(tls_client, options.to_inputs(page))
+        })
+    }
+
+    /// Execute the scan verb to fetch the at_ids matching `filter`.
+    pub fn scan_with_filter(
+        &mut self,
+        show_hidden: bool,
+        filter: &AtKeyFilter,
+    ) -> Result<Vec<AtKey>> {
+        debug!("Fetching at_ids matching {:?}", filter);
+        let scan_results = self.execute_with_reconnect(true, |tls_client| {
+            let scan_verb_args = ScanVerbInputs::new_with_filter(show_hidden, None, filter);
+            ScanVerb::execute(tls_client, scan_verb_args)
+        })?;
+        Ok(scan_results
+            .keys
+            .into_iter()
+            .filter(|at_key| filter.matches(at_key))
+            .collect())
     }
 
     // TODO: Create a private method for each verb and then create a public method that abstracts away the complexity of which verb is suitable for the given AtKey.
 
     /// Lookup the value of the given at_key.
+    ///
+    /// Our atServer answers from its cached copy, unless `refresh_stale_cache` is set and the copy's ttr says it needs refreshing.
     fn lookup(
         &mut self,
         at_key: &AtKey,
         return_type: LookupReturnType,
     ) -> Result<LookupVerbOutput> {
         debug!("Looking up at_key");
-        let lookup_verb_args = LookupVerbInputs::new(at_key, return_type);
-        let lookup_result = LookupVerb::execute(&mut self.tls_client, lookup_verb_args)?;
+        let bypass_cache = self.should_bypass_cache(at_key)?;
+        let lookup_result = self.execute_with_reconnect(true, |tls_client| {
+            let lookup_verb_args =
+                LookupVerbInputs::new(at_key, return_type.clone()).with_bypass_cache(bypass_cache);
+            LookupVerb::execute(tls_client, lookup_verb_args)
+        })?;
         debug!("Lookup ran successfully: {:?}", lookup_result);
         Ok(lookup_result)
     }
 
+    /// Whether to look up `at_key` from its owner's atServer rather than our atServer's cached copy,
+    /// because the metadata of the cached copy says it is stale. Keys we own are never cached.
+    /// Only checked when `refresh_stale_cache` is set in the options.
+    fn should_bypass_cache(&mut self, at_key: &AtKey) -> Result<bool> {
+        if !self.options.refresh_stale_cache || at_key.owner == self.client_at_sign {
+            return Ok(false);
+        }
+        let cached_at_key = cached_copy(at_key, &self.client_at_sign);
+        let cached_metadata = self.execute_with_reconnect(true, |tls_client| {
+            let llookup_verb_args = LlookupVerbInputs::new(&cached_at_key, LlookupReturnType::Meta);
+            LlookupVerb::execute(tls_client, llookup_verb_args)
+        });
+        is_stale(cached_metadata)
+    }
+
     /// Get the data for the given AtKey.
     pub fn get_record(
         &mut self,
         request_type: GetRequestType,
         at_key: &AtKey,
     ) -> Result<GetResponseType> {
-        match &at_key.visibility_scope {
-            Visibility::Public => todo!(),
-            Visibility::Private => todo!(),
-            Visibility::Internal => todo!(),
-            Visibility::Shared(_) => {
-                // This is symmetric key that is created by the client and shared with server.
-                // Unlike most at_keys, the client is not the owner of this key.
-                let symm_key_at_key = AtKey {
-                    record_id: String::from("shared_key"),
-                    namespace: None,
-                    is_cached: false,
-                    owner: at_key.owner.clone(),
-                    visibility_scope: Visibility::Shared(self.client_at_sign.clone()),
-                };
-                debug!("Created at_key for getting shared_key: {}", symm_key_at_key);
-
-                let symm_key_lookup_result =
-                    match self.lookup(&symm_key_at_key, LookupReturnType::Data)? {
-                        LookupVerbOutput::Data(data) => data,
-                        LookupVerbOutput::Metadata(_) => todo!(),
-                        LookupVerbOutput::All(_) => todo!(),
-                    };
-                let data_lookup_result = self.lookup(at_key, request_type.into())?;
-                match data_lookup_result {
-                    LookupVerbOutput::Data(data) => {
-                        let decrypted_symm_key = self
-                            .at_chops
-                            .decrypt_symmetric_key(&symm_key_lookup_result)?;
-                        let data = self
-                            .at_chops
-                            .decrypt_data_with_shared_symmetric_key(&decrypted_symm_key, &data)?;
-                        Ok(GetResponseType::Data(AtValue::Text(
-                            data.trim().to_string(),
-                        )))
+        let mut shared_keys = HashMap::new();
+        self.get_record_with_shared_keys(request_type, at_key, &mut shared_keys)
+    }
+
+    /// Get the data for each of the given AtKeys, with a result for each.
+    ///
+    /// The symmetric key each owner shares with us is only looked up and decrypted once.
+    pub fn get_records<'k>(
+        &mut self,
+        request_type: GetRequestType,
+        at_keys: impl IntoIterator<Item = &'k AtKey>,
+    ) -> Vec<Result<GetResponseType>> {
+        let mut shared_keys = HashMap::new();
+        at_keys
+            .into_iter()
+            .map(|at_key| {
+                self.get_record_with_shared_keys(request_type.clone(), at_key, &mut shared_keys)
+            })
+            .collect()
+    }
+
+    /// Gets a record, reusing and adding to the decrypted symmetric keys shared with us, by owner.
+    fn get_record_with_shared_keys(
+        &mut self,
+        request_type: GetRequestType,
+        at_key: &AtKey,
+        shared_keys: &mut HashMap<AtSign, String>,
+    ) -> Result<GetResponseType> {
+        check_gettable(at_key)?;
+        let symm_key = match shared_keys.get(&at_key.owner) {
+            Some(symm_key) => symm_key.clone(),
+            None => {
+                let symm_key = self.symmetric_key_shared_with_us(&at_key.owner)?;
+                shared_keys.insert(at_key.owner.clone(), symm_key.clone());
+                symm_key
+            }
+        };
+        let record = self.lookup(at_key, request_type.into())?;
+        decrypt_shared_record(&self.at_chops, &symm_key, record)
+    }
+
+    /// Looks up and decrypts the symmetric key `owner` shares with us.
+    fn symmetric_key_shared_with_us(&mut self, owner: &AtSign) -> Result<String> {
+        // This is symmetric key that is created by the client and shared with server.
+        // Unlike most at_keys, the client is not the owner of this key.
+        let symm_key_at_key = recipient_copy_key(owner, &self.client_at_sign);
+        debug!("Created at_key for getting shared_key: {}", symm_key_at_key);
+        let recipient_copy = self.lookup(&symm_key_at_key, LookupReturnType::Data)?;
+        decrypt_recipient_copy(&self.at_chops, recipient_copy)
+    }
+
+    /// Put or update the data for the given AtKey.
+    ///
+    /// Only text values shared with another atSign are supported so far.
+    pub fn put_record(&mut self, at_key: &AtKey, data: &AtValue) -> Result<String> {
+        let shared_with = shared_with(at_key)?;
+        let text = text_to_put(data)?;
+        let symm_key = self.symmetric_key_shared_by_us(&at_key.owner, shared_with)?;
+        let encrypted_data = encrypt_shared_value(&self.at_chops, &symm_key, text)?;
+        let result = self.execute_with_reconnect(false, |tls_client| {
+            let update_verb_args = UpdateVerbInputs::new(at_key, &encrypted_data);
+            UpdateVerb::execute(tls_client, update_verb_args)
+        })?;
+        Ok(result)
+    }
+
+    /// Put or update the data for each of the given AtKeys, with a result (the commit ID) for each.
+    ///
+    /// The symmetric key for each recipient is only looked up, or created, once.
+    /// The updates are sent with the batch verb, 50 at a time.
+    pub fn put_records<'k>(
+        &mut self,
+        records: impl IntoIterator<Item = (&'k AtKey, &'k AtValue)>,
+    ) -> Vec<Result<String>> {
+        let mut shared_keys: HashMap<AtSign, String> = HashMap::new();
+        let mut results = Vec::new();
+        // The update commands to send, with the index of their result
+        let mut commands = Vec::new();
+        for (at_key, data) in records {
+            match self.encrypt_for_put(at_key, data, &mut shared_keys) {
+                Ok(command) => {
+                    commands.push((results.len(), command));
+                    // Replaced once the batch has run
+                    results.push(Ok(String::new()));
+                }
+                Err(error) => results.push(Err(error)),
+            }
+        }
+        for chunk in commands.chunks(PUT_RECORDS_BATCH_SIZE) {
+            let commands: Vec<String> = chunk.iter().map(|(_, command)| command.clone()).collect();
+            // Not retried after reconnecting, as some of the updates may have been committed
+            let batch_result = self.execute_with_reconnect(false, |tls_client| {
+                BatchVerb::execute(tls_client, BatchVerbInputs::new(commands.clone()))
+            });
+            match batch_result {
+                Ok(batch_results) => {
+                    for ((index, _), result) in chunk.iter().zip(batch_results) {
+                        results[*index] = result.map_err(AtClientError::from);
+                    }
+                }
+                Err(error) => {
+                    for (index, _) in chunk {
+                        results[*index] = Err(copy_error(&error).into());
                     }
-                    LookupVerbOutput::Metadata(_) => todo!(),
-                    LookupVerbOutput::All(_) => todo!(),
                 }
             }
         }
+        results
     }
 
-    /// Put or update the data for the given AtKey.
-    pub fn put_record(&mut self, at_key: &AtKey, data: &AtValue) -> Result<String> {
-        // 1. See if the we have already shared our symmetric key with the recipient of the data.
-        let symm_key_at_key = AtKey::new_private_key(
-            String::from("shared_key"),
-            match &at_key.visibility_scope {
-                Visibility::Shared(shared_with) => Some(shared_with.get_at_sign_without_prefix()),
-                _ => panic!("This should not happen"),
-            },
-            at_key.owner.clone(),
-        );
+    /// Encrypts `data` with the symmetric key for the recipient and formats the update command for it.
+    fn encrypt_for_put(
+        &mut self,
+        at_key: &AtKey,
+        data: &AtValue,
+        shared_keys: &mut HashMap<AtSign, String>,
+    ) -> Result<String> {
+        let shared_with = shared_with(at_key)?;
+        let text = text_to_put(data)?;
+        let symm_key =
+            self.reused_symmetric_key_shared_by_us(at_key, shared_with, shared_keys, true)?;
+        let encrypted_data = encrypt_shared_value(&self.at_chops, &symm_key, text)?;
+        Ok(UpdateVerb::format_command(&UpdateVerbInputs::new(
+            at_key,
+            &encrypted_data,
+        ))?)
+    }
+
+    /// The symmetric key for `shared_with` from `shared_keys`, or looked up and added to `shared_keys`.
+    ///
+    /// If we haven't shared a key with `shared_with` yet, a new one is created and shared when `create_if_missing`,
+    /// otherwise this fails with the atServer's key not found error.
+    fn reused_symmetric_key_shared_by_us(
+        &mut self,
+        at_key: &AtKey,
+        shared_with: &AtSign,
+        shared_keys: &mut HashMap<AtSign, String>,
+        create_if_missing: bool,
+    ) -> Result<String> {
+        if let Some(symm_key) = shared_keys.get(shared_with) {
+            return Ok(symm_key.clone());
+        }
+        let symm_key = if create_if_missing {
+            self.symmetric_key_shared_by_us(&at_key.owner, shared_with)?
+        } else {
+            self.existing_symmetric_key_shared_by_us(&at_key.owner, shared_with)?
+        };
+        shared_keys.insert(shared_with.clone(), symm_key.clone());
+        Ok(symm_key)
+    }
+
+    /// Encrypts `value` the way it is stored under `at_key` on our atServer, so `get_record` and other clients can read it.
+    ///
+    /// Public values are stored as they are, values we share with the symmetric key for the recipient
+    /// (like `put_record`) and self keys with our self encryption key.
+    /// Keys whose values we don't encrypt ourselves, like cached and internal keys, aren't supported.
+    pub(crate) fn encrypt_value(
+        &mut self,
+        at_key: &AtKey,
+        value: &str,
+        shared_keys: &mut HashMap<AtSign, String>,
+    ) -> Result<String> {
+        match self.value_encryption(at_key)? {
+            Visibility::Public => Ok(value.to_owned()),
+            Visibility::Shared(shared_with) => {
+                let symm_key = self.reused_symmetric_key_shared_by_us(
+                    at_key,
+                    &shared_with,
+                    shared_keys,
+                    true,
+                )?;
+                Ok(self
+                    .at_chops
+                    .encrypt_data_with_shared_symmetric_key(&symm_key, value)?)
+            }
+            _ => Ok(self.at_chops.encrypt_data_with_self_encryption_key(value)?),
+        }
+    }
+
+    /// Decrypts a value stored under `at_key` on our atServer. The reverse of `encrypt_value`.
+    ///
+    /// Unlike encrypting, this never creates a shared key, so it fails if we haven't shared one with the recipient.
+    pub(crate) fn decrypt_value(
+        &mut self,
+        at_key: &AtKey,
+        value: &str,
+        shared_keys: &mut HashMap<AtSign, String>,
+    ) -> Result<String> {
+        match self.value_encryption(at_key)? {
+            Visibility::Public => Ok(value.to_owned()),
+            Visibility::Shared(shared_with) => {
+                let symm_key = self.reused_symmetric_key_shared_by_us(
+                    at_key,
+                    &shared_with,
+                    shared_keys,
+                    false,
+                )?;
+                Ok(self
+                    .at_chops
+                    .decrypt_data_with_shared_symmetric_key(&symm_key, value)?)
+            }
+            _ => Ok(self.at_chops.decrypt_data_with_self_encryption_key(value)?),
+        }
+    }
 
+    /// The visibility which decides how `encrypt_value` encrypts the value of `at_key`, either public, shared or private.
+    fn value_encryption(&self, at_key: &AtKey) -> Result<Visibility> {
+        if at_key.is_cached || at_key.owner != self.client_at_sign {
+            return Err(unsupported(
+                "Encrypting values of keys owned by other atSigns",
+            ));
+        }
+        if at_key.record_id == "shared_key" {
+            // Encrypted with an RSA public key by `put_record`
+            return Err(unsupported("Encrypting shared symmetric keys"));
+        }
+        match &at_key.visibility_scope {
+            Visibility::Internal => Err(unsupported("Encrypting values of internal keys")),
+            visibility_scope => Ok(visibility_scope.clone()),
+        }
+    }
+
+    /// Looks up the symmetric key `owner` has already shared with `shared_with`.
+    ///
+    /// Fails with the atServer's key not found error if there isn't one.
+    fn existing_symmetric_key_shared_by_us(
+        &mut self,
+        owner: &AtSign,
+        shared_with: &AtSign,
+    ) -> Result<String> {
+        let symm_key_at_key = owner_copy_key(owner, shared_with);
         debug!(
             "Created at_key for fetching potentially already created symm key: {}",
             symm_key_at_key
         );
+        let owner_copy = self.execute_with_reconnect(true, |tls_client| {
+            let llookup_verb_args =
+                LlookupVerbInputs::new(&symm_key_at_key, LlookupReturnType::Data);
+            LlookupVerb::execute(tls_client, llookup_verb_args)
+        })?;
+        info!("Already have symm key");
+        decrypt_owner_copy(&self.at_chops, owner_copy)
+    }
 
-        let llookup_verb_args = LlookupVerbInputs::new(&symm_key_at_key, LlookupReturnType::Data);
-        let llokup_verb_result = LlookupVerb::execute(&mut self.tls_client, llookup_verb_args);
-
-        match llokup_verb_result {
-            Err(AtError::KeyNotFound) => {
+    /// Looks up the symmetric key `owner` shares with `shared_with`, or creates and shares a new one.
+    fn symmetric_key_shared_by_us(
+        &mut self,
+        owner: &AtSign,
+        shared_with: &AtSign,
+    ) -> Result<String> {
+        // 1. See if the we have already shared our symmetric key with the recipient of the data.
+        match self.existing_symmetric_key_shared_by_us(owner, shared_with) {
+            Err(AtClientError::AtError(error))
+                if error.server_code() == Some(&ServerErrorCode::KeyNotFound) =>
+            {
                 info!("No shared key found. Creating a new one.");
-                // 2. If we have not shared the symmetric key, then we need to create it
-                let new_symm_key = self.at_chops.create_new_shared_symmetric_key()?;
-                // 3. If we have just created a new symmetric key, we should encrypt with "our" public key and save it for use later
-                info!("Encrypting and saving the new shared key.");
-                let encrypted_new_symm_key = self
-                    .at_chops
-                    .encrypt_data_with_our_public_key(&new_symm_key)?;
-                let encrypted_new_symm_key_value = AtValue::Text(encrypted_new_symm_key);
-                let update_verb_args =
-                    UpdateVerbInputs::new(&symm_key_at_key, &encrypted_new_symm_key_value);
-                let _ = UpdateVerb::execute(&mut self.tls_client, update_verb_args)?;
-                // 4. If we have just created a new symmetric key, we should encrypt with "their" public key and send it to them
-                info!("Looking up recipient's public key.");
-                let public_key_at_key = AtKey {
-                    record_id: String::from("publickey"),
-                    namespace: None,
-                    is_cached: false,
-                    owner: match &at_key.visibility_scope {
-                        Visibility::Shared(shared_with) => shared_with.clone(),
-                        _ => panic!("This should not happen."),
-                    },
-                    visibility_scope: Visibility::Public,
-                };
-                let plookup_verb_args =
-                    PlookupVerbInputs::new(&public_key_at_key, PlookupReturnType::Data);
-                let plookup_verb_result =
-                    PlookupVerb::execute(&mut self.tls_client, plookup_verb_args)?;
-                info!("Encrypting and sending the new shared key.");
-                let their_public_key = match plookup_verb_result {
-                    PlookupVerbOutput::Data(data) => match data {
-                        AtValue::Text(text) => text,
-                        _ => panic!("This should not happen."),
-                    },
-                    PlookupVerbOutput::Meta(_) => panic!("This should not happen."),
-                    PlookupVerbOutput::All(_) => panic!("This should not happen."),
-                };
-                let encrypted_new_symm_key = self
-                    .at_chops
-                    .encrypt_data_with_public_key(&their_public_key, &new_symm_key)?;
-                let encrypted_new_symm_key_value = AtValue::Text(encrypted_new_symm_key);
-                let shared_key_at_key = AtKey {
-                    record_id: String::from("shared_key"),
-                    namespace: None,
-                    is_cached: false,
-                    owner: at_key.owner.clone(),
-                    visibility_scope: Visibility::Shared(self.client_at_sign.clone()),
-                };
-                let update_verb_args = UpdateVerbInputs::new_with_options(
-                    &shared_key_at_key,
-                    &encrypted_new_symm_key_value,
-                    UpdateOptions::new(None, None, Some(86400), None),
-                );
-                let _ = UpdateVerb::execute(&mut self.tls_client, update_verb_args)?;
-                // 5. Encrypt the data with the symmetric key and send it to the server
-                match data {
-                    AtValue::Text(text) => {
-                        let encrypted_data = self
-                            .at_chops
-                            .encrypt_data_with_shared_symmetric_key(&new_symm_key, text)?;
-                        let encrypted_data = AtValue::Text(encrypted_data);
-                        let update_verb_args = UpdateVerbInputs::new(at_key, &encrypted_data);
-                        let result = UpdateVerb::execute(&mut self.tls_client, update_verb_args)?;
-                        Ok(result)
-                    }
-                    AtValue::Binary(_) => todo!(),
-                }
+                self.create_shared_key(owner, shared_with)
             }
-            Ok(LlookupVerbOutput::Data(symm_key)) => {
-                // 5. Encrypt the data with the symmetric key and send it to the server
-                info!("Already have symm key");
-                let encrypted_symm_key = match symm_key {
-                    AtValue::Text(text) => text,
-                    _ => panic!("Unexpected variant"),
-                };
-                let symm_key = self.at_chops.decrypt_symmetric_key(&encrypted_symm_key)?;
-                match data {
-                    AtValue::Text(text) => {
-                        let encrypted_data = self
-                            .at_chops
-                            .encrypt_data_with_shared_symmetric_key(&symm_key, text)?;
-                        let encrypted_data = AtValue::Text(encrypted_data);
-                        let update_verb_args = UpdateVerbInputs::new(at_key, &encrypted_data);
-                        let result = UpdateVerb::execute(&mut self.tls_client, update_verb_args)?;
-                        Ok(result)
-                    }
-                    AtValue::Binary(_) => todo!(),
-                }
+            result => result,
+        }
+    }
+
+    /// Creates a new symmetric key, saves a copy for ourselves and shares it with `shared_with`.
+    fn create_shared_key(&mut self, owner: &AtSign, shared_with: &AtSign) -> Result<String> {
+        // 2. If we have not shared the symmetric key, then we need to create it
+        // 3. If we have just created a new symmetric key, we should encrypt with "our" public key and save it for use later
+        let (new_symm_key, owner_copy) = new_shared_key(&self.at_chops)?;
+        info!("Encrypting and saving the new shared key.");
+        let owner_copy_key = owner_copy_key(owner, shared_with);
+        self.execute_with_reconnect(false, |tls_client| {
+            let update_verb_args = UpdateVerbInputs::new(&owner_copy_key, &owner_copy);
+            UpdateVerb::execute(tls_client, update_verb_args)
+        })?;
+        // 4. If we have just created a new symmetric key, we should encrypt with "their" public key and send it to them
+        info!("Looking up recipient's public key.");
+        let public_key_at_key = public_key(shared_with);
+        let bypass_cache = self.should_bypass_cache(&public_key_at_key)?;
+        let their_public_key = self.execute_with_reconnect(true, |tls_client| {
+            let plookup_verb_args =
+                PlookupVerbInputs::new(&public_key_at_key, PlookupReturnType::Data)
+                    .with_bypass_cache(bypass_cache);
+            PlookupVerb::execute(tls_client, plookup_verb_args)
+        })?;
+        info!("Encrypting and sending the new shared key.");
+        let recipient_copy = recipient_copy(&self.at_chops, their_public_key, &new_symm_key)?;
+        let recipient_copy_key = recipient_copy_key(owner, shared_with);
+        self.execute_with_reconnect(false, |tls_client| {
+            let update_verb_args = UpdateVerbInputs::new_with_options(
+                &recipient_copy_key,
+                &recipient_copy,
+                recipient_copy_options(),
+            );
+            UpdateVerb::execute(tls_client, update_verb_args)
+        })?;
+        Ok(new_symm_key)
+    }
+
+    /// Sends `exit` and closes the connection cleanly, so the atServer frees it straight away
+    /// instead of counting it towards its connection limit until it times out.
+    pub fn close(mut self) -> Result<()> {
+        self.exit()
+    }
+
+    fn exit(&mut self) -> Result<()> {
+        debug!("Closing connection to {}'s server", self.client_at_sign);
+        let exited = ExitVerb::execute(&mut self.tls_client, ());
+        // Close even if exit couldn't be sent, e.g. because the connection had already dropped
+        let closed = self.tls_client.close();
+        exited?;
+        Ok(closed?)
+    }
+
+    /// The atServer's version and the optional features it supports.
+    pub fn info(&mut self) -> Result<ServerInfo> {
+        Ok(self.execute_with_reconnect(true, |tls_client| InfoVerb::execute(tls_client, ()))?)
+    }
+
+    /// Measures the round trip time to the atServer with a `noop`.
+    ///
+    /// If the connection had dropped, the time includes reconnecting.
+    /// To stop the atServer closing an idle connection, see `KeepAlive`.
+    pub fn ping(&mut self) -> Result<Duration> {
+        let start = Instant::now();
+        self.execute_with_reconnect(true, |tls_client| {
+            NoopVerb::execute(tls_client, NoopVerbInputs::new(Duration::ZERO))
+        })?;
+        Ok(start.elapsed())
+    }
+
+    /// Sends a `noop` for `KeepAlive`, waiting at most `timeout` for each read and write, and without reconnecting.
+    ///
+    /// If it fails the connection is abandoned, so the next call reconnects instead of reading the late reply.
+    pub(crate) fn keep_alive_noop(&mut self, timeout: Duration) -> Result<()> {
+        let read_timeout = self.options.connection_config.read_timeout;
+        let write_timeout = self.options.connection_config.write_timeout;
+        let shorter = |configured: Option<Duration>| configured.map_or(timeout, |t| t.min(timeout));
+        self.last_used = Instant::now();
+        let result = self
+            .tls_client
+            .set_read_timeout(Some(shorter(read_timeout)))
+            .and_then(|()| {
+                self.tls_client
+                    .set_write_timeout(Some(shorter(write_timeout)))
+            })
+            .map_err(AtError::from)
+            .and_then(|()| {
+                NoopVerb::execute(&mut self.tls_client, NoopVerbInputs::new(Duration::ZERO))
+            })
+            .and_then(|_| {
+                self.tls_client.set_read_timeout(read_timeout)?;
+                Ok(self.tls_client.set_write_timeout(write_timeout)?)
+            });
+        if result.is_err() {
+            self.tls_client.abandon();
+        }
+        Ok(result?)
+    }
+
+    /// The notifications our atServer has received, limited to those matching `filter`.
+    pub fn list_notifications(
+        &mut self,
+        filter: NotifyListVerbInputs,
+    ) -> Result<Vec<Notification>> {
+        Ok(self.execute_with_reconnect(true, |tls_client| {
+            NotifyListVerb::execute(tls_client, filter.clone())
+        })?)
+    }
+
+    /// How far our atServer has got delivering a notification we sent, given the ID it returned.
+    pub fn notification_status(&mut self, id: &str) -> Result<NotificationStatus> {
+        Ok(self.execute_with_reconnect(true, |tls_client| {
+            NotifyStatusVerb::execute(tls_client, NotifyStatusVerbInputs::new(id))
+        })?)
+    }
+
+    /// Removes a notification our atServer has received.
+    pub fn remove_notification(&mut self, id: &str) -> Result<()> {
+        // Removing twice is harmless, so safe to retry
+        Ok(self.execute_with_reconnect(true, |tls_client| {
+            NotifyRemoveVerb::execute(tls_client, NotifyRemoveVerbInputs::new(id))
+        })?)
+    }
+
+    /// Notifies each of `recipients` of an operation on `at_key`, returning the ID of each recipient's notification.
+    ///
+    /// The value is sent as is, so only pass one which is public or already encrypted for every recipient.
+    pub fn notify_all(
+        &mut self,
+        recipients: &[AtSign],
+        at_key: &AtKey,
+        operation: NotificationOperation,
+        value: Option<&str>,
+    ) -> Result<Vec<(AtSign, String)>> {
+        if recipients.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Not retried, which could notify the recipients twice
+        Ok(self.execute_with_reconnect(false, |tls_client| {
+            let mut notify_all_verb_args =
+                NotifyAllVerbInputs::new(recipients, at_key).with_operation(operation.clone());
+            if let Some(value) = value {
+                notify_all_verb_args = notify_all_verb_args.with_value(value);
             }
-            Ok(LlookupVerbOutput::All(_) | LlookupVerbOutput::Meta(_)) => {
-                panic!("Unexpected LlookupVerbOutput variant")
+            NotifyAllVerb::execute(tls_client, notify_all_verb_args)
+        })?)
+    }
+
+    /// Block the given atSigns, so our atServer drops their notifications.
+    pub fn block_at_signs(&mut self, at_signs: &[AtSign]) -> Result<()> {
+        self.change_block_list(ConfigOperation::Add(at_signs.to_vec()))
+    }
+
+    /// Stop blocking the given atSigns.
+    pub fn unblock_at_signs(&mut self, at_signs: &[AtSign]) -> Result<()> {
+        self.change_block_list(ConfigOperation::Remove(at_signs.to_vec()))
+    }
+
+    /// The atSigns we have blocked.
+    pub fn blocked_at_signs(&mut self) -> Result<Vec<AtSign>> {
+        Ok(self.execute_with_reconnect(true, |tls_client| {
+            let config_verb_args = ConfigVerbInputs::new(ConfigList::Block, ConfigOperation::Show);
+            ConfigVerb::execute(tls_client, config_verb_args)
+        })?)
+    }
+
+    fn change_block_list(&mut self, operation: ConfigOperation) -> Result<()> {
+        if let ConfigOperation::Add(at_signs) | ConfigOperation::Remove(at_signs) = &operation {
+            if at_signs.is_empty() {
+                return Ok(());
             }
-            Err(error) => Err(error.into()),
         }
+        // Adding or removing the same atSigns twice is harmless, so safe to retry
+        self.execute_with_reconnect(true, |tls_client| {
+            let config_verb_args = ConfigVerbInputs::new(ConfigList::Block, operation.clone());
+            ConfigVerb::execute(tls_client, config_verb_args)
+        })?;
+        Ok(())
     }
 
     pub fn put_metadata(&mut self, _at_key: &AtKey, _metadata: &RecordMetadata) -> Result<String> {
         // let update_verb_args = UpdateVerbInputs::new(at_key, &AtValue::Metadata(metadata.clone()));
         // let result = UpdateVerb::execute(&mut self.tls_client, update_verb_args)?;
         // Ok(result)
-        todo!()
+        Err(unsupported("Putting metadata"))
     }
 }
 
-#[derive(Debug)]
+impl Drop for AtClient {
+    fn drop(&mut self) {
+        // Test connections panic on unexpected commands, and panicking again would abort
+        if self.tls_client.is_closed() || std::thread::panicking() {
+            return;
+        }
+        if let Err(e) = self.tls_client.set_write_timeout(Some(DROP_WRITE_TIMEOUT)) {
+            debug!("Failed to limit how long closing takes: {}", e);
+        }
+        if let Err(e) = self.exit() {
+            debug!("Failed to close connection: {}", e);
+        }
+    }
+}
+
+/// Sends `exit` on a connection to another atSign's atServer once we are done with it, on a best-effort basis.
+fn close_remote(mut remote_client: TlsClient) {
+    if let Err(e) = ExitVerb::execute(&mut remote_client, ()) {
+        debug!("Failed to exit remote connection: {}", e);
+    }
+}
+
+pub(crate) fn unsupported(operation: &str) -> AtClientError {
+    AtError::Unsupported(String::from(operation)).into()
+}
+
+/// A verb returned a different kind of output than was requested.
+pub(crate) fn unexpected_output(output: impl std::fmt::Debug) -> AtClientError {
+    AtError::UnexpectedResponse(format!("{:?}", output)).into()
+}
+
+/// Our atServer's cached copy of `at_key`, which is owned by another atSign, e.g. `cached:@alice:phone@bob` for `@alice:phone@bob`.
+pub(crate) fn cached_copy(at_key: &AtKey, client_at_sign: &AtSign) -> AtKey {
+    let visibility_scope = match &at_key.visibility_scope {
+        Visibility::Shared(_) => Visibility::Shared(client_at_sign.clone()),
+        visibility_scope => visibility_scope.clone(),
+    };
+    AtKey {
+        is_cached: true,
+        visibility_scope,
+        ..at_key.clone()
+    }
+}
+
+/// Whether the result of `llookup:meta:` of a cached copy says the copy needs refreshing. No cached copy doesn't.
+pub(crate) fn is_stale(cached_metadata: at_errors::Result<LlookupVerbOutput>) -> Result<bool> {
+    match cached_metadata {
+        Ok(LlookupVerbOutput::Meta(metadata)) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_millis() as usize);
+            Ok(metadata.needs_refresh(now))
+        }
+        Ok(other) => Err(unexpected_output(other)),
+        Err(error) if error.is_not_found() => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// A copy of `error` for each command of a batch which failed as a whole, keeping whether it is retryable.
+fn copy_error(error: &AtError) -> AtError {
+    match error {
+        AtError::Server(server_error) => AtError::Server(server_error.clone()),
+        AtError::IoError(io_error) => {
+            AtError::IoError(std::io::Error::new(io_error.kind(), io_error.to_string()))
+        }
+        AtError::Timeout(io_error) => {
+            AtError::Timeout(std::io::Error::new(io_error.kind(), io_error.to_string()))
+        }
+        other => AtError::UnknownAtClientException(other.to_string()),
+    }
+}
+
+/// The address of the atsign "DNS" server.
+fn root_server_addr() -> AtServerAddr {
+    AtServerAddr::new(String::from("root.atsign.org"), 64)
+}
+
+/// Parses the root server's `@host:port` answer for `at_sign` into the atServer address.
+pub(crate) fn parse_root_server_response(
+    at_sign: &AtSign,
+    response: &[u8],
+) -> Result<AtServerAddr> {
+    let response = std::str::from_utf8(response).map_err(AtError::from)?;
+    // Trimming to remove the newline character
+    let addr = response.trim().trim_start_matches('@');
+    if addr == "null" {
+        return Err(AtClientError::AtSignNotFound(at_sign.to_string()));
+    }
+    let invalid_response = || AtClientError::InvalidRootServerResponse(addr.to_owned());
+    let (host, port) = addr.split_once(':').ok_or_else(invalid_response)?;
+    let port = port.parse::<u16>().map_err(|_| invalid_response())?;
+    Ok(AtServerAddr::new(host.to_string(), port))
+}
+
+/// Decrypts the keys in `at_secrets` for use by the client.
+pub(crate) fn create_at_chops(at_secrets: &AtSecrets) -> Result<AtChops> {
+    let crypto_service = Box::new(DefaultCryptoFunctions::new());
+    debug!("Initialising at_chops");
+    let at_chops = AtChops::new(
+        crypto_service,
+        &at_secrets.encoded_self_encryption_key,
+        &at_secrets.encoded_and_encrypted_encrypt_private_key,
+        &at_secrets.encoded_and_encrypted_pkam_private_key,
+    )?;
+    debug!("Initialised at_chops successfully");
+    Ok(at_chops)
+}
+
+#[derive(Debug, Clone)]
 pub enum GetRequestType {
     Data,
     MetaData,
@@ -307,3 +994,880 @@ pub enum GetResponseType {
     Meta(RecordMetadata),
     All(AtRecord),
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use at_mock_server::{fixtures, key_store::StoredRecord, MockAtServer, MockRootServer};
+    use at_tls::{replay_connection::ReplayConnection, trace::TraceSession};
+
+    use super::*;
+    use crate::local_key_store::InMemoryKeyStore;
+    use crate::test_utils::{alice_client, options, start_separate_servers, start_servers};
+
+    #[test]
+    fn test_init_authenticates() {
+        let (root_server, _at_server) = start_servers();
+        let alice = fixtures::alice();
+        let result =
+            AtClient::init_with_options(alice.secrets(), alice.at_sign, options(&root_server));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_init_with_wrong_keys_fails() {
+        let (root_server, _at_server) = start_servers();
+        let result = AtClient::init_with_options(
+            fixtures::bob().secrets(),
+            fixtures::alice().at_sign,
+            options(&root_server),
+        );
+        assert!(result.err().unwrap().is_auth_failure());
+    }
+
+    #[test]
+    fn test_init_unknown_at_sign_fails() {
+        let (root_server, _at_server) = start_servers();
+        let result = AtClient::init_with_options(
+            fixtures::alice().secrets(),
+            AtSign::new(String::from("unknown")),
+            options(&root_server),
+        );
+        assert!(result.err().unwrap().is_not_found());
+    }
+
+    #[test]
+    fn test_put_and_get_record() {
+        let (root_server, at_server) = start_servers();
+        let bob = fixtures::bob();
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+
+        let mut alice_client = alice_client(&root_server);
+        alice_client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .unwrap();
+        // A second put reuses the shared key created by the first
+        alice_client
+            .put_record(&at_key, &AtValue::Text(String::from("hello again")))
+            .unwrap();
+        assert_ne!(
+            at_server.get("@bob:message.test@alice").unwrap().value,
+            "hello again"
+        );
+        assert_eq!(
+            at_server.get("@bob:shared_key@alice").unwrap().ttr,
+            Some(86400)
+        );
+
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign, options(&root_server)).unwrap();
+        let result = bob_client
+            .get_record(GetRequestType::Data, &at_key)
+            .unwrap();
+        match result {
+            GetResponseType::Data(AtValue::Text(text)) => assert_eq!(text, "hello again"),
+            other => panic!("Unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_put_record_shares_key_with_recipient() {
+        let (root_server, at_server) = start_servers();
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+
+        let mut client = alice_client(&root_server);
+        client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .unwrap();
+        // Our own copy is private, and the copy encrypted for @bob is shared with @bob
+        assert!(at_server.get("shared_key.bob@alice").is_some());
+        assert!(at_server.get("@bob:shared_key@alice").is_some());
+        assert!(at_server.get("@alice:shared_key@alice").is_none());
+    }
+
+    #[test]
+    fn test_lookup_uses_stale_cache_by_default() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("@alice:phone.test@bob", "fresh");
+        at_server.put_record(
+            "cached:@alice:phone.test@bob",
+            StoredRecord {
+                value: String::from("cached"),
+                ttr: Some(0),
+                ..Default::default()
+            },
+        );
+        let mut client = alice_client(&root_server);
+        let at_key = AtKey::from_str("@alice:phone.test@bob").unwrap();
+        match client.lookup(&at_key, LookupReturnType::Data).unwrap() {
+            LookupVerbOutput::Data(data) => assert_eq!(data, "cached"),
+            other => panic!("Unexpected output {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lookup_refreshes_stale_cache() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("@alice:phone.test@bob", "fresh");
+        let alice = fixtures::alice();
+        let mut options = options(&root_server);
+        options.refresh_stale_cache = true;
+        let mut client =
+            AtClient::init_with_options(alice.secrets(), alice.at_sign, options).unwrap();
+        let at_key = AtKey::from_str("@alice:phone.test@bob").unwrap();
+        let mut lookup = |ttr, refresh_at: Option<&str>| {
+            at_server.put_record(
+                "cached:@alice:phone.test@bob",
+                StoredRecord {
+                    value: String::from("cached"),
+                    ttr,
+                    refresh_at: refresh_at.map(str::to_owned),
+                    ..Default::default()
+                },
+            );
+            match client.lookup(&at_key, LookupReturnType::Data).unwrap() {
+                LookupVerbOutput::Data(data) => data,
+                other => panic!("Unexpected output {:?}", other),
+            }
+        };
+
+        assert_eq!(lookup(Some(-1), None), "cached");
+        assert_eq!(
+            lookup(Some(60_000), Some("2999-01-01 00:00:00.000Z")),
+            "cached"
+        );
+        assert_eq!(
+            lookup(Some(60_000), Some("2000-01-01 00:00:00.000Z")),
+            "fresh"
+        );
+        assert_eq!(lookup(Some(0), None), "fresh");
+    }
+
+    #[test]
+    fn test_put_and_get_records() {
+        let (root_server, at_server) = start_servers();
+        let bob = fixtures::bob();
+        let at_keys: Vec<AtKey> = (0..60)
+            .map(|i| AtKey::from_str(&format!("@bob:message{}.test@alice", i)).unwrap())
+            .collect();
+        let values: Vec<AtValue> = (0..60)
+            .map(|i| AtValue::Text(format!("hello {}", i)))
+            .collect();
+        let carol_key = AtKey::from_str("@carol:message.test@alice").unwrap();
+        let binary = AtValue::Binary(vec![1, 2, 3]);
+
+        let mut alice_client = alice_client(&root_server);
+        let mut records: Vec<(&AtKey, &AtValue)> = at_keys.iter().zip(&values).collect();
+        records.insert(1, (&carol_key, &values[0]));
+        records.insert(2, (&at_keys[0], &binary));
+        let results = alice_client.put_records(records);
+        assert_eq!(results.len(), 62);
+        assert!(results[0].is_ok());
+        // @carol has no public key to share the symmetric key with
+        assert!(results[1].as_ref().unwrap_err().is_not_found());
+        assert!(results[2].is_err());
+        assert!(results[3..].iter().all(Result::is_ok));
+        assert!(at_server.get("@bob:message59.test@alice").is_some());
+
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign, options(&root_server)).unwrap();
+        let missing_key = AtKey::from_str("@bob:missing.test@alice").unwrap();
+        let mut get_keys: Vec<&AtKey> = at_keys.iter().collect();
+        get_keys.push(&missing_key);
+        let results = bob_client.get_records(GetRequestType::Data, get_keys);
+        assert_eq!(results.len(), 61);
+        for (i, result) in results[..60].iter().enumerate() {
+            match result {
+                Ok(GetResponseType::Data(AtValue::Text(text))) => {
+                    assert_eq!(text, &format!("hello {}", i))
+                }
+                other => panic!("Unexpected response {:?}", other),
+            }
+        }
+        assert!(results[60].as_ref().unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_put_record_unsupported() {
+        let (root_server, _at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        let public_key = AtKey::from_str("public:email.test@alice").unwrap();
+        let result = client.put_record(&public_key, &AtValue::Text(String::from("a")));
+        assert!(matches!(
+            result,
+            Err(AtClientError::AtError(AtError::Unsupported(_)))
+        ));
+        let shared_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+        let result = client.put_record(&shared_key, &AtValue::Binary(vec![1, 2]));
+        assert!(matches!(
+            result,
+            Err(AtClientError::AtError(AtError::Unsupported(_)))
+        ));
+    }
+
+    #[test]
+    fn test_scan() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("public:email.test@alice", "alice@example.com");
+        let mut client = alice_client(&root_server);
+        let keys = client.scan(false).unwrap();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                String::from("public:email.test@alice"),
+                String::from("public:publickey@alice")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_with_options() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("public:email.test@alice", "alice@example.com");
+        at_server.put("@bob:phone.test@alice", "123");
+        at_server.put("bad:key@alice", "x");
+        let mut client = alice_client(&root_server);
+        let options = ScanOptions {
+            regex: Some(String::from(r"\.test@")),
+            ..Default::default()
+        };
+        let output = client.scan_with_options(&options).unwrap();
+        let keys: Vec<String> = output.keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                String::from("@bob:phone.test@alice"),
+                String::from("public:email.test@alice")
+            ]
+        );
+        let output = client.scan_with_options(&ScanOptions::default()).unwrap();
+        assert_eq!(output.invalid_keys.len(), 1);
+        assert_eq!(output.invalid_keys[0].0, "bad:key@alice");
+    }
+
+    fn scan_iter_keys(client: &mut AtClient) -> Vec<String> {
+        client
+            .scan_iter(ScanOptions::default())
+            .map(|key| match key {
+                Ok(key) => key.to_string(),
+                Err(AtClientError::InvalidAtKey(key, _)) => format!("invalid {}", key),
+                Err(error) => panic!("Unexpected error {}", error),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_iter() {
+        let (root_server, at_server) = start_servers();
+        for key in ["a", "b", "c", "d", "e"] {
+            at_server.put(&format!("public:{}.test@alice", key), "x");
+        }
+        at_server.put("bad:key@alice", "x");
+        let mut client = alice_client(&root_server);
+        let expected = vec![
+            "public:a.test@alice",
+            "public:b.test@alice",
+            "public:c.test@alice",
+            "public:d.test@alice",
+            "public:e.test@alice",
+            "public:publickey@alice",
+            "invalid bad:key@alice",
+        ];
+
+        // Paging isn't supported by the server, so every key is fetched at once
+        assert_eq!(scan_iter_keys(&mut client), expected);
+
+        at_server.set_scan_page_limit(Some(2));
+        let mut keys = scan_iter_keys(&mut client);
+        keys.sort();
+        let mut sorted_expected = expected.clone();
+        sorted_expected.sort();
+        assert_eq!(keys, sorted_expected);
+    }
+
+    #[test]
+    fn test_scan_iter_ends_when_server_ignores_pages() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("bad:key@alice", "x");
+        at_server.set_scan_page_limit(Some(2));
+        at_server.set_ignore_scan_pages(true);
+        let mut client = alice_client(&root_server);
+        // Every page is the same single invalid key
+        let options = ScanOptions {
+            regex: Some(String::from("^bad:")),
+            ..Default::default()
+        };
+        let keys: Vec<Result<AtKey>> = client.scan_iter(options).take(10).collect();
+        assert_eq!(keys.len(), 1);
+        assert!(matches!(keys[0], Err(AtClientError::InvalidAtKey(_, _))));
+    }
+
+    #[test]
+    fn test_scan_with_filter() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("@bob:laptop.devices.myapp@alice", "1");
+        at_server.put("@bob:phone.devices.myapp@alice", "2");
+        at_server.put("@bob:laptop.myapp@alice", "3");
+        at_server.put("public:laptop.devices.myapp@alice", "4");
+        let mut client = alice_client(&root_server);
+        let filter = AtKeyFilter::new()
+            .with_namespace_prefix("devices.myapp")
+            .with_shared_with(fixtures::bob().at_sign)
+            .with_record_id_glob("lap*");
+        let keys = client.scan_with_filter(false, &filter).unwrap();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(keys, vec![String::from("@bob:laptop.devices.myapp@alice")]);
+    }
+
+    #[test]
+    fn test_scan_remote() {
+        let (root_server, alice_server, bob_server) = start_separate_servers();
+        bob_server.put("public:phone.test@bob", "123");
+        bob_server.put("public:email.other@bob", "bob@example.com");
+        bob_server.put("@alice:secret.test@bob", "shh");
+        alice_server.put("public:phone.test@alice", "456");
+        let mut client = alice_client(&root_server);
+
+        let keys = client
+            .scan_remote(&fixtures::bob().at_sign, &AtKeyFilter::new())
+            .unwrap();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                String::from("public:email.other@bob"),
+                String::from("public:phone.test@bob"),
+                String::from("public:publickey@bob")
+            ]
+        );
+
+        let filter = AtKeyFilter::new().with_namespace_prefix("test");
+        let keys = client
+            .scan_remote(&fixtures::bob().at_sign, &filter)
+            .unwrap();
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(keys, vec![String::from("public:phone.test@bob")]);
+
+        let unknown = AtSign::new(String::from("unknown"));
+        let result = client.scan_remote(&unknown, &AtKeyFilter::new());
+        assert!(result.unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_lookup_remote() {
+        let (root_server, alice_server, bob_server) = start_separate_servers();
+        bob_server.put("@alice:phone.test@bob", "123");
+        bob_server.put("@carol:email.test@bob", "carol@example.com");
+        let mut client = alice_client(&root_server);
+
+        let at_key = AtKey::from_str("@alice:phone.test@bob").unwrap();
+        let output = client
+            .lookup_remote(&at_key, LookupReturnType::Data)
+            .unwrap();
+        assert!(matches!(output, LookupVerbOutput::Data(data) if data == "123"));
+        assert!(alice_server
+            .keys()
+            .iter()
+            .any(|key| key.starts_with("public:_") && key.ends_with("@alice")));
+
+        let at_key = AtKey::from_str("@alice:email.test@bob").unwrap();
+        let result = client.lookup_remote(&at_key, LookupReturnType::Data);
+        assert!(result.unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_connect_to_at_sign_unverified_proof() {
+        let (root_server, _alice_server, _bob_server) = start_separate_servers();
+        // Bob's atServer can't find alice's atServer to check the proof.
+        let bob_server = at_mock_server::MockAtServer::start();
+        root_server.register(&fixtures::bob().at_sign, &bob_server.address());
+        let mut client = alice_client(&root_server);
+
+        let Err(error) = client.connect_to_at_sign(&fixtures::bob().at_sign) else {
+            panic!("pol succeeded without a verified proof");
+        };
+        assert!(error.is_auth_failure());
+    }
+
+    #[test]
+    fn test_sync_pushes_and_pulls() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("public:email.test@alice", "alice@example.com");
+        let mut client = alice_client(&root_server);
+        let mut store = InMemoryKeyStore::new();
+
+        let report = client.sync(&mut store).unwrap();
+        assert_eq!(report.pushed, 0);
+        assert_eq!(
+            store.keys().unwrap(),
+            vec!["public:email.test@alice", "public:publickey@alice"]
+        );
+        assert_eq!(client.sync(&mut store).unwrap(), SyncReport::default());
+
+        // Changes made offline are pushed on the next sync
+        let phone = AtKey::from_str("public:phone.test@alice").unwrap();
+        let email = AtKey::from_str("public:email.test@alice").unwrap();
+        store.update(&phone, "123").unwrap();
+        store.delete(&email).unwrap();
+        let report = client.sync(&mut store).unwrap();
+        assert_eq!(report.pushed, 2);
+        assert_eq!(report.pulled, 0);
+        assert_eq!(
+            at_server.get("public:phone.test@alice").unwrap().value,
+            "123"
+        );
+        assert!(at_server.get("public:email.test@alice").is_none());
+        assert!(store.uncommitted().unwrap().is_empty());
+        assert!(store
+            .get("public:phone.test@alice")
+            .unwrap()
+            .unwrap()
+            .commit_id
+            .is_some());
+
+        // Changes made by other devices are pulled
+        at_server.put("public:phone.test@alice", "456");
+        let report = client.sync(&mut store).unwrap();
+        assert_eq!(report.pulled, 1);
+        assert_eq!(
+            store.get("public:phone.test@alice").unwrap().unwrap().value,
+            "456"
+        );
+    }
+
+    #[test]
+    fn test_sync_encrypts_shared_and_self_keys() {
+        let (root_server, at_server) = start_servers();
+        let bob = fixtures::bob();
+        let message = AtKey::from_str("@bob:message.test@alice").unwrap();
+        let secret = AtKey::from_str("secret.test@alice").unwrap();
+        let mut client = alice_client(&root_server);
+        let mut store = InMemoryKeyStore::new();
+        store.update(&message, "hello bob").unwrap();
+        store.update(&secret, "only alice").unwrap();
+
+        let report = client.sync(&mut store).unwrap();
+        assert_eq!(report.pushed, 2);
+        let stored_message = at_server.get("@bob:message.test@alice").unwrap().value;
+        let stored_secret = at_server.get("secret.test@alice").unwrap().value;
+        assert_ne!(stored_message, "hello bob");
+        assert_ne!(stored_secret, "only alice");
+        assert!(!stored_secret.contains("only alice"));
+
+        // Another device pulls the plain text values
+        let mut other_store = InMemoryKeyStore::new();
+        client.sync(&mut other_store).unwrap();
+        assert_eq!(
+            other_store
+                .get("@bob:message.test@alice")
+                .unwrap()
+                .unwrap()
+                .value,
+            "hello bob"
+        );
+        assert_eq!(
+            other_store.get("secret.test@alice").unwrap().unwrap().value,
+            "only alice"
+        );
+
+        // The recipient can read the shared key
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign, options(&root_server)).unwrap();
+        match bob_client
+            .get_record(GetRequestType::Data, &message)
+            .unwrap()
+        {
+            GetResponseType::Data(AtValue::Text(text)) => assert_eq!(text, "hello bob"),
+            other => panic!("Unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sync_does_not_create_shared_keys_when_pulling() {
+        let (root_server, at_server) = start_servers();
+        // Shared by another device, but the shared key has gone
+        at_server.put("@bob:message.test@alice", "ciphertext");
+        let mut client = alice_client(&root_server);
+        let mut store = InMemoryKeyStore::new();
+
+        client.sync(&mut store).unwrap();
+        assert!(store.get("@bob:message.test@alice").unwrap().is_none());
+        assert!(at_server.get("shared_key.bob@alice").is_none());
+        assert!(at_server.get("@bob:shared_key@alice").is_none());
+    }
+
+    #[test]
+    fn test_sync_rejects_keys_it_cannot_encrypt() {
+        let (root_server, at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        let mut store = InMemoryKeyStore::new();
+        let cached = AtKey::from_str("cached:public:email.test@bob").unwrap();
+        store.update(&cached, "bob@example.com").unwrap();
+
+        let error = client.sync(&mut store).unwrap_err();
+        assert!(matches!(
+            error,
+            AtClientError::AtError(AtError::Unsupported(_))
+        ));
+        assert!(at_server.get("cached:public:email.test@bob").is_none());
+    }
+
+    #[test]
+    fn test_sync_last_write_wins() {
+        let (root_server, at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        let mut store = InMemoryKeyStore::new();
+        client.sync(&mut store).unwrap();
+
+        // Changed remotely, then locally while offline: the local change reaches the atServer last
+        let phone = AtKey::from_str("public:phone.test@alice").unwrap();
+        at_server.put("public:phone.test@alice", "remote");
+        store.update(&phone, "local").unwrap();
+        let report = client.sync(&mut store).unwrap();
+        assert_eq!(report.conflicts, vec!["public:phone.test@alice"]);
+        assert_eq!(
+            at_server.get("public:phone.test@alice").unwrap().value,
+            "local"
+        );
+        assert_eq!(
+            store.get("public:phone.test@alice").unwrap().unwrap().value,
+            "local"
+        );
+
+        // A remote change committed after the local one wins
+        at_server.put("public:phone.test@alice", "remote again");
+        let report = client.sync(&mut store).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(
+            store.get("public:phone.test@alice").unwrap().unwrap().value,
+            "remote again"
+        );
+    }
+
+    /// Waits for the atServer to notice clients leaving.
+    fn wait_for_open_connections(at_server: &MockAtServer, expected: usize) {
+        let start = Instant::now();
+        while at_server.open_connections() != expected {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "{} connections still open",
+                at_server.open_connections()
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_close() {
+        let (root_server, at_server) = start_servers();
+        let alice = fixtures::alice();
+        let client = AtClient::init_with_options(
+            alice.secrets(),
+            alice.at_sign.clone(),
+            options(&root_server),
+        )
+        .unwrap();
+        wait_for_open_connections(&at_server, 1);
+        client.close().unwrap();
+        wait_for_open_connections(&at_server, 0);
+
+        let client = alice_client(&root_server);
+        wait_for_open_connections(&at_server, 1);
+        drop(client);
+        wait_for_open_connections(&at_server, 0);
+    }
+
+    #[test]
+    fn test_reconnect_reauthenticates() {
+        let (root_server, at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+
+        at_server.drop_connection_after(0);
+        // The llookup and update only work once the new connection has authenticated again
+        client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .unwrap();
+        assert!(at_server.get("@bob:message.test@alice").is_some());
+        wait_for_open_connections(&at_server, 1);
+    }
+
+    #[test]
+    fn test_reconnect_retries_idempotent_verbs() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("public:email.test@alice", "alice@example.com");
+        let mut client = alice_client(&root_server);
+
+        at_server.drop_connection_after(0);
+        let keys = client.scan(false).unwrap();
+        assert!(keys
+            .iter()
+            .any(|key| key.to_string() == "public:email.test@alice"));
+    }
+
+    #[test]
+    fn test_reconnect_does_not_retry_other_verbs() {
+        let (root_server, at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+        client
+            .put_record(&at_key, &AtValue::Text(String::from("hello bob")))
+            .unwrap();
+        let stored = at_server.get("@bob:message.test@alice").unwrap().value;
+
+        // Answers the llookup of the shared key, then drops the update
+        at_server.drop_connection_after(1);
+        let value = AtValue::Text(String::from("hello again"));
+        let Err(error) = client.put_record(&at_key, &value) else {
+            panic!("Expected the update to fail");
+        };
+        assert!(error.is_retryable());
+        assert_eq!(
+            at_server.get("@bob:message.test@alice").unwrap().value,
+            stored
+        );
+
+        // The client has already reconnected, so the caller can retry
+        client.put_record(&at_key, &value).unwrap();
+        assert_ne!(
+            at_server.get("@bob:message.test@alice").unwrap().value,
+            stored
+        );
+    }
+
+    #[test]
+    fn test_info_and_ping() {
+        let (root_server, _at_server) = start_servers();
+        let mut alice_client = alice_client(&root_server);
+        let info = alice_client.info().unwrap();
+        assert!(!info.version.is_empty());
+        assert!(alice_client.ping().unwrap() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_notification_management() {
+        let (root_server, _at_server) = start_servers();
+        let bob = fixtures::bob();
+        let mut alice_client = alice_client(&root_server);
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign.clone(), options(&root_server))
+                .unwrap();
+        let carol = AtSign::from_str("carol").unwrap();
+        let at_key = AtKey::from_str("public:phone.test@alice").unwrap();
+
+        assert!(alice_client
+            .notify_all(&[], &at_key, NotificationOperation::Update, None)
+            .unwrap()
+            .is_empty());
+        let ids = alice_client
+            .notify_all(
+                &[bob.at_sign.clone(), carol.clone()],
+                &at_key,
+                NotificationOperation::Update,
+                Some("123"),
+            )
+            .unwrap();
+        assert_eq!(ids[0].0, bob.at_sign);
+        assert_eq!(ids[1].0, carol);
+        let bob_id = &ids[0].1;
+        assert_eq!(
+            alice_client.notification_status(bob_id).unwrap(),
+            NotificationStatus::Delivered
+        );
+
+        let notifications = bob_client
+            .list_notifications(NotifyListVerbInputs::new().with_regex("phone"))
+            .unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(&notifications[0].id, bob_id);
+        assert_eq!(notifications[0].key, "@bob:phone.test@alice");
+        assert_eq!(notifications[0].value.as_deref(), Some("123"));
+        assert_eq!(notifications[0].operation, NotificationOperation::Update);
+        assert!(bob_client
+            .list_notifications(NotifyListVerbInputs::new().with_regex("email"))
+            .unwrap()
+            .is_empty());
+
+        bob_client.remove_notification(bob_id).unwrap();
+        assert!(bob_client
+            .list_notifications(NotifyListVerbInputs::new())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_block_at_signs() {
+        let (root_server, at_server) = start_servers();
+        let alice = fixtures::alice();
+        let bob = fixtures::bob();
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign, options(&root_server)).unwrap();
+        let carol = AtSign::from_str("carol").unwrap();
+
+        assert!(bob_client.blocked_at_signs().unwrap().is_empty());
+        bob_client
+            .block_at_signs(&[alice.at_sign.clone(), carol.clone()])
+            .unwrap();
+        bob_client.unblock_at_signs(&[carol]).unwrap();
+        bob_client.unblock_at_signs(&[]).unwrap();
+        assert_eq!(
+            bob_client.blocked_at_signs().unwrap(),
+            vec![alice.at_sign.clone()]
+        );
+
+        // Notifications from a blocked atSign are dropped
+        let mut alice_client = alice_client(&root_server);
+        alice_client
+            .tls_client
+            .send_data("notify:update:@bob:phone.test@alice:123")
+            .unwrap();
+        alice_client.tls_client.read_data().unwrap();
+        assert!(at_server.notifications().is_empty());
+    }
+
+    /// Options which record the session to a new trace file, and the path of the file.
+    fn recording_options(root_server: &MockRootServer, name: &str) -> (AtClientOptions, PathBuf) {
+        let trace_path = std::env::temp_dir().join(format!(
+            "at_rust_trace_{}_{}_{:?}.txt",
+            name,
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let mut recording_options = options(root_server);
+        recording_options.connection_config.record_trace_to = Some(trace_path.clone());
+        (recording_options, trace_path)
+    }
+
+    /// Reads and removes a recorded trace.
+    fn take_trace(trace_path: &PathBuf) -> String {
+        let trace = std::fs::read_to_string(trace_path).unwrap();
+        std::fs::remove_file(trace_path).unwrap();
+        trace
+    }
+
+    /// A client for `test_at_sign` which replays the session with `server_addr` in `trace`.
+    fn replaying_client(
+        trace: &str,
+        server_addr: AtServerAddr,
+        test_at_sign: &fixtures::TestAtSign,
+        root_server: &MockRootServer,
+    ) -> AtClient {
+        let session = TraceSession::parse_for_address(trace, &server_addr).unwrap();
+        let tls_client = TlsClient::new(Box::new(ReplayConnection::new(session)));
+        AtClient::init_with_tls_client(
+            test_at_sign.secrets(),
+            test_at_sign.at_sign.clone(),
+            tls_client,
+            server_addr,
+            options(root_server),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_drop_while_panicking_does_not_abort() {
+        let root_server = MockRootServer::start();
+        let alice = fixtures::alice();
+        let server_addr = AtServerAddr::new(String::from("localhost"), 1234);
+        let trace = "# session localhost:1234\n< @\n> from:@alice\n< @data:challenge\n> pkam:<redacted>\n< @alice@data:success\n";
+        // The replay doesn't expect exit, and panicking again while unwinding would abort the tests
+        let result = std::panic::catch_unwind(|| {
+            let _client = replaying_client(trace, server_addr, &alice, &root_server);
+            panic!("test failed");
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_replay_recorded_session() {
+        let (root_server, at_server) = start_servers();
+        at_server.put("public:email.test@alice", "alice@example.com");
+        let alice = fixtures::alice();
+        let (recording_options, trace_path) = recording_options(&root_server, "scan");
+        let mut client =
+            AtClient::init_with_options(alice.secrets(), alice.at_sign.clone(), recording_options)
+                .unwrap();
+        let recorded_keys = client.scan(false).unwrap();
+        drop(client);
+        let trace = take_trace(&trace_path);
+        assert!(trace.contains("> pkam:<redacted>"));
+        assert!(trace.contains("> exit"));
+
+        // Replay without the atServer running
+        let server_addr = at_server.address();
+        drop(at_server);
+        let mut client = replaying_client(&trace, server_addr, &alice, &root_server);
+        let replayed_keys = client.scan(false).unwrap();
+        assert_eq!(
+            format!("{:?}", replayed_keys),
+            format!("{:?}", recorded_keys)
+        );
+    }
+
+    #[test]
+    fn test_replay_recorded_put_and_get() {
+        let (root_server, at_server) = start_servers();
+        let alice = fixtures::alice();
+        let bob = fixtures::bob();
+        let at_key = AtKey::from_str("@bob:message.test@alice").unwrap();
+        let value = AtValue::Text(String::from("hello bob"));
+
+        let (alice_options, alice_trace_path) = recording_options(&root_server, "put");
+        let mut alice_client =
+            AtClient::init_with_options(alice.secrets(), alice.at_sign.clone(), alice_options)
+                .unwrap();
+        alice_client.put_record(&at_key, &value).unwrap();
+        drop(alice_client);
+        let (bob_options, bob_trace_path) = recording_options(&root_server, "get");
+        let mut bob_client =
+            AtClient::init_with_options(bob.secrets(), bob.at_sign.clone(), bob_options).unwrap();
+        bob_client
+            .get_record(GetRequestType::Data, &at_key)
+            .unwrap();
+        drop(bob_client);
+        let alice_trace = take_trace(&alice_trace_path);
+        let bob_trace = take_trace(&bob_trace_path);
+        // The symmetric keys and data are only sent encrypted, and even then redacted
+        assert!(alice_trace.contains("> update:ttr:86400:@bob:shared_key@alice <redacted>"));
+        assert!(alice_trace.contains("> update:@bob:message.test@alice <redacted>"));
+        assert!(!alice_trace.contains("hello bob"));
+
+        // Replay without the atServer running
+        let server_addr = at_server.address();
+        drop(at_server);
+        let mut alice_client =
+            replaying_client(&alice_trace, server_addr.clone(), &alice, &root_server);
+        alice_client.put_record(&at_key, &value).unwrap();
+        let mut bob_client = replaying_client(&bob_trace, server_addr, &bob, &root_server);
+        match bob_client
+            .get_record(GetRequestType::Data, &at_key)
+            .unwrap()
+        {
+            GetResponseType::Data(AtValue::Text(text)) => assert_eq!(text, "hello bob"),
+            other => panic!("Unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_root_server_response() {
+        let at_sign = AtSign::new(String::from("alice"));
+        let addr =
+            parse_root_server_response(&at_sign, b"@abc123.swarm0001.atsign.zone:1234\n").unwrap();
+        assert_eq!(addr.host, "abc123.swarm0001.atsign.zone");
+        assert_eq!(addr.port, 1234);
+    }
+
+    #[test]
+    fn test_parse_root_server_response_invalid() {
+        let at_sign = AtSign::new(String::from("alice"));
+        let result = parse_root_server_response(&at_sign, b"null\n");
+        assert!(matches!(result, Err(AtClientError::AtSignNotFound(_))));
+        let result = parse_root_server_response(&at_sign, b"@host:notaport\n");
+        assert!(matches!(
+            result,
+            Err(AtClientError::InvalidRootServerResponse(_))
+        ));
+    }
+}
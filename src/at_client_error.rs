@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fmt;
+
+use at_errors::AtError;
+use at_records::at_key::AtKeyError;
+
+pub type Result<T> = std::result::Result<T, AtClientError>;
+
+/// The errors returned by `AtClient` and `AsyncAtClient`.
+#[derive(Debug)]
+pub enum AtClientError {
+    /// Talking to the atServer or the root server failed, or a value could not be encrypted or decrypted.
+    AtError(AtError),
+    /// The root server does not know the atSign.
+    AtSignNotFound(String),
+    /// The root server answered with something other than `@host:port`.
+    InvalidRootServerResponse(String),
+    /// The atServer returned a key which isn't a valid `AtKey`.
+    InvalidAtKey(String, AtKeyError),
+}
+
+impl AtClientError {
+    /// Whether the same call might succeed later. See `AtError::is_retryable`.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AtClientError::AtError(error) => error.is_retryable(),
+            AtClientError::AtSignNotFound(_)
+            | AtClientError::InvalidRootServerResponse(_)
+            | AtClientError::InvalidAtKey(_, _) => false,
+        }
+    }
+
+    /// Whether retrying can't help. The opposite of `is_retryable`.
+    pub fn is_permanent(&self) -> bool {
+        !self.is_retryable()
+    }
+
+    /// Whether the atServer refused to authenticate the client, e.g. because the keys belong to another atSign.
+    pub fn is_auth_failure(&self) -> bool {
+        match self {
+            AtClientError::AtError(error) => error.is_auth_failure(),
+            _ => false,
+        }
+    }
+
+    /// Whether the requested key or atSign does not exist.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            AtClientError::AtError(error) => error.is_not_found(),
+            AtClientError::AtSignNotFound(_) => true,
+            AtClientError::InvalidRootServerResponse(_) | AtClientError::InvalidAtKey(_, _) => {
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for AtClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtClientError::AtError(error) => write!(f, "{}", error),
+            AtClientError::AtSignNotFound(at_sign) => {
+                write!(f, "The root server does not know {}", at_sign)
+            }
+            AtClientError::InvalidRootServerResponse(response) => {
+                write!(f, "Unexpected response from root server: {}", response)
+            }
+            AtClientError::InvalidAtKey(key, error) => write!(f, "Invalid key {}: {}", key, error),
+        }
+    }
+}
+
+impl Error for AtClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtClientError::AtError(error) => Some(error),
+            AtClientError::InvalidAtKey(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<AtError> for AtClientError {
+    fn from(error: AtError) -> Self {
+        AtClientError::AtError(error)
+    }
+}
+
+impl From<std::io::Error> for AtClientError {
+    fn from(error: std::io::Error) -> Self {
+        AtClientError::AtError(error.into())
+    }
+}
+
+/// `AtChops` reports failures as `anyhow::Error`.
+impl From<anyhow::Error> for AtClientError {
+    fn from(error: anyhow::Error) -> Self {
+        AtClientError::AtError(AtError::CryptoError(error.into()))
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::at_client::AtClient;
+
+/// Keeps an `AtClient`'s connection open by sending `noop` from a background thread
+/// whenever the connection has been idle for `interval`, so the atServer doesn't close it as idle.
+///
+/// The client is shared behind a mutex, so the application can keep using it in between.
+/// Sending stops when the `KeepAlive` is dropped, or once the client itself has been dropped.
+/// Dropping waits for a `noop` already sent, which gives up after `NOOP_TIMEOUT` if the atServer has stalled.
+/// The keepalive doesn't reconnect: if a `noop` fails, the client reconnects when the application next uses it.
+pub struct KeepAlive {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    pub fn start(client: &Arc<Mutex<AtClient>>, interval: Duration) -> Self {
+        let client = Arc::downgrade(client);
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut wait = interval;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(wait) {
+                match keep_alive(&client, interval) {
+                    Some(next_wait) => wait = next_wait,
+                    None => break,
+                }
+            }
+            debug!("Keepalive stopped");
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// How long each read and write of a keepalive `noop` may take, however long the client's own timeouts are.
+pub const NOOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends a `noop` if the client has been idle for `interval`, and returns how long to wait before checking again.
+/// Returns `None` once the client has gone.
+fn keep_alive(client: &Weak<Mutex<AtClient>>, interval: Duration) -> Option<Duration> {
+    let client = client.upgrade()?;
+    let mut client = client.lock().ok()?;
+    let idle_for = client.idle_for();
+    if idle_for < interval {
+        return Some(interval - idle_for);
+    }
+    debug!("Connection idle for {:?}, sending noop", idle_for);
+    if let Err(error) = client.keep_alive_noop(NOOP_TIMEOUT) {
+        warn!("Keepalive failed: {}", error);
+    }
+    Some(interval)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::test_utils::{alice_client, start_servers};
+
+    #[test]
+    fn test_keep_alive_sends_noop_when_idle() {
+        let (root_server, at_server) = start_servers();
+        let client = Arc::new(Mutex::new(alice_client(&root_server)));
+
+        let noops = at_server.noop_events();
+        let keep_alive = KeepAlive::start(&client, Duration::from_millis(50));
+        for _ in 0..2 {
+            noops
+                .recv_timeout(Duration::from_secs(10))
+                .expect("No noop sent while idle");
+        }
+        drop(keep_alive);
+
+        // Stops once dropped, so the only noop is the client's own
+        let noop_count = at_server.noop_count();
+        client.lock().unwrap().ping().unwrap();
+        assert_eq!(at_server.noop_count(), noop_count + 1);
+    }
+
+    #[test]
+    fn test_keep_alive_noop_gives_up_on_a_stalled_server() {
+        let (root_server, at_server) = start_servers();
+        let mut client = alice_client(&root_server);
+        at_server.set_noop_delay(Duration::from_secs(5));
+
+        let start = Instant::now();
+        assert!(client.keep_alive_noop(Duration::from_millis(100)).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // The next call reconnects rather than reading the late reply
+        at_server.set_noop_delay(Duration::ZERO);
+        client.ping().unwrap();
+        client.info().unwrap();
+    }
+}
@@ -2,4 +2,15 @@
 //!
 //! This library is only a thin-wrapper around the atProtocol and does not provide any additional functionality.
 
+#[cfg(feature = "tokio")]
+pub mod async_at_client;
+
 pub mod at_client;
+pub mod at_client_error;
+pub mod keepalive;
+pub mod local_key_store;
+pub mod scan;
+mod shared_keys;
+pub mod sync;
+#[cfg(test)]
+mod test_utils;
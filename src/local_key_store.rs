@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use at_errors::AtError;
+use at_records::at_key::AtKey;
+use serde::{Deserialize, Serialize};
+
+use crate::at_client_error::Result;
+
+/// A value held locally, keyed by the key as the atServer stores it, e.g. `public:phone.wavi@alice`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalRecord {
+    pub value: String,
+    /// The atServer's commit ID for this value, or `None` if it has only been changed locally.
+    pub commit_id: Option<u64>,
+}
+
+/// A change made locally which hasn't been pushed to the atServer yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LocalChange {
+    Update(String),
+    Delete(String),
+}
+
+impl LocalChange {
+    pub fn key(&self) -> &str {
+        match self {
+            LocalChange::Update(key) | LocalChange::Delete(key) => key,
+        }
+    }
+}
+
+/// Where `AtClient::sync` keeps a local copy of an atSign's keys, so they can be read and changed while offline.
+///
+/// Holds the records, the local changes waiting to be pushed and the last commit ID pulled from the atServer.
+/// Applications change keys with `update` and `delete`, which record the change for the next sync.
+pub trait LocalKeyStore {
+    fn get(&self, key: &str) -> Result<Option<LocalRecord>>;
+
+    /// Every key held locally, in lexicographic order.
+    fn keys(&self) -> Result<Vec<String>>;
+
+    /// Stores the record without recording a local change.
+    fn put(&mut self, key: &str, record: LocalRecord) -> Result<()>;
+
+    /// Removes the record without recording a local change.
+    fn remove(&mut self, key: &str) -> Result<()>;
+
+    /// The local changes waiting to be pushed, oldest first.
+    fn uncommitted(&self) -> Result<Vec<LocalChange>>;
+
+    fn add_uncommitted(&mut self, change: LocalChange) -> Result<()>;
+
+    /// Removes the oldest waiting change equal to `change`, once it has been pushed.
+    fn remove_uncommitted(&mut self, change: &LocalChange) -> Result<()>;
+
+    /// The highest commit ID seen by the last sync, or `None` before the first sync.
+    fn last_synced_commit_id(&self) -> Result<Option<u64>>;
+
+    fn set_last_synced_commit_id(&mut self, commit_id: u64) -> Result<()>;
+
+    /// Changes the value of `at_key` locally. The next sync pushes it to the atServer.
+    fn update(&mut self, at_key: &AtKey, value: &str) -> Result<()> {
+        let key = at_key.full_key();
+        let record = LocalRecord {
+            value: value.to_owned(),
+            commit_id: None,
+        };
+        self.put(&key, record)?;
+        self.add_uncommitted(LocalChange::Update(key))
+    }
+
+    /// Deletes `at_key` locally. The next sync deletes it from the atServer.
+    fn delete(&mut self, at_key: &AtKey) -> Result<()> {
+        let key = at_key.full_key();
+        self.remove(&key)?;
+        self.add_uncommitted(LocalChange::Delete(key))
+    }
+}
+
+/// A `LocalKeyStore` which lasts as long as the process.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InMemoryKeyStore {
+    records: BTreeMap<String, LocalRecord>,
+    uncommitted: VecDeque<LocalChange>,
+    last_synced_commit_id: Option<u64>,
+}
+
+impl InMemoryKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LocalKeyStore for InMemoryKeyStore {
+    fn get(&self, key: &str) -> Result<Option<LocalRecord>> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.records.keys().cloned().collect())
+    }
+
+    fn put(&mut self, key: &str, record: LocalRecord) -> Result<()> {
+        self.records.insert(key.to_owned(), record);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.records.remove(key);
+        Ok(())
+    }
+
+    fn uncommitted(&self) -> Result<Vec<LocalChange>> {
+        Ok(self.uncommitted.iter().cloned().collect())
+    }
+
+    fn add_uncommitted(&mut self, change: LocalChange) -> Result<()> {
+        self.uncommitted.push_back(change);
+        Ok(())
+    }
+
+    fn remove_uncommitted(&mut self, change: &LocalChange) -> Result<()> {
+        if let Some(index) = self.uncommitted.iter().position(|c| c == change) {
+            self.uncommitted.remove(index);
+        }
+        Ok(())
+    }
+
+    fn last_synced_commit_id(&self) -> Result<Option<u64>> {
+        Ok(self.last_synced_commit_id)
+    }
+
+    fn set_last_synced_commit_id(&mut self, commit_id: u64) -> Result<()> {
+        self.last_synced_commit_id = Some(commit_id);
+        Ok(())
+    }
+}
+
+/// A `LocalKeyStore` saved as JSON in a file.
+///
+/// The whole file is rewritten after every change, so it suits the small number of keys an atSign usually has.
+///
+/// The file holds the plain text values of every key, including self and shared keys which `AtClient::sync`
+/// decrypts, so on Unix it is only readable and writable by its owner.
+#[derive(Debug)]
+pub struct FileKeyStore {
+    path: PathBuf,
+    store: InMemoryKeyStore,
+}
+
+impl FileKeyStore {
+    /// Opens the store saved at `path`, or starts an empty one if the file doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let store = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(AtError::from)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => InMemoryKeyStore::new(),
+            Err(error) => return Err(AtError::from(error).into()),
+        };
+        Ok(Self { path, store })
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string(&self.store).map_err(AtError::from)?;
+        // Write then rename, so a crash part way through doesn't lose the store
+        let temp_path = self.path.with_extension("tmp");
+        write_private(&temp_path, json.as_bytes()).map_err(AtError::from)?;
+        fs::rename(&temp_path, &self.path).map_err(AtError::from)?;
+        Ok(())
+    }
+}
+
+/// Writes `contents` to `path`, which only its owner may read and write on Unix.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, not one left behind by an earlier crash
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+impl LocalKeyStore for FileKeyStore {
+    fn get(&self, key: &str) -> Result<Option<LocalRecord>> {
+        self.store.get(key)
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        self.store.keys()
+    }
+
+    fn put(&mut self, key: &str, record: LocalRecord) -> Result<()> {
+        self.store.put(key, record)?;
+        self.save()
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.store.remove(key)?;
+        self.save()
+    }
+
+    fn uncommitted(&self) -> Result<Vec<LocalChange>> {
+        self.store.uncommitted()
+    }
+
+    fn add_uncommitted(&mut self, change: LocalChange) -> Result<()> {
+        self.store.add_uncommitted(change)?;
+        self.save()
+    }
+
+    fn remove_uncommitted(&mut self, change: &LocalChange) -> Result<()> {
+        self.store.remove_uncommitted(change)?;
+        self.save()
+    }
+
+    fn last_synced_commit_id(&self) -> Result<Option<u64>> {
+        self.store.last_synced_commit_id()
+    }
+
+    fn set_last_synced_commit_id(&mut self, commit_id: u64) -> Result<()> {
+        self.store.set_last_synced_commit_id(commit_id)?;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_update_and_delete_record_changes() {
+        let mut store = InMemoryKeyStore::new();
+        let at_key = AtKey::from_str("public:phone.test@alice").unwrap();
+        store.update(&at_key, "123").unwrap();
+        store.delete(&at_key).unwrap();
+        store.update(&at_key, "456").unwrap();
+
+        assert_eq!(
+            store.get("public:phone.test@alice").unwrap(),
+            Some(LocalRecord {
+                value: String::from("456"),
+                commit_id: None
+            })
+        );
+        let key = String::from("public:phone.test@alice");
+        assert_eq!(
+            store.uncommitted().unwrap(),
+            vec![
+                LocalChange::Update(key.clone()),
+                LocalChange::Delete(key.clone()),
+                LocalChange::Update(key.clone())
+            ]
+        );
+        store
+            .remove_uncommitted(&LocalChange::Update(key.clone()))
+            .unwrap();
+        assert_eq!(
+            store.uncommitted().unwrap(),
+            vec![LocalChange::Delete(key.clone()), LocalChange::Update(key)]
+        );
+    }
+
+    #[test]
+    fn test_file_key_store_reopens() {
+        let path = std::env::temp_dir().join(format!(
+            "at_rust_file_key_store_{}.json",
+            std::process::id()
+        ));
+        let at_key = AtKey::from_str("phone.test@alice").unwrap();
+        {
+            let mut store = FileKeyStore::open(&path).unwrap();
+            assert!(store.keys().unwrap().is_empty());
+            store.update(&at_key, "123").unwrap();
+            store.set_last_synced_commit_id(7).unwrap();
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let store = FileKeyStore::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(store.get("phone.test@alice").unwrap().unwrap().value, "123");
+        assert_eq!(store.uncommitted().unwrap().len(), 1);
+        assert_eq!(store.last_synced_commit_id().unwrap(), Some(7));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+
+use at_errors::ServerErrorCode;
+use at_records::at_key::AtKey;
+use at_sign::AtSign;
+use at_verbs::scan_verb::{ScanVerbInputs, ScanVerbOutput};
+use log::debug;
+
+use crate::at_client::AtClient;
+use crate::at_client_error::{AtClientError, Result};
+
+/// Which keys to scan for with `AtClient::scan_with_options` and `AtClient::scan_iter`.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Include hidden keys, e.g. internal keys starting with `_`.
+    pub show_hidden: bool,
+
+    /// Only keys shared with this atSign.
+    pub for_at_sign: Option<AtSign>,
+
+    /// Only keys matching this regex, e.g. from `AtKeyFilter::to_regex`.
+    pub regex: Option<String>,
+}
+
+impl ScanOptions {
+    pub(crate) fn to_inputs(&self, page: Option<usize>) -> ScanVerbInputs {
+        let inputs = ScanVerbInputs::new(
+            self.show_hidden,
+            self.for_at_sign.clone(),
+            self.regex.clone(),
+        );
+        match page {
+            Some(page) => inputs.with_page(page),
+            None => inputs,
+        }
+    }
+}
+
+/// Iterates over the keys of a scan, fetching one page at a time from the atServer.
+///
+/// atServers which can't page scans are asked for every key at once instead.
+/// Keys which aren't valid `AtKey`s are returned as `AtClientError::InvalidAtKey` and iteration carries on.
+/// Any other error ends the iteration.
+pub struct ScanIter<'a> {
+    client: &'a mut AtClient,
+    options: ScanOptions,
+    /// The next page to ask for, or `None` once every key has been fetched.
+    next_page: Option<usize>,
+    /// Every key of the last page, valid or not, to notice an atServer which ignores the page and returns every key each time.
+    last_page: Vec<String>,
+    fetched: VecDeque<Result<AtKey>>,
+}
+
+impl<'a> ScanIter<'a> {
+    pub(crate) fn new(client: &'a mut AtClient, options: ScanOptions) -> Self {
+        Self {
+            client,
+            options,
+            next_page: Some(1),
+            last_page: vec![],
+            fetched: VecDeque::new(),
+        }
+    }
+
+    fn push_output(&mut self, output: ScanVerbOutput) {
+        self.fetched.extend(output.keys.into_iter().map(Ok));
+        self.fetched.extend(
+            output
+                .invalid_keys
+                .into_iter()
+                .map(|(key, error)| Err(AtClientError::InvalidAtKey(key, error))),
+        );
+    }
+
+    /// Fetches the next page, or every key if the atServer can't page scans.
+    fn fetch(&mut self, page: usize) -> Result<()> {
+        match self.client.scan_page(&self.options, Some(page)) {
+            Ok(output) => {
+                let page_keys: Vec<String> = output
+                    .keys
+                    .iter()
+                    .map(|key| key.full_key())
+                    .chain(output.invalid_keys.iter().map(|(key, _)| key.clone()))
+                    .collect();
+                if output.is_empty() || (page > 1 && page_keys == self.last_page) {
+                    self.next_page = None;
+                } else {
+                    self.last_page = page_keys;
+                    self.next_page = Some(page + 1);
+                    self.push_output(output);
+                }
+                Ok(())
+            }
+            Err(error)
+                if page == 1 && error.server_code() == Some(&ServerErrorCode::InvalidSyntax) =>
+            {
+                debug!("atServer can't page scans, fetching every key at once");
+                self.next_page = None;
+                let output = self.client.scan_page(&self.options, None)?;
+                self.push_output(output);
+                Ok(())
+            }
+            Err(error) => {
+                self.next_page = None;
+                Err(error.into())
+            }
+        }
+    }
+}
+
+impl Iterator for ScanIter<'_> {
+    type Item = Result<AtKey>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(key) = self.fetched.pop_front() {
+                return Some(key);
+            }
+            let page = self.next_page?;
+            if let Err(error) = self.fetch(page) {
+                return Some(Err(error));
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! The keys and encryption steps for records shared with another atSign.
+//!
+//! Used by both `AtClient` and `AsyncAtClient`, which only differ in how they talk to the atServer.
+
+use at_chops::AtChops;
+use at_records::{
+    at_key::{AtKey, Visibility},
+    at_record::AtValue,
+};
+use at_sign::AtSign;
+use at_verbs::{
+    llookup_verb::LlookupVerbOutput, lookup_verb::LookupVerbOutput,
+    plookup_verb::PlookupVerbOutput, update_verb::UpdateOptions,
+};
+
+use crate::at_client::{unexpected_output, unsupported, GetResponseType};
+use crate::at_client_error::Result;
+
+/// How long the recipient's atServer may cache their copy of a shared symmetric key, in seconds.
+const RECIPIENT_COPY_TTR: usize = 86400;
+
+/// The atSign `at_key` is shared with. Putting and getting records is only supported for shared keys so far.
+pub(crate) fn shared_with(at_key: &AtKey) -> Result<&AtSign> {
+    match &at_key.visibility_scope {
+        Visibility::Shared(shared_with) => Ok(shared_with),
+        _ => Err(unsupported(
+            "Putting keys which are not shared with another atSign",
+        )),
+    }
+}
+
+/// Checks that `at_key` is one `get_record` supports, which so far is only keys shared with us.
+pub(crate) fn check_gettable(at_key: &AtKey) -> Result<()> {
+    match &at_key.visibility_scope {
+        Visibility::Shared(_) => Ok(()),
+        Visibility::Public | Visibility::Private | Visibility::Internal => {
+            Err(unsupported("Getting keys which are not shared with us"))
+        }
+    }
+}
+
+/// The text of a value to put. Binary values aren't supported yet.
+pub(crate) fn text_to_put(data: &AtValue) -> Result<&str> {
+    match data {
+        AtValue::Text(text) => Ok(text),
+        _ => Err(unsupported("Putting binary values")),
+    }
+}
+
+/// `shared_key.<shared_with>@<owner>`, the owner's copy of the symmetric key it shares with `shared_with`,
+/// encrypted with the owner's public key.
+pub(crate) fn owner_copy_key(owner: &AtSign, shared_with: &AtSign) -> AtKey {
+    AtKey::new_private_key(
+        String::from("shared_key"),
+        Some(shared_with.get_at_sign_without_prefix()),
+        owner.clone(),
+    )
+}
+
+/// `@<shared_with>:shared_key@<owner>`, the recipient's copy of the symmetric key `owner` shares with them,
+/// encrypted with their public key. They find it with `lookup:shared_key@<owner>`.
+pub(crate) fn recipient_copy_key(owner: &AtSign, shared_with: &AtSign) -> AtKey {
+    AtKey {
+        record_id: String::from("shared_key"),
+        namespace: None,
+        is_cached: false,
+        owner: owner.clone(),
+        visibility_scope: Visibility::Shared(shared_with.clone()),
+    }
+}
+
+/// `public:publickey@<at_sign>`, the public key others encrypt the symmetric keys they share with `at_sign` with.
+pub(crate) fn public_key(at_sign: &AtSign) -> AtKey {
+    AtKey {
+        record_id: String::from("publickey"),
+        namespace: None,
+        is_cached: false,
+        owner: at_sign.clone(),
+        visibility_scope: Visibility::Public,
+    }
+}
+
+/// The options to update the recipient's copy of a shared symmetric key with.
+pub(crate) fn recipient_copy_options() -> UpdateOptions {
+    UpdateOptions::new(None, None, Some(RECIPIENT_COPY_TTR), None)
+}
+
+/// Creates a new symmetric key to share, returning it with the owner's copy to save under `owner_copy_key`.
+pub(crate) fn new_shared_key(at_chops: &AtChops) -> Result<(String, AtValue)> {
+    let symm_key = at_chops.create_new_shared_symmetric_key()?;
+    let owner_copy = at_chops.encrypt_data_with_our_public_key(&symm_key)?;
+    Ok((symm_key, AtValue::Text(owner_copy)))
+}
+
+/// The recipient's copy of `symm_key` to save under `recipient_copy_key`,
+/// given the `plookup` of their `public_key`.
+pub(crate) fn recipient_copy(
+    at_chops: &AtChops,
+    their_public_key: PlookupVerbOutput,
+    symm_key: &str,
+) -> Result<AtValue> {
+    let their_public_key = match their_public_key {
+        PlookupVerbOutput::Data(AtValue::Text(text)) => text,
+        other => return Err(unexpected_output(other)),
+    };
+    let recipient_copy = at_chops.encrypt_data_with_public_key(&their_public_key, symm_key)?;
+    Ok(AtValue::Text(recipient_copy))
+}
+
+/// Decrypts the owner's copy of a shared symmetric key, given the `llookup` of `owner_copy_key`.
+pub(crate) fn decrypt_owner_copy(
+    at_chops: &AtChops,
+    owner_copy: LlookupVerbOutput,
+) -> Result<String> {
+    match owner_copy {
+        LlookupVerbOutput::Data(AtValue::Text(encrypted_symm_key)) => {
+            Ok(at_chops.decrypt_symmetric_key(&encrypted_symm_key)?)
+        }
+        other => Err(unexpected_output(other)),
+    }
+}
+
+/// Decrypts our copy of a symmetric key shared with us, given the `lookup` of `recipient_copy_key`.
+pub(crate) fn decrypt_recipient_copy(
+    at_chops: &AtChops,
+    recipient_copy: LookupVerbOutput,
+) -> Result<String> {
+    match recipient_copy {
+        LookupVerbOutput::Data(encrypted_symm_key) => {
+            Ok(at_chops.decrypt_symmetric_key(&encrypted_symm_key)?)
+        }
+        other => Err(unexpected_output(other)),
+    }
+}
+
+/// Encrypts the text of a record shared with another atSign with the symmetric key shared with them.
+pub(crate) fn encrypt_shared_value(
+    at_chops: &AtChops,
+    symm_key: &str,
+    text: &str,
+) -> Result<AtValue> {
+    let encrypted = at_chops.encrypt_data_with_shared_symmetric_key(symm_key, text)?;
+    Ok(AtValue::Text(encrypted))
+}
+
+/// What `get_record` returns for the `lookup` of a record shared with us, decrypted with the symmetric key shared with us.
+pub(crate) fn decrypt_shared_record(
+    at_chops: &AtChops,
+    symm_key: &str,
+    record: LookupVerbOutput,
+) -> Result<GetResponseType> {
+    match record {
+        LookupVerbOutput::Data(data) => {
+            let data = at_chops.decrypt_data_with_shared_symmetric_key(symm_key, &data)?;
+            Ok(GetResponseType::Data(AtValue::Text(
+                data.trim().to_string(),
+            )))
+        }
+        LookupVerbOutput::Metadata(_) | LookupVerbOutput::All(_) => {
+            Err(unsupported("Getting metadata"))
+        }
+    }
+}
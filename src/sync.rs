@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use at_records::{at_key::AtKey, at_record::AtValue};
+use at_sign::AtSign;
+use at_verbs::{
+    delete_verb::{DeleteVerb, DeleteVerbInputs},
+    stats_verb::StatsVerb,
+    sync_verb::{SyncEntry, SyncOperation, SyncVerb, SyncVerbInputs},
+    update_verb::{UpdateVerb, UpdateVerbInputs},
+    verb_trait::Verb,
+};
+use log::{debug, info, warn};
+
+use crate::at_client::{unexpected_output, AtClient};
+use crate::at_client_error::{AtClientError, Result};
+use crate::local_key_store::{LocalChange, LocalKeyStore, LocalRecord};
+
+/// How many commits to ask for with each `sync` verb.
+const SYNC_PAGE_LIMIT: usize = 100;
+
+/// What `AtClient::sync` did.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncReport {
+    /// How many local changes were pushed to the atServer.
+    pub pushed: usize,
+    /// How many remote changes were applied to the local store.
+    pub pulled: usize,
+    /// Keys changed both locally and remotely, where the remote change was overwritten by the later local one.
+    pub conflicts: Vec<String>,
+}
+
+/// Pushes `store`'s local changes to the atServer, then pulls the atServer's changes into `store`.
+///
+/// Conflicts are resolved by last write wins, where later means a higher commit ID.
+/// Pushing first gives local changes higher commit IDs than the remote changes they conflict with,
+/// so a remote change is only applied if the atServer committed it after the local value.
+///
+/// `store` holds plain text values, which are encrypted on the atServer with `AtClient::encrypt_value`.
+/// Pushing a key it can't encrypt fails, and pulling one is skipped.
+pub(crate) fn sync(client: &mut AtClient, store: &mut dyn LocalKeyStore) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let last_synced = store.last_synced_commit_id()?;
+    // The symmetric keys we share with each atSign, so each is only looked up once
+    let mut shared_keys = HashMap::new();
+
+    let pushed = push(client, store, &mut shared_keys, &mut report)?;
+
+    let last_commit_id =
+        client.execute_with_reconnect(true, |tls_client| StatsVerb::execute(tls_client, ()))?;
+    if last_commit_id <= last_synced {
+        debug!("Nothing to pull since commit {:?}", last_synced);
+        return Ok(report);
+    }
+
+    let mut from_commit_id = last_synced;
+    loop {
+        let entries = client.execute_with_reconnect(true, |tls_client| {
+            let sync_verb_args = SyncVerbInputs::new(from_commit_id).with_limit(SYNC_PAGE_LIMIT);
+            SyncVerb::execute(tls_client, sync_verb_args)
+        })?;
+        let page_size = entries.len();
+        for entry in entries {
+            from_commit_id = from_commit_id.max(Some(entry.commit_id));
+            pull(client, store, &mut shared_keys, &pushed, entry, &mut report)?;
+        }
+        if let Some(commit_id) = from_commit_id {
+            store.set_last_synced_commit_id(commit_id)?;
+        }
+        if page_size < SYNC_PAGE_LIMIT {
+            break;
+        }
+    }
+    info!(
+        "Synced: pushed {}, pulled {}, {} conflicts",
+        report.pushed,
+        report.pulled,
+        report.conflicts.len()
+    );
+    Ok(report)
+}
+
+/// Pushes the local changes, oldest first, and returns the commit IDs the atServer gave each key, oldest first.
+fn push(
+    client: &mut AtClient,
+    store: &mut dyn LocalKeyStore,
+    shared_keys: &mut HashMap<AtSign, String>,
+    report: &mut SyncReport,
+) -> Result<HashMap<String, Vec<u64>>> {
+    let mut pushed = HashMap::new();
+    for change in store.uncommitted()? {
+        let key = change.key();
+        let at_key = AtKey::from_str(key)
+            .map_err(|error| AtClientError::InvalidAtKey(key.to_owned(), error))?;
+        let commit_id = match &change {
+            LocalChange::Update(_) => {
+                // A later local change replaced or deleted the value, and will be pushed itself
+                let Some(record) = store.get(key)?.filter(|record| record.commit_id.is_none())
+                else {
+                    store.remove_uncommitted(&change)?;
+                    continue;
+                };
+                let value =
+                    AtValue::Text(client.encrypt_value(&at_key, &record.value, shared_keys)?);
+                let response = client.execute_with_reconnect(false, |tls_client| {
+                    UpdateVerb::execute(tls_client, UpdateVerbInputs::new(&at_key, &value))
+                })?;
+                let commit_id = parse_commit_id(&response)?;
+                if !has_later_change(store, &change)? {
+                    store.put(
+                        key,
+                        LocalRecord {
+                            commit_id: Some(commit_id),
+                            ..record
+                        },
+                    )?;
+                }
+                commit_id
+            }
+            LocalChange::Delete(_) => {
+                let result = client.execute_with_reconnect(false, |tls_client| {
+                    DeleteVerb::execute(tls_client, DeleteVerbInputs::new(&at_key))
+                });
+                match result {
+                    Ok(response) => parse_commit_id(&response)?,
+                    // Never pushed, or already deleted remotely
+                    Err(error) if error.is_not_found() => {
+                        store.remove_uncommitted(&change)?;
+                        continue;
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        };
+        debug!("Pushed {:?} as commit {}", change, commit_id);
+        store.remove_uncommitted(&change)?;
+        pushed
+            .entry(key.to_owned())
+            .or_insert_with(Vec::new)
+            .push(commit_id);
+        report.pushed += 1;
+    }
+    Ok(pushed)
+}
+
+/// Whether another change to the same key is waiting behind `change`.
+fn has_later_change(store: &dyn LocalKeyStore, change: &LocalChange) -> Result<bool> {
+    Ok(store
+        .uncommitted()?
+        .iter()
+        .filter(|other| other.key() == change.key())
+        .count()
+        > 1)
+}
+
+/// Applies a remote change unless the local value was committed later.
+fn pull(
+    client: &mut AtClient,
+    store: &mut dyn LocalKeyStore,
+    shared_keys: &mut HashMap<AtSign, String>,
+    pushed: &HashMap<String, Vec<u64>>,
+    entry: SyncEntry,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let local = store.get(&entry.key)?;
+    let pushed = pushed.get(&entry.key).map_or(&[][..], Vec::as_slice);
+    let local_commit_id = local
+        .as_ref()
+        .and_then(|record| record.commit_id)
+        .max(pushed.last().copied());
+    if local_commit_id >= Some(entry.commit_id) {
+        let overwritten = !pushed.is_empty() && !pushed.contains(&entry.commit_id);
+        if overwritten && !report.conflicts.contains(&entry.key) {
+            debug!(
+                "Local change to {} wins over commit {}",
+                entry.key, entry.commit_id
+            );
+            report.conflicts.push(entry.key);
+        }
+        return Ok(());
+    }
+    match entry.operation {
+        SyncOperation::Update => {
+            // Deleted since, which a later entry says
+            let Some(value) = entry.value else {
+                return Ok(());
+            };
+            let value = match decrypt(client, &entry.key, &value, shared_keys) {
+                Ok(value) => value,
+                Err(error) => {
+                    warn!("Not pulling {}: {}", entry.key, error);
+                    return Ok(());
+                }
+            };
+            let record = LocalRecord {
+                value,
+                commit_id: Some(entry.commit_id),
+            };
+            store.put(&entry.key, record)?;
+        }
+        SyncOperation::UpdateMeta => {
+            // Metadata isn't held locally, so only the commit ID changes
+            if let Some(record) = local {
+                let record = LocalRecord {
+                    commit_id: Some(entry.commit_id),
+                    ..record
+                };
+                store.put(&entry.key, record)?;
+            }
+        }
+        SyncOperation::Delete => store.remove(&entry.key)?,
+    }
+    report.pulled += 1;
+    Ok(())
+}
+
+/// Decrypts the value of a pulled key.
+fn decrypt(
+    client: &mut AtClient,
+    key: &str,
+    value: &str,
+    shared_keys: &mut HashMap<AtSign, String>,
+) -> Result<String> {
+    let at_key =
+        AtKey::from_str(key).map_err(|error| AtClientError::InvalidAtKey(key.to_owned(), error))?;
+    client.decrypt_value(&at_key, value, shared_keys)
+}
+
+fn parse_commit_id(response: &str) -> Result<u64> {
+    response.parse().map_err(|_| unexpected_output(response))
+}
@@ -0,0 +1,54 @@
+//! Helpers for tests which run clients against the in-process mock servers.
+
+use at_mock_server::{fixtures, MockAtServer, MockRootServer};
+use at_tls::retry_policy::RetryPolicy;
+
+use crate::at_client::{AtClient, AtClientOptions};
+
+/// A root server and an atServer serving @alice and @bob.
+pub(crate) fn start_servers() -> (MockRootServer, MockAtServer) {
+    let root_server = MockRootServer::start();
+    let at_server = MockAtServer::start();
+    for test_at_sign in [fixtures::alice(), fixtures::bob()] {
+        at_server.register_at_sign(&test_at_sign.at_sign, test_at_sign.pkam_public_key);
+        at_server.put(
+            &format!("public:publickey{}", test_at_sign.at_sign),
+            test_at_sign.encrypt_public_key,
+        );
+        root_server.register(&test_at_sign.at_sign, &at_server.address());
+    }
+    (root_server, at_server)
+}
+
+/// A root server, and separate atServers for @alice and @bob, for tests of one atSign talking to another's atServer.
+/// Each atServer uses the root server to check pol proofs.
+pub(crate) fn start_separate_servers() -> (MockRootServer, MockAtServer, MockAtServer) {
+    let root_server = MockRootServer::start();
+    let [alice_server, bob_server] = [fixtures::alice(), fixtures::bob()].map(|test_at_sign| {
+        let at_server = MockAtServer::start();
+        at_server.register_at_sign(&test_at_sign.at_sign, test_at_sign.pkam_public_key);
+        at_server.put(
+            &format!("public:publickey{}", test_at_sign.at_sign),
+            test_at_sign.encrypt_public_key,
+        );
+        root_server.register(&test_at_sign.at_sign, &at_server.address());
+        at_server.set_root_server(root_server.address());
+        at_server
+    });
+    (root_server, alice_server, bob_server)
+}
+
+/// Options which trust the mock servers and find atServers using `root_server`.
+pub(crate) fn options(root_server: &MockRootServer) -> AtClientOptions {
+    let mut options =
+        AtClientOptions::new(at_mock_server::connection_config(), RetryPolicy::no_retry());
+    options.root_server = root_server.address();
+    options
+}
+
+/// An `AtClient` authenticated as @alice, using the atServer found through `root_server`.
+pub(crate) fn alice_client(root_server: &MockRootServer) -> AtClient {
+    let alice = fixtures::alice();
+    AtClient::init_with_options(alice.secrets(), alice.at_sign, options(root_server))
+        .expect("Failed to authenticate as @alice")
+}